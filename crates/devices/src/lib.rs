@@ -15,7 +15,7 @@ pub mod utils;
 pub use fdt_parser as fdt;
 
 use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
-use device::{BlkDriver, DeviceSet, Driver, IntDriver, NetDriver, UartDriver};
+use device::{BlkDriver, DeviceSet, Driver, IntDriver, NetDriver, RtcDriver, UartDriver};
 use fdt_parser::Node;
 pub use linkme::{self, distributed_slice as linker_use};
 pub use polyhal::{consts::VIRT_ADDR_START, pagetable::PAGE_SIZE};
@@ -48,6 +48,11 @@ pub fn get_blk_devices() -> Vec<Arc<dyn BlkDriver>> {
     ALL_DEVICES.lock().blk.clone()
 }
 
+#[inline]
+pub fn get_rtc_device() -> Option<Arc<dyn RtcDriver>> {
+    ALL_DEVICES.lock().rtc.first().cloned()
+}
+
 #[inline]
 pub fn get_int_device() -> Arc<dyn IntDriver> {
     INT_DEVICE.try_get().expect("can't find int device").clone()
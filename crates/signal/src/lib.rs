@@ -146,6 +146,48 @@ impl SignalFlags {
     pub fn real_time_index(&self) -> Option<usize> {
         self.is_real_time().then(|| self.num() - 32)
     }
+
+    /// The default action of the signal is stopping the process.
+    #[inline]
+    pub fn is_stop(&self) -> bool {
+        self.default_action() == SigDefault::Stop
+    }
+
+    /// The action taken when the handler of the signal is SIG_DFL.
+    /// The real time signals terminate the process.
+    pub fn default_action(&self) -> SigDefault {
+        match *self {
+            Self::SIGQUIT
+            | Self::SIGILL
+            | Self::SIGTRAP
+            | Self::SIGABRT
+            | Self::SIGBUS
+            | Self::SIGFPE
+            | Self::SIGSEGV
+            | Self::SIGXCPU
+            | Self::SIGXFSZ
+            | Self::SIGSYS => SigDefault::Core,
+            Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU => SigDefault::Stop,
+            Self::SIGCONT => SigDefault::Cont,
+            Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH => SigDefault::Ign,
+            _ => SigDefault::Term,
+        }
+    }
+}
+
+/// The default disposition of the signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigDefault {
+    /// Terminate the process.
+    Term,
+    /// Terminate the process and dump the core.
+    Core,
+    /// Stop the process until SIGCONT.
+    Stop,
+    /// Continue the process if it is stopped.
+    Cont,
+    /// Ignore the signal.
+    Ign,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -16,7 +16,10 @@ mod tty;
 mod urandom;
 mod zero;
 
-pub use {sdx::Sdx, tty::Tty};
+pub use {
+    sdx::Sdx,
+    tty::{set_job_control, set_signal_pgrp, Tty},
+};
 
 pub struct DevFS {
     root_dir: Arc<DevDir>,
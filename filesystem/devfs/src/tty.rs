@@ -5,9 +5,33 @@ use bitflags::bitflags;
 use devices::utils::{get_char, puts};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use sync::Mutex;
+use sync::{LazyInit, Mutex};
 use syscalls::Errno;
 use vfscore::{INodeInterface, PollEvent, Stat, StatMode, VfsResult};
+
+/// Send the signal to the process group, (pgid, signum).
+/// The tty can't access the tasks, so the kernel sets it.
+static SIGNAL_PGRP: LazyInit<fn(usize, usize)> = LazyInit::new();
+
+pub fn set_signal_pgrp(f: fn(usize, usize)) {
+    SIGNAL_PGRP.init_by(f);
+}
+
+/// Check the caller is in the foreground process group, (pgid, signum).
+/// The background caller gets the signal, the kernel sets it too.
+static JOB_CONTROL: LazyInit<fn(usize, usize) -> VfsResult<()>> = LazyInit::new();
+
+pub fn set_job_control(f: fn(usize, usize) -> VfsResult<()>) {
+    JOB_CONTROL.init_by(f);
+}
+
+const SIGTTIN: usize = 21;
+const SIGTTOU: usize = 22;
+
+const VINTR: usize = 0;
+const VQUIT: usize = 1;
+const VSUSP: usize = 10;
+
 pub struct Tty {
     buffer: Mutex<VecDeque<u8>>,
     termios: Mutex<Termios>,
@@ -24,11 +48,45 @@ impl Tty {
             winsize: Default::default(),
         }
     }
+
+    /// Handle the special chars if ISIG is set, Ctrl-C, Ctrl-\ and Ctrl-Z
+    /// send the signal to the foreground process group and are dropped.
+    fn input_char(&self, c: u8) -> Option<u8> {
+        let termios = *self.termios.lock();
+        if !LocalModes::from_bits_truncate(termios.lflag).contains(LocalModes::ISIG) {
+            return Some(c);
+        }
+        let signum = match c {
+            _ if c == termios.cc[VINTR] => 2,  // SIGINT
+            _ if c == termios.cc[VQUIT] => 3,  // SIGQUIT
+            _ if c == termios.cc[VSUSP] => 20, // SIGTSTP
+            _ => return Some(c),
+        };
+        // Keep the char if there isn't a foreground process group.
+        let pgid = *self.pgid.lock() as usize;
+        match SIGNAL_PGRP.try_get() {
+            Some(signal_pgrp) if pgid != 0 => {
+                signal_pgrp(pgid, signum);
+                None
+            }
+            _ => Some(c),
+        }
+    }
+
+    /// The background process group can't read, and can't write if TOSTOP is set.
+    fn job_control(&self, signum: usize) -> VfsResult<()> {
+        let pgid = *self.pgid.lock() as usize;
+        match JOB_CONTROL.try_get() {
+            Some(job_control) if pgid != 0 => job_control(pgid, signum),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl INodeInterface for Tty {
     fn readat(&self, _offset: usize, buffer: &mut [u8]) -> vfscore::VfsResult<usize> {
         assert!(buffer.len() > 0);
+        self.job_control(SIGTTIN)?;
         let mut self_buffer = self.buffer.lock();
         if self_buffer.len() > 0 {
             let rlen = cmp::min(buffer.len(), self_buffer.len());
@@ -37,7 +95,7 @@ impl INodeInterface for Tty {
             }
             Ok(rlen)
         } else {
-            if let Some(c) = get_char() {
+            if let Some(c) = get_char().and_then(|c| self.input_char(c)) {
                 buffer[0] = c as u8;
                 Ok(1)
             } else {
@@ -61,6 +119,10 @@ impl INodeInterface for Tty {
     }
 
     fn writeat(&self, _offset: usize, buffer: &[u8]) -> vfscore::VfsResult<usize> {
+        let lflag = self.termios.lock().lflag;
+        if LocalModes::from_bits_truncate(lflag).contains(LocalModes::TOSTOP) {
+            self.job_control(SIGTTOU)?;
+        }
         puts(buffer);
         Ok(buffer.len())
     }
@@ -72,7 +134,7 @@ impl INodeInterface for Tty {
             if buf_len > 0 {
                 res |= PollEvent::POLLIN;
             } else {
-                if let Some(c) = get_char() {
+                if let Some(c) = get_char().and_then(|c| self.input_char(c)) {
                     res |= PollEvent::POLLIN;
                     self.buffer.lock().push_back(c);
                 }
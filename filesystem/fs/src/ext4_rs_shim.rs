@@ -109,6 +109,10 @@ impl FileSystem for Ext4FileSystem {
     fn name(&self) -> &str {
         "ext4"
     }
+
+    fn has_modes(&self) -> bool {
+        true
+    }
 }
 
 unsafe impl Sync for Ext4FileSystem {}
@@ -262,8 +266,8 @@ impl INodeInterface for Ext4FileWrapper {
     }
 
     fn stat(&self, stat: &mut vfscore::Stat) -> VfsResult<()> {
-        stat.ino = 1; // TODO: convert path to number(ino)
         let inodeif = self.ext4.get_inode_ref(self.inode);
+        stat.ino = self.inode as _;
         stat.mode = match inodeif.inode.file_type() {
             InodeFileType::S_IFREG => StatMode::FILE,
             InodeFileType::S_IFDIR => StatMode::DIR,
@@ -273,20 +277,20 @@ impl INodeInterface for Ext4FileWrapper {
             InodeFileType::S_IFCHR => StatMode::CHAR,
             InodeFileType::S_IFIFO => StatMode::FIFO,
             _ => unreachable!(),
-        };
+        } | StatMode::from_bits_truncate(inodeif.inode.mode() as u32 & 0o7777);
         stat.nlink = 1;
-        stat.uid = 0;
-        stat.gid = 0;
+        stat.uid = inodeif.inode.uid() as _;
+        stat.gid = inodeif.inode.gid() as _;
         stat.size = inodeif.inode.size();
         stat.blksize = 4096;
         stat.blocks = 0;
         stat.rdev = 0; // TODO: add device id
         stat.atime.nsec = 0;
-        stat.atime.sec = 0;
+        stat.atime.sec = inodeif.inode.atime() as _;
         stat.ctime.nsec = 0;
-        stat.ctime.sec = 0;
+        stat.ctime.sec = inodeif.inode.ctime() as _;
         stat.mtime.nsec = 0;
-        stat.mtime.sec = 0;
+        stat.mtime.sec = inodeif.inode.mtime() as _;
         Ok(())
     }
 
@@ -306,6 +310,22 @@ impl INodeInterface for Ext4FileWrapper {
     fn utimes(&self, _times: &mut [TimeSpec]) -> VfsResult<()> {
         Ok(())
     }
+
+    fn chown(&self, uid: u32, gid: u32) -> VfsResult<()> {
+        let mut inode_ref = self.ext4.get_inode_ref(self.inode);
+        inode_ref.inode.set_uid(uid as _);
+        inode_ref.inode.set_gid(gid as _);
+        self.ext4.write_back_inode(&mut inode_ref);
+        Ok(())
+    }
+
+    fn chmod(&self, mode: u32) -> VfsResult<()> {
+        let mut inode_ref = self.ext4.get_inode_ref(self.inode);
+        let mode = (inode_ref.inode.mode() as u32 & !0o7777) | (mode & 0o7777);
+        inode_ref.inode.set_mode(mode as _);
+        self.ext4.write_back_inode(&mut inode_ref);
+        Ok(())
+    }
 }
 
 #[inline(always)]
@@ -7,7 +7,11 @@ use alloc::{
 use core::iter::zip;
 use devices::get_blk_device;
 use lwext4_rust::{
-    bindings::{ext4_fsymlink, ext4_readlink, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY},
+    bindings::{
+        ext4_fsymlink, ext4_mode_get, ext4_mode_set, ext4_mtime_get, ext4_owner_get,
+        ext4_owner_set, ext4_raw_inode_fill, ext4_readlink, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC,
+        O_WRONLY,
+    },
     Ext4BlockWrapper, Ext4File, InodeTypes, KernelDevOp,
 };
 use sync::Mutex;
@@ -130,6 +134,10 @@ impl FileSystem for Ext4FileSystem {
     fn name(&self) -> &str {
         "ext4"
     }
+
+    fn has_modes(&self) -> bool {
+        true
+    }
 }
 
 pub struct Ext4FileWrapper {
@@ -384,7 +392,32 @@ impl INodeInterface for Ext4FileWrapper {
             file.file_open(path, O_RDONLY).map_err(map_ext4_err)?;
         }
 
-        stat.ino = 1; // TODO: 获取真正的 INode
+        // the inode number and the modify time of the path.
+        let path = file.get_path();
+        let mut ino = 0;
+        let mut inode = unsafe { core::mem::zeroed() };
+        let mut mtime = 0;
+        let mut mode = 0;
+        let (mut uid, mut gid) = (0, 0);
+        let ret = unsafe {
+            match ext4_raw_inode_fill(path.as_ptr(), &mut ino, &mut inode) {
+                0 => match ext4_mtime_get(path.as_ptr(), &mut mtime) {
+                    0 => match ext4_mode_get(path.as_ptr(), &mut mode) {
+                        0 => ext4_owner_get(path.as_ptr(), &mut uid, &mut gid),
+                        err => err,
+                    },
+                    err => err,
+                },
+                err => err,
+            }
+        };
+        if ret != 0 {
+            if self.file_type == FileType::File {
+                let _ = file.file_close();
+            }
+            return Err(map_ext4_err(ret));
+        }
+        stat.ino = ino as _;
         stat.mode = match file.get_type() {
             InodeTypes::EXT4_DE_REG_FILE => StatMode::FILE,
             InodeTypes::EXT4_DE_DIR => StatMode::DIR,
@@ -392,10 +425,10 @@ impl INodeInterface for Ext4FileWrapper {
             InodeTypes::EXT4_DE_SOCK => StatMode::SOCKET,
             InodeTypes::EXT4_DE_SYMLINK => StatMode::LINK,
             _ => unreachable!(),
-        };
+        } | StatMode::from_bits_truncate(mode & 0o7777);
         stat.nlink = 1;
-        stat.uid = 0;
-        stat.gid = 0;
+        stat.uid = uid;
+        stat.gid = gid;
         stat.size = file.file_size();
         stat.blksize = 512;
         stat.blocks = 0;
@@ -405,7 +438,7 @@ impl INodeInterface for Ext4FileWrapper {
         stat.ctime.nsec = 0;
         stat.ctime.sec = 0;
         stat.mtime.nsec = 0;
-        stat.mtime.sec = 0;
+        stat.mtime.sec = mtime as _;
 
         if self.file_type == FileType::File {
             let _ = file.file_close();
@@ -432,4 +465,20 @@ impl INodeInterface for Ext4FileWrapper {
         // Err(vfscore::VfsError::NotSupported)
         Ok(())
     }
+
+    fn chown(&self, uid: u32, gid: u32) -> VfsResult<()> {
+        let path = self.inner.lock().get_path();
+        match unsafe { ext4_owner_set(path.as_ptr(), uid, gid) } {
+            0 => Ok(()),
+            err => Err(map_ext4_err(err)),
+        }
+    }
+
+    fn chmod(&self, mode: u32) -> VfsResult<()> {
+        let path = self.inner.lock().get_path();
+        match unsafe { ext4_mode_set(path.as_ptr(), mode) } {
+            0 => Ok(()),
+            err => Err(map_ext4_err(err)),
+        }
+    }
 }
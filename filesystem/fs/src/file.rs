@@ -59,6 +59,11 @@ impl<'a> File {
         dir.remove(&self.path_buf.filename())
     }
 
+    /// The filesystem of the file stores the permission bits.
+    pub fn has_modes(&self) -> bool {
+        get_mounted(&self.path_buf).0.fs.has_modes()
+    }
+
     pub fn get_bare_file(&self) -> Arc<dyn INodeInterface> {
         self.inner.clone()
     }
@@ -210,6 +215,14 @@ impl File {
         self.inner.utimes(times)
     }
 
+    pub fn chown(&self, uid: u32, gid: u32) -> Result<(), Errno> {
+        self.inner.chown(uid, gid)
+    }
+
+    pub fn chmod(&self, mode: u32) -> Result<(), Errno> {
+        self.inner.chmod(mode)
+    }
+
     pub fn poll(&self, events: PollEvent) -> Result<PollEvent, Errno> {
         self.inner.poll(events)
     }
@@ -267,12 +280,12 @@ impl File {
             return Ok(0);
         }
         let offset = *self.offset.lock();
-        WaitBlockingWrite(self.inner.clone(), &buffer, offset)
-            .await
-            .map(|x| {
-                *self.offset.lock() += x;
-                x
-            })
+        if self.flags.lock().contains(OpenFlags::O_NONBLOCK) {
+            self.inner.writeat(offset, buffer)
+        } else {
+            WaitBlockingWrite(self.inner.clone(), buffer, offset).await
+        }
+        .inspect(|x| *self.offset.lock() += x)
     }
 
     pub fn seek(&self, seek_from: SeekFrom) -> Result<usize, Errno> {
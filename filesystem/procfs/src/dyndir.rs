@@ -0,0 +1,90 @@
+use alloc::{collections::BTreeMap, string::ToString, sync::Arc, vec::Vec};
+use sync::Mutex;
+use syscalls::Errno;
+use vfscore::{DirEntry, FileType, INodeInterface, StatMode, VfsResult};
+
+enum DynNode {
+    Dir(Arc<DynDir>),
+    Node(Arc<dyn INodeInterface>),
+}
+
+/// The directory whose nodes are registered at runtime.
+pub struct DynDir {
+    nodes: Mutex<BTreeMap<&'static str, DynNode>>,
+}
+
+impl DynDir {
+    pub const fn new() -> Self {
+        Self {
+            nodes: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Insert the node at the path, the parent directories are created
+    /// if they don't exist.
+    pub fn insert(&self, path: &'static str, node: Arc<dyn INodeInterface>) {
+        match path.split_once('/') {
+            Some((name, rest)) => {
+                let dir = match self
+                    .nodes
+                    .lock()
+                    .entry(name)
+                    .or_insert_with(|| DynNode::Dir(Arc::new(DynDir::new())))
+                {
+                    DynNode::Dir(dir) => dir.clone(),
+                    DynNode::Node(_) => panic!("procfs node {} is not a directory", name),
+                };
+                dir.insert(rest, node);
+            }
+            None => {
+                self.nodes.lock().insert(path, DynNode::Node(node));
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn INodeInterface>> {
+        self.nodes.lock().get(name).map(|x| match x {
+            DynNode::Dir(dir) => dir.clone() as Arc<dyn INodeInterface>,
+            DynNode::Node(node) => node.clone(),
+        })
+    }
+
+    pub fn entries(&self) -> Vec<DirEntry> {
+        self.nodes
+            .lock()
+            .iter()
+            .map(|(name, node)| DirEntry {
+                filename: name.to_string(),
+                len: 0,
+                file_type: match node {
+                    DynNode::Dir(_) => FileType::Directory,
+                    DynNode::Node(_) => FileType::Device,
+                },
+            })
+            .collect()
+    }
+}
+
+impl INodeInterface for DynDir {
+    fn lookup(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.get(name).ok_or(Errno::ENOENT)
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        Ok(self.entries())
+    }
+
+    fn stat(&self, stat: &mut vfscore::Stat) -> VfsResult<()> {
+        stat.dev = 0;
+        stat.ino = 1; // TODO: convert path to number(ino)
+        stat.mode = StatMode::DIR; // TODO: add access mode
+        stat.nlink = 1;
+        stat.uid = 0;
+        stat.gid = 0;
+        stat.size = 0;
+        stat.blksize = 512;
+        stat.blocks = 0;
+        stat.rdev = 0; // TODO: add device id
+        Ok(())
+    }
+}
@@ -2,17 +2,41 @@
 
 extern crate alloc;
 
+mod dyndir;
 mod interrupts;
 mod meminfo;
 mod mounts;
 
 use alloc::{collections::BTreeMap, string::ToString, sync::Arc, vec::Vec};
+use dyndir::DynDir;
 use interrupts::Interrupts;
 use meminfo::MemInfo;
 use mounts::Mounts;
+use sync::LazyInit;
 use syscalls::Errno;
 use vfscore::{DirEntry, FileSystem, FileType, INodeInterface, StatMode, VfsResult};
 
+/// Nodes registered by other modules, such as the kernel.
+/// They are listed after the builtin nodes.
+static EXTRA_NODES: DynDir = DynDir::new();
+
+/// Register a node at the path relative to the root of the procfs,
+/// such as `sys/kernel/core_pattern`.
+pub fn register_node(path: &'static str, node: Arc<dyn INodeInterface>) {
+    EXTRA_NODES.insert(path, node);
+}
+
+/// The lookup and the listing of the process directories, such as `self`
+/// and the pids. The procfs can't access the tasks, so the kernel sets it.
+static PROCESS_DIRS: LazyInit<(ProcessLookup, ProcessEntries)> = LazyInit::new();
+
+pub type ProcessLookup = fn(&str) -> Option<Arc<dyn INodeInterface>>;
+pub type ProcessEntries = fn() -> Vec<DirEntry>;
+
+pub fn set_process_dirs(lookup: ProcessLookup, entries: ProcessEntries) {
+    PROCESS_DIRS.init_by((lookup, entries));
+}
+
 pub struct ProcFS {
     root: Arc<ProcDir>,
 }
@@ -60,7 +84,9 @@ impl INodeInterface for DevDirContainer {
         self.inner
             .map
             .get(name)
-            .map(|x| x.clone())
+            .cloned()
+            .or_else(|| EXTRA_NODES.get(name))
+            .or_else(|| PROCESS_DIRS.try_get().and_then(|(lookup, _)| lookup(name)))
             .ok_or(Errno::ENOENT)
     }
 
@@ -68,12 +94,18 @@ impl INodeInterface for DevDirContainer {
         Ok(self
             .inner
             .map
-            .iter()
-            .map(|(name, _)| DirEntry {
+            .keys()
+            .map(|name| DirEntry {
                 filename: name.to_string(),
                 len: 0,
                 file_type: FileType::Device,
             })
+            .chain(EXTRA_NODES.entries())
+            .chain(
+                PROCESS_DIRS
+                    .try_get()
+                    .map_or(Vec::new(), |(_, entries)| entries()),
+            )
             .collect())
     }
 
@@ -202,6 +202,10 @@ pub trait FileSystem: Send + Sync {
     fn flush(&self) -> VfsResult<()> {
         Ok(())
     }
+    /// The inodes store the owners and the permission bits.
+    fn has_modes(&self) -> bool {
+        false
+    }
 }
 
 pub type VfsResult<T> = core::result::Result<T, Errno>;
@@ -375,6 +379,16 @@ pub trait INodeInterface: DowncastSync + Send + Sync {
     fn poll(&self, _events: PollEvent) -> VfsResult<PollEvent> {
         Err(Errno::EPERM)
     }
+
+    /// Change the owner, the created file is owned by the creator.
+    fn chown(&self, _uid: u32, _gid: u32) -> VfsResult<()> {
+        Err(Errno::EPERM)
+    }
+
+    /// Change the permission bits, the file type is kept.
+    fn chmod(&self, _mode: u32) -> VfsResult<()> {
+        Err(Errno::EPERM)
+    }
 }
 
 impl_downcast!(sync INodeInterface);
@@ -9,5 +9,8 @@ pub const USER_DYN_ADDR: usize = 0x20000000;
 /// 用户态栈顶
 pub const USER_STACK_TOP: usize = 0x8000_0000;
 
+/// 用户态 vDSO 的地址，数据页在前，elf 页在后
+pub const USER_VDSO_ADDR: usize = 0x1_0000_0000;
+
 /// 用户栈初始大小
 pub const USER_STACK_INIT_SIZE: usize = 0x20000;
@@ -48,6 +48,8 @@ use polyhal::{va, PhysAddr};
 use polyhal_trap::trap::TrapType;
 use polyhal_trap::trapframe::{TrapFrame, TrapFrameArgs};
 use runtime::frame::{frame_alloc_persist, frame_unalloc};
+use signal::SignalFlags;
+use syscall::types::signal::{SigInfo, TRAP_BRKPT, TRAP_TRACE};
 use tasks::UserTask;
 use user::user_cow_int;
 use vfscore::OpenFlags;
@@ -84,7 +86,7 @@ fn kernel_interrupt(cx_ref: &mut TrapFrame, trap_type: TrapType) {
                 );
             }
             // judge whether it is trigger by a user_task handler.
-            if let Some(task) = current_task().downcast_arc::<UserTask>().ok() {
+            if let Ok(task) = current_task().downcast_arc::<UserTask>() {
                 let cx_ref = task.force_cx_ref();
                 if task.pcb.is_locked() {
                     // task.pcb.force_unlock();
@@ -105,14 +107,14 @@ fn kernel_interrupt(cx_ref: &mut TrapFrame, trap_type: TrapType) {
             warn!(
                 "illegal instruction fault @ {:#x} paddr: {:?}",
                 addr,
-                task.page_table.translate(addr.into()),
+                task.page_table().translate(addr.into()),
             );
             warn!("the fault occurs @ {:#x}", cx_ref[TrapFrameArgs::SEPC]);
             // warn!("user_task map: {:#x?}", task.pcb.lock().memset);
             warn!(
                 "mapped ppn addr: {:#x} @ {:?}",
                 cx_ref[TrapFrameArgs::SEPC],
-                task.page_table
+                task.page_table()
                     .translate(cx_ref[TrapFrameArgs::SEPC].into())
             );
             task_ilegal(&task, va!(cx_ref[TrapFrameArgs::SEPC]), cx_ref);
@@ -130,6 +132,20 @@ fn kernel_interrupt(cx_ref: &mut TrapFrame, trap_type: TrapType) {
             //     .add_signal(SignalFlags::SIGSEGV);
             // return UserTaskControlFlow::Break;
         }
+        TrapType::Breakpoint => {
+            // the pc is at the breakpoint when the tracer gets SIGTRAP.
+            if let Ok(task) = current_task().downcast_arc::<UserTask>() {
+                let cx_ref = task.force_cx_ref();
+                cx_ref[TrapFrameArgs::SEPC] -= tasks::ptrace::BREAKPOINT_LEN;
+                let signo = SignalFlags::SIGTRAP.num();
+                let pc = cx_ref[TrapFrameArgs::SEPC];
+                let code = match task.ptrace_step_done() {
+                    true => TRAP_TRACE,
+                    false => TRAP_BRKPT,
+                };
+                task.send_siginfo(SigInfo::fault(signo, code, pc));
+            }
+        }
         TrapType::SupervisorExternal => {
             get_int_device().try_handle_interrupt(u32::MAX);
         }
@@ -182,6 +198,9 @@ fn main(hart_id: usize) {
     //println!("猴子1号，你好！");
     // get devices and init
     devices::regist_devices_irq();
+    // CLOCK_REALTIME starts from the rtc.
+    utils::time::init_clock();
+    utils::time::init_realtime();
 
     // TODO: test ebreak
     // Instruction::ebreak();
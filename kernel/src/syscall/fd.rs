@@ -1,8 +1,14 @@
+use super::time::nsec_to_timespec;
 use super::types::fd::IoVec;
 use super::types::poll::{EpollEvent, EpollFile};
 use super::SysResult;
 use crate::syscall::types::fd::FcntlCmd;
 use crate::syscall::types::fd::AT_CWD;
+use crate::syscall::types::fd::EFD_SEMAPHORE;
+use crate::syscall::types::sys::{RLIMIT_FSIZE, RLIM_INFINITY};
+use crate::tasks::cred::Access;
+use crate::tasks::exec::invalidate_task_cache;
+use crate::tasks::{interruptible, EventFd};
 use crate::user::UserTaskContainer;
 use crate::utils::time::{current_nsec, current_timespec};
 use crate::utils::useref::UserRef;
@@ -19,10 +25,32 @@ use fs::{
 use log::debug;
 use num_traits::FromPrimitive;
 use polyhal::VirtAddr;
+use signal::SignalFlags;
 use syscalls::Errno;
 use vfscore::FileType;
 
 impl UserTaskContainer {
+    /// Removing or renaming a file needs the write permission of the parent.
+    fn check_parent_writable(&self, file: &File) -> Result<(), Errno> {
+        let parent = File::open(file.path_buf().dir(), OpenFlags::O_DIRECTORY)?;
+        let cred = self.task.pcb.lock().cred.clone();
+        cred.check_file(&parent, Access::WRITE | Access::EXEC)
+    }
+
+    /// RLIMIT_FSIZE limits the regular files, the writing across the limit
+    /// is cut short, SIGXFSZ is sent if nothing can be written.
+    fn fsize_limit(&self, file: &File, offset: usize, len: usize) -> Result<usize, Errno> {
+        let limit = self.task.rlimit(RLIMIT_FSIZE);
+        if limit == RLIM_INFINITY || len == 0 || file.file_type() != Ok(FileType::File) {
+            return Ok(len);
+        }
+        if offset >= limit {
+            self.task.send_signal(SignalFlags::SIGXFSZ);
+            return Err(Errno::EFBIG);
+        }
+        Ok(cmp::min(len, limit - offset))
+    }
+
     pub async fn sys_dup(&self, fd: usize) -> SysResult {
         debug!("sys_dup3 @ fd_src: {}", fd);
         let fd_dst = self.task.alloc_fd().ok_or(Errno::EMFILE)?;
@@ -47,11 +75,8 @@ impl UserTaskContainer {
             self.tid, fd as isize, buf_ptr, count
         );
         let buffer = buf_ptr.slice_mut_with_len(count);
-        self.task
-            .get_fd(fd)
-            .ok_or(Errno::EBADF)?
-            .async_read(buffer)
-            .await
+        let file = self.task.get_fd(fd).ok_or(Errno::EBADF)?;
+        interruptible(self.task.clone(), file.async_read(buffer)).await
     }
 
     pub async fn sys_write(&self, fd: usize, buf_ptr: VirtAddr, count: usize) -> SysResult {
@@ -59,9 +84,10 @@ impl UserTaskContainer {
             "[task {}] sys_write @ fd: {} buf_ptr: {:?} count: {}",
             self.tid, fd as isize, buf_ptr, count
         );
-        let buffer = buf_ptr.slice_with_len(count);
         let file = self.task.get_fd(fd).ok_or(Errno::EBADF)?;
-        file.async_write(buffer).await
+        let count = self.fsize_limit(&file, *file.offset.lock(), count)?;
+        let buffer = buf_ptr.slice_with_len(count);
+        interruptible(self.task.clone(), file.async_write(buffer)).await
     }
 
     pub async fn sys_readv(&self, fd: usize, iov: UserRef<IoVec>, iocnt: usize) -> SysResult {
@@ -85,8 +111,15 @@ impl UserTaskContainer {
         let file = self.task.get_fd(fd).ok_or(Errno::EBADF)?;
 
         for io in iov {
-            let buffer = UserRef::<u8>::from(io.base).slice_mut_with_len(io.len);
+            let len = match self.fsize_limit(&file, *file.offset.lock(), io.len) {
+                Err(_) if wsize > 0 => break,
+                len => len?,
+            };
+            let buffer = UserRef::<u8>::from(io.base).slice_mut_with_len(len);
             wsize += file.write(buffer)?;
+            if len < io.len {
+                break;
+            }
         }
 
         Ok(wsize)
@@ -104,8 +137,8 @@ impl UserTaskContainer {
             "sys_mkdir_at @ dir_fd: {}, path: {}, mode: {}",
             dir_fd as isize, path, mode
         );
-        self.task
-            .fd_open(dir_fd, path, OpenFlags::O_DIRECTORY | OpenFlags::O_CREAT)?;
+        let flags = OpenFlags::O_DIRECTORY | OpenFlags::O_CREAT;
+        self.task.fd_create(dir_fd, path, flags, mode as _)?;
         Ok(0)
     }
 
@@ -125,6 +158,8 @@ impl UserTaskContainer {
 
         let old_path: &str = oldpath.get_cstr().map_err(|_| Errno::EINVAL)?;
         let old_file = self.task.fd_open(olddir_fd, old_path, flags.clone())?;
+        self.check_parent_writable(&old_file)?;
+        invalidate_task_cache(&old_file.path_buf());
 
         let old_file_type = old_file.file_type()?;
         let new_path = newpath.get_cstr().map_err(|_| Errno::EINVAL)?;
@@ -133,6 +168,7 @@ impl UserTaskContainer {
             let new_file = self
                 .task
                 .fd_open(newdir_fd, new_path, OpenFlags::O_CREAT | flags)?;
+            invalidate_task_cache(&new_file.path_buf());
             let file_size = old_file.file_size()?;
             let mut buffer = vec![0u8; file_size];
             old_file.read(&mut buffer)?;
@@ -169,6 +205,8 @@ impl UserTaskContainer {
         );
         let flags = OpenFlags::from_bits_truncate(flags);
         let file = self.task.fd_open(dir_fd, path, flags)?;
+        self.check_parent_writable(&file)?;
+        invalidate_task_cache(&file.path_buf());
 
         file.remove_self()?;
         Ok(0)
@@ -193,7 +231,18 @@ impl UserTaskContainer {
         );
         // let dir = to_node(&self.task, fd, filename)?;
         // let file = dir.dentry_open(filename, flags)?;
-        let file = self.task.fd_open(dir_fd, filename, flags)?;
+        let (file, created) = self
+            .task
+            .fd_create(dir_fd, filename, flags.clone(), mode as _)?;
+        // the new file is opened even if its mode doesn't permit the access.
+        if !created {
+            let cred = self.task.pcb.lock().cred.clone();
+            cred.check_open(&file, flags.clone())?;
+        }
+        // the cached executable may be changed by the writable file.
+        if flags.intersects(OpenFlags::O_WRONLY | OpenFlags::O_RDWR | OpenFlags::O_TRUNC) {
+            invalidate_task_cache(&file.path_buf());
+        }
         let fd = self.task.alloc_fd().ok_or(Errno::EMFILE)?;
         self.task.set_fd(fd, Arc::new(file));
         debug!("sys_openat @ ret fd: {}", fd);
@@ -223,7 +272,15 @@ impl UserTaskContainer {
             "sys_accessat @ fd: {}, filename: {}, flags: {:?}, mode: {}",
             dir_fd as isize, filename, open_flags, mode
         );
-        self.task.fd_open(dir_fd, filename, open_flags)?;
+        // check by the real uid and gid unless AT_EACCESS.
+        const AT_EACCESS: usize = 0x200;
+        let file = self.task.fd_open(dir_fd, filename, open_flags)?;
+        let cred = self.task.pcb.lock().cred.clone();
+        let cred = match flags & AT_EACCESS {
+            0 => cred.real(),
+            _ => cred,
+        };
+        cred.check_file(&file, Access::from_bits_truncate(mode as _))?;
         Ok(0)
     }
 
@@ -331,11 +388,10 @@ impl UserTaskContainer {
             "sys_write @ fd: {} buf_ptr: {:?} count: {}",
             fd as isize, buf_ptr, count
         );
+        let file = self.task.get_fd(fd).ok_or(Errno::EBADF)?;
+        let count = self.fsize_limit(&file, offset, count)?;
         let buffer = buf_ptr.slice_with_len(count);
-        self.task
-            .get_fd(fd)
-            .ok_or(Errno::EBADF)?
-            .writeat(offset, buffer)
+        file.writeat(offset, buffer)
     }
 
     pub async fn sys_mount(
@@ -570,6 +626,7 @@ impl UserTaskContainer {
             in_file.seek(SeekFrom::CURRENT(0))?
         };
         let rlen = cmp::min(in_file.file_size()? - curr_off, count);
+        let rlen = self.fsize_limit(&out_file, *out_file.offset.lock(), rlen)?;
 
         let mut buffer = vec![0u8; rlen];
 
@@ -582,6 +639,25 @@ impl UserTaskContainer {
         out_file.write(&buffer)
     }
 
+    pub async fn sys_eventfd2(&self, initval: usize, flags: usize) -> SysResult {
+        debug!(
+            "[task {}] sys_eventfd2 @ initval: {}, flags: {:#x}",
+            self.tid, initval, flags
+        );
+        // EFD_NONBLOCK and EFD_CLOEXEC are the same as the open flags.
+        let open_flags =
+            OpenFlags::from_bits((flags & !EFD_SEMAPHORE) as _).ok_or(Errno::EINVAL)?;
+        if !(OpenFlags::O_NONBLOCK | OpenFlags::O_CLOEXEC).contains(open_flags.clone()) {
+            return Err(Errno::EINVAL);
+        }
+        let fd = self.task.alloc_fd().ok_or(Errno::EMFILE)?;
+        let eventfd = EventFd::new(initval as u32 as _, flags & EFD_SEMAPHORE != 0);
+        let file = File::new_dev(Arc::new(eventfd));
+        *file.flags.lock() |= open_flags;
+        self.task.set_fd(fd, file);
+        Ok(fd)
+    }
+
     /// TODO: improve it.
     pub async fn sys_ppoll(
         &self,
@@ -617,6 +693,14 @@ impl UserTaskContainer {
             if current_nsec() >= etime || num > 0 {
                 break num;
             }
+            // the polling is restarted only if no handler runs, the rest of
+            // the timeout is written back for it.
+            if self.task.interrupt_signal().is_some() {
+                if timeout_ptr.is_valid() {
+                    *timeout_ptr.get_mut() = nsec_to_timespec(etime.saturating_sub(current_nsec()));
+                }
+                return Err(Errno::ERESTARTNOHAND);
+            }
             yield_now().await;
         };
         Ok(n)
@@ -652,6 +736,13 @@ impl UserTaskContainer {
             if (timeout > 0 && current_nsec() >= etime) || num > 0 {
                 break num;
             }
+            // the relative timeout can't be restarted.
+            if self.task.interrupt_signal().is_some() {
+                return match timeout > 0 {
+                    true => Err(Errno::EINTR),
+                    false => Err(Errno::ERESTARTNOHAND),
+                };
+            }
             yield_now().await;
         };
         Ok(n)
@@ -688,7 +779,8 @@ impl UserTaskContainer {
         loop {
             yield_now().await;
             let mut num = 0;
-            let inner = self.task.pcb.lock();
+            let files = self.task.files();
+            let inner = files.lock();
             if readfds.is_valid() {
                 let rfds = readfds.slice_mut_with_len(4);
                 for i in 0..max_fdp1 {
@@ -697,11 +789,11 @@ impl UserTaskContainer {
                         rfds_r.set_bit(i, false);
                         continue;
                     }
-                    if inner.fd_table[i].is_none() {
+                    if inner[i].is_none() {
                         rfds_r.set_bit(i, false);
                         continue;
                     }
-                    let file = inner.fd_table[i].clone().unwrap();
+                    let file = inner[i].clone().unwrap();
                     match file.poll(PollEvent::POLLIN) {
                         Ok(res) => {
                             if res.contains(PollEvent::POLLIN) {
@@ -721,11 +813,11 @@ impl UserTaskContainer {
                     if !wfds.get_bit(i) {
                         continue;
                     }
-                    if inner.fd_table[i].is_none() {
+                    if inner[i].is_none() {
                         wfds_r.set_bit(i, false);
                         continue;
                     }
-                    let file = inner.fd_table[i].clone().unwrap();
+                    let file = inner[i].clone().unwrap();
                     match file.poll(PollEvent::POLLOUT) {
                         Ok(res) => {
                             if res.contains(PollEvent::POLLOUT) {
@@ -746,11 +838,11 @@ impl UserTaskContainer {
                     if !efds.get_bit(i) {
                         continue;
                     }
-                    if inner.fd_table[i].is_none() {
+                    if inner[i].is_none() {
                         efds_r.set_bit(i, false);
                         continue;
                     }
-                    let file = inner.fd_table[i].clone().unwrap();
+                    let file = inner[i].clone().unwrap();
                     match file.poll(PollEvent::POLLERR) {
                         Ok(res) => {
                             if res.contains(PollEvent::POLLERR) {
@@ -790,6 +882,14 @@ impl UserTaskContainer {
                 }
                 return Ok(0);
             }
+            // the rest of the timeout is written back for the restart.
+            if self.task.interrupt_signal().is_some() {
+                if timeout_ptr.is_valid() {
+                    *timeout_ptr.get_mut() =
+                        nsec_to_timespec(timeout.saturating_sub(current_nsec()));
+                }
+                return Err(Errno::ERESTARTNOHAND);
+            }
         }
     }
 
@@ -813,6 +913,10 @@ impl UserTaskContainer {
             return Err(Errno::EPERM);
         }
         let file = self.task.get_fd(fields).ok_or(Errno::EINVAL)?;
+        if len > self.task.rlimit(RLIMIT_FSIZE) {
+            self.task.send_signal(SignalFlags::SIGXFSZ);
+            return Err(Errno::EFBIG);
+        }
         file.truncate(len)?;
         Ok(0)
     }
@@ -892,6 +996,13 @@ impl UserTaskContainer {
             if current_nsec() >= end || num > 0 {
                 break num;
             }
+            // the relative timeout can't be restarted.
+            if self.task.interrupt_signal().is_some() {
+                return match timeout == usize::MAX {
+                    true => Err(Errno::ERESTARTNOHAND),
+                    false => Err(Errno::EINTR),
+                };
+            }
         };
 
         Ok(n)
@@ -913,6 +1024,11 @@ impl UserTaskContainer {
         );
         let in_file = self.task.get_fd(fd_in).ok_or(Errno::EBADF)?;
         let out_file = self.task.get_fd(fd_out).ok_or(Errno::EBADF)?;
+        let out_off = match off_out.is_valid() {
+            true => *off_out.get_ref(),
+            false => *out_file.offset.lock(),
+        };
+        let len = self.fsize_limit(&out_file, out_off, len)?;
         let mut buffer = vec![0u8; len];
         let rsize = if off_in.is_valid() {
             let rsize = in_file.readat(*off_in.get_ref(), &mut buffer)?;
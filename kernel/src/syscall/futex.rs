@@ -0,0 +1,391 @@
+use super::{
+    time::WaitUntilsec,
+    types::fd::{
+        FutexFlags, RobustListHead, FUTEX_BITSET_MATCH_ANY, FUTEX_CLOCK_REALTIME, FUTEX_OWNER_DIED,
+        FUTEX_PRIVATE_FLAG, FUTEX_TID_MASK, FUTEX_WAITERS,
+    },
+    SysResult,
+};
+use crate::{
+    tasks::{
+        futex_dequeue, futex_requeue, futex_wake_bitset, tid2user_task, FutexTable, FutexWaiter,
+        WaitFutex, SHARED_FUTEX_TABLE,
+    },
+    user::UserTaskContainer,
+    utils::{
+        time::{current_nsec, realtime_to_boot},
+        useref::UserRef,
+    },
+};
+use alloc::sync::Arc;
+use core::{
+    mem::size_of,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use executor::{select, yield_now, Either};
+use fs::TimeSpec;
+use log::debug;
+use num_traits::FromPrimitive;
+use sync::Mutex;
+use syscalls::Errno;
+
+/// The futex word in the user memory, it is changed by the user atomically.
+fn futex_word(uaddr: usize) -> &'static AtomicU32 {
+    unsafe { AtomicU32::from_ptr(UserRef::<u32>::from(uaddr).get_mut()) }
+}
+
+/// The deadline in nanoseconds of the timespec, it is None if there is no
+/// timeout. The absolute timeout is used by WAIT_BITSET and LOCK_PI, it is
+/// CLOCK_REALTIME with FUTEX_CLOCK_REALTIME, or the time since the boot.
+fn futex_deadline(timeout: usize, absolute: bool, realtime: bool) -> Option<usize> {
+    if timeout == 0 {
+        return None;
+    }
+    let nsec = UserRef::<TimeSpec>::from(timeout).get_ref().to_nsec();
+    match (absolute, realtime) {
+        (true, true) => Some(realtime_to_boot(nsec)),
+        (true, false) => Some(nsec),
+        (false, _) => Some(current_nsec() + nsec),
+    }
+}
+
+/// The decoded arguments of the futex syscall.
+struct FutexArgs {
+    cmd: FutexFlags,
+    realtime: bool,
+    uaddr: usize,
+    value: usize,
+    value2: usize,
+    uaddr2: usize,
+    value3: usize,
+}
+
+impl UserTaskContainer {
+    /// The PI futexes don't boost the owner because the executor doesn't
+    /// schedule by the priority, the lock is handed to the first waiter.
+    pub async fn sys_futex(
+        &self,
+        uaddr: usize,
+        op: usize,
+        value: usize,
+        value2: usize,
+        uaddr2: usize,
+        value3: usize,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_futex @ uaddr: {:#x} op: {} value: {:#x}, value2: {:#x}, uaddr2: {:#x} , value3: {:#x}",
+            self.tid, uaddr, op, value, value2, uaddr2, value3
+        );
+        let cmd = op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME);
+        let cmd = FutexFlags::from_usize(cmd).ok_or(Errno::ENOSYS)?;
+        let realtime = op & FUTEX_CLOCK_REALTIME != 0;
+        if uaddr % size_of::<u32>() != 0 {
+            return Err(Errno::EINVAL);
+        }
+        let args = FutexArgs {
+            cmd,
+            realtime,
+            uaddr,
+            value,
+            value2,
+            uaddr2,
+            value3,
+        };
+        // the private futexes are kept in the table of the memory space,
+        // the others may be shared with the other processes.
+        match op & FUTEX_PRIVATE_FLAG != 0 {
+            true => {
+                let futex_table = self.task.mm().lock().futex_table.clone();
+                self.futex_op(futex_table, |x| x, args).await
+            }
+            false => {
+                let key = |x| self.task.futex_key(x);
+                let futex_table = SHARED_FUTEX_TABLE.clone();
+                self.futex_op(futex_table, key, args).await
+            }
+        }
+    }
+
+    async fn futex_op<K: Ord + Copy>(
+        &self,
+        futex_table: Arc<Mutex<FutexTable<K>>>,
+        key: impl Fn(usize) -> K,
+        args: FutexArgs,
+    ) -> SysResult {
+        let FutexArgs {
+            cmd,
+            realtime,
+            uaddr,
+            value,
+            value2,
+            uaddr2,
+            value3,
+        } = args;
+        match cmd {
+            FutexFlags::Wait => {
+                let deadline = futex_deadline(value2, false, false);
+                let bitset = FUTEX_BITSET_MATCH_ANY;
+                self.futex_wait(futex_table, key(uaddr), uaddr, value as _, deadline, bitset)
+                    .await
+            }
+            FutexFlags::WaitBitset => {
+                if value3 as u32 == 0 {
+                    return Err(Errno::EINVAL);
+                }
+                let deadline = futex_deadline(value2, true, realtime);
+                let bitset = value3 as _;
+                self.futex_wait(futex_table, key(uaddr), uaddr, value as _, deadline, bitset)
+                    .await
+            }
+            FutexFlags::Wake | FutexFlags::WakeBitset => {
+                let bitset = match cmd {
+                    FutexFlags::WakeBitset => value3 as u32,
+                    _ => FUTEX_BITSET_MATCH_ANY,
+                };
+                if bitset == 0 {
+                    return Err(Errno::EINVAL);
+                }
+                let count = futex_wake_bitset(&mut futex_table.lock(), key(uaddr), value, bitset);
+                yield_now().await;
+                Ok(count)
+            }
+            FutexFlags::Requeue => {
+                let (waked, _) = futex_requeue(futex_table, key(uaddr), value, key(uaddr2), value2);
+                Ok(waked)
+            }
+            FutexFlags::CmpRequeue => {
+                if futex_word(uaddr).load(Ordering::SeqCst) != value3 as u32 {
+                    return Err(Errno::EAGAIN);
+                }
+                let (waked, requeued) =
+                    futex_requeue(futex_table, key(uaddr), value, key(uaddr2), value2);
+                Ok(waked + requeued)
+            }
+            FutexFlags::WakeOp => {
+                let keys = (key(uaddr), key(uaddr2));
+                self.futex_wake_op(futex_table, keys, uaddr2, value, value2, value3 as _)
+            }
+            FutexFlags::LockPi | FutexFlags::TrylockPi => {
+                // the timeout of LOCK_PI is always CLOCK_REALTIME.
+                let deadline = futex_deadline(value2, true, true);
+                let trylock = matches!(cmd, FutexFlags::TrylockPi);
+                self.futex_lock_pi(futex_table, key(uaddr), uaddr, deadline, trylock)
+                    .await
+            }
+            FutexFlags::UnlockPi => self.futex_unlock_pi(futex_table, key(uaddr), uaddr),
+            FutexFlags::Fd => Err(Errno::ENOSYS),
+        }
+    }
+
+    /// Sleep if the word is still the value, the check and the queueing
+    /// are done with the table locked, so the wake can't be lost.
+    async fn futex_wait<K: Ord>(
+        &self,
+        futex_table: Arc<Mutex<FutexTable<K>>>,
+        key: K,
+        uaddr: usize,
+        value: u32,
+        deadline: Option<usize>,
+        bitset: u32,
+    ) -> SysResult {
+        let mut table = futex_table.lock();
+        if futex_word(uaddr).load(Ordering::SeqCst) != value {
+            return Err(Errno::EAGAIN);
+        }
+        table.entry(key).or_default().push(FutexWaiter {
+            tid: self.tid,
+            bitset,
+        });
+        drop(table);
+        self.futex_sleep(futex_table, deadline).await
+    }
+
+    /// Wait until the waiter is woken, it is dequeued if it is timed out.
+    async fn futex_sleep<K>(
+        &self,
+        futex_table: Arc<Mutex<FutexTable<K>>>,
+        deadline: Option<usize>,
+    ) -> SysResult {
+        let wait_func = WaitFutex(futex_table.clone(), self.tid);
+        let Some(deadline) = deadline else {
+            return wait_func.await;
+        };
+        match select(wait_func, WaitUntilsec(deadline)).await {
+            // the timed wait isn't restarted, the timeout may be relative.
+            Either::Left((Err(Errno::ERESTARTSYS), _)) => Err(Errno::EINTR),
+            Either::Left((res, _)) => res,
+            Either::Right(_) => match futex_dequeue(&futex_table, self.tid) {
+                true => Err(Errno::ETIMEDOUT),
+                // woken just before the timeout.
+                false => Ok(0),
+            },
+        }
+    }
+
+    /// Change the word at uaddr2 by the op encoded in `encoded`, wake the
+    /// waiters of uaddr, and the waiters of uaddr2 if the old value matches.
+    fn futex_wake_op<K: Ord>(
+        &self,
+        futex_table: Arc<Mutex<FutexTable<K>>>,
+        (key, key2): (K, K),
+        uaddr2: usize,
+        wake_count: usize,
+        wake_count2: usize,
+        encoded: u32,
+    ) -> SysResult {
+        let op = (encoded >> 28) & 0xf;
+        let cmp = (encoded >> 24) & 0xf;
+        // the arguments are 12 bits signed integers.
+        let mut oparg = ((encoded << 8) as i32) >> 20;
+        let cmparg = ((encoded << 20) as i32) >> 20;
+        // FUTEX_OP_OPARG_SHIFT
+        if op & 8 != 0 {
+            oparg = 1 << (oparg & 31);
+        }
+        if op & 7 > 4 || cmp > 5 {
+            return Err(Errno::ENOSYS);
+        }
+        let mut table = futex_table.lock();
+        let word = futex_word(uaddr2);
+        let oparg = oparg as u32;
+        let old = match op & 7 {
+            0 => word.swap(oparg, Ordering::SeqCst),
+            1 => word.fetch_add(oparg, Ordering::SeqCst),
+            2 => word.fetch_or(oparg, Ordering::SeqCst),
+            3 => word.fetch_and(!oparg, Ordering::SeqCst),
+            _ => word.fetch_xor(oparg, Ordering::SeqCst),
+        } as i32;
+        let matched = match cmp {
+            0 => old == cmparg,
+            1 => old != cmparg,
+            2 => old < cmparg,
+            3 => old <= cmparg,
+            4 => old > cmparg,
+            _ => old >= cmparg,
+        };
+        let mut count = futex_wake_bitset(&mut table, key, wake_count, FUTEX_BITSET_MATCH_ANY);
+        if matched {
+            count += futex_wake_bitset(&mut table, key2, wake_count2, FUTEX_BITSET_MATCH_ANY);
+        }
+        Ok(count)
+    }
+
+    /// Take the PI lock, the word is the tid of the owner. The lock of a
+    /// dead owner is taken with FUTEX_OWNER_DIED.
+    async fn futex_lock_pi<K: Ord + Copy>(
+        &self,
+        futex_table: Arc<Mutex<FutexTable<K>>>,
+        key: K,
+        uaddr: usize,
+        deadline: Option<usize>,
+        trylock: bool,
+    ) -> SysResult {
+        let tid = self.tid as u32;
+        let word = futex_word(uaddr);
+        loop {
+            let mut table = futex_table.lock();
+            let value = word.load(Ordering::SeqCst);
+            let owner = value & FUTEX_TID_MASK;
+            if owner == tid {
+                return Err(Errno::EDEADLK);
+            }
+            if owner == 0 || tid2user_task(owner as _).is_none() {
+                let died = match owner {
+                    0 => value & FUTEX_OWNER_DIED,
+                    _ => FUTEX_OWNER_DIED,
+                };
+                let waiters = match table.get(&key).is_some_and(|x| !x.is_empty()) {
+                    true => FUTEX_WAITERS,
+                    false => 0,
+                };
+                let new = tid | died | waiters;
+                match word.compare_exchange(value, new, Ordering::SeqCst, Ordering::SeqCst) {
+                    Ok(_) => return Ok(0),
+                    Err(_) => continue,
+                }
+            }
+            if trylock {
+                return Err(Errno::EAGAIN);
+            }
+            let new = value | FUTEX_WAITERS;
+            if word
+                .compare_exchange(value, new, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+            {
+                continue;
+            }
+            table.entry(key).or_default().push(FutexWaiter {
+                tid: self.tid,
+                bitset: FUTEX_BITSET_MATCH_ANY,
+            });
+            drop(table);
+            self.futex_sleep(futex_table.clone(), deadline).await?;
+            // the unlocker hands the lock to the first waiter.
+            if word.load(Ordering::SeqCst) & FUTEX_TID_MASK == tid {
+                return Ok(0);
+            }
+        }
+    }
+
+    /// Release the PI lock and hand it to the first waiter.
+    fn futex_unlock_pi<K: Ord>(
+        &self,
+        futex_table: Arc<Mutex<FutexTable<K>>>,
+        key: K,
+        uaddr: usize,
+    ) -> SysResult {
+        let word = futex_word(uaddr);
+        let mut table = futex_table.lock();
+        if word.load(Ordering::SeqCst) & FUTEX_TID_MASK != self.tid as u32 {
+            return Err(Errno::EPERM);
+        }
+        let new = match table.get_mut(&key).filter(|x| !x.is_empty()) {
+            Some(que) => {
+                let next = que.remove(0);
+                match que.is_empty() {
+                    true => next.tid as u32,
+                    false => next.tid as u32 | FUTEX_WAITERS,
+                }
+            }
+            None => 0,
+        };
+        word.store(new, Ordering::SeqCst);
+        Ok(0)
+    }
+
+    pub async fn sys_set_robust_list(&self, head: usize, len: usize) -> SysResult {
+        debug!(
+            "[task {}] sys_set_robust_list @ head: {:#x}, len: {}",
+            self.tid, head, len
+        );
+        if len != size_of::<RobustListHead>() {
+            return Err(Errno::EINVAL);
+        }
+        self.task.tcb.write().robust_list = head;
+        Ok(0)
+    }
+
+    /// Reading the list of the other users needs the privilege.
+    pub async fn sys_get_robust_list(
+        &self,
+        pid: usize,
+        head_ptr: UserRef<usize>,
+        len_ptr: UserRef<usize>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_get_robust_list @ pid: {}, head_ptr: {}, len_ptr: {}",
+            self.tid, pid, head_ptr, len_ptr
+        );
+        let task = match pid {
+            0 => self.task.clone(),
+            _ => tid2user_task(pid).ok_or(Errno::ESRCH)?,
+        };
+        let cred = self.task.pcb.lock().cred.clone();
+        if !Arc::ptr_eq(&task.pcb, &self.task.pcb) && !cred.may_access(&task.pcb.lock().cred) {
+            return Err(Errno::EPERM);
+        }
+        *head_ptr.get_mut() = task.tcb.read().robust_list;
+        *len_ptr.get_mut() = size_of::<RobustListHead>();
+        Ok(0)
+    }
+}
@@ -1,5 +1,6 @@
 use super::SysResult;
 use crate::syscall::types::mm::{MSyncFlags, MapFlags, MmapProt, ProtFlags};
+use crate::syscall::types::sys::{RLIMIT_AS, RLIMIT_DATA};
 use crate::tasks::{MemArea, MemType};
 use crate::user::UserTaskContainer;
 use crate::utils::useref::UserRef;
@@ -15,9 +16,23 @@ const MAP_AREA_START: usize = 0x2_0000_0000;
 impl UserTaskContainer {
     pub async fn sys_brk(&self, addr: usize) -> SysResult {
         debug!("sys_brk @ new: {:#x} old: {:#x}", addr, self.task.heap());
-        match addr {
-            0 => Ok(self.task.heap()),
-            _ => Ok(self.task.sbrk(addr)),
+        let heap = self.task.heap();
+        if addr == 0 {
+            return Ok(heap);
+        }
+        // the old break is returned if the limits are exceeded.
+        let (data, space) = (self.task.rlimit(RLIMIT_DATA), self.task.rlimit(RLIMIT_AS));
+        let mm = self.task.mm();
+        let mm = mm.lock();
+        let grow = addr
+            .div_ceil(PAGE_SIZE)
+            .saturating_sub(heap.div_ceil(PAGE_SIZE))
+            * PAGE_SIZE;
+        let exceeded = addr.saturating_sub(mm.brk_start) > data || mm.mapped_size() + grow > space;
+        drop(mm);
+        match exceeded {
+            true => Ok(heap),
+            false => Ok(self.task.sbrk(addr)),
         }
     }
 
@@ -56,22 +71,14 @@ impl UserTaskContainer {
         }
 
         if flags.contains(MapFlags::MAP_FIXED) {
-            let overlaped = self
-                .task
-                .pcb
-                .lock()
-                .memset
-                .overlapping(addr.raw(), addr.raw() + len);
-            if overlaped {
-                self.task.pcb.lock().memset.sub_area(
-                    addr.raw(),
-                    addr.raw() + len,
-                    &self.task.page_table,
-                );
+            let mm = self.task.mm();
+            let mut mm = mm.lock();
+            if mm.memset.overlapping(addr.raw(), addr.raw() + len) {
+                mm.sub_area(addr.raw(), addr.raw() + len);
             }
         } else if self
             .task
-            .pcb
+            .mm()
             .lock()
             .memset
             .overlapping(addr.raw(), addr.raw() + len)
@@ -79,6 +86,11 @@ impl UserTaskContainer {
             return Err(Errno::EINVAL);
         }
 
+        let mapped = self.task.mm().lock().mapped_size();
+        if mapped + len > self.task.rlimit(RLIMIT_AS) {
+            return Err(Errno::ENOMEM);
+        }
+
         if flags.contains(MapFlags::MAP_SHARED) {
             match &file {
                 Some(file) => self
@@ -111,7 +123,7 @@ impl UserTaskContainer {
                 .frame_alloc(addr, MemType::Mmap, len.div_ceil(PAGE_SIZE))
                 .ok_or(Errno::EFAULT)?;
         } else {
-            self.task.pcb.lock().memset.push(MemArea {
+            self.task.mm().lock().memset.push(MemArea {
                 mtype: MemType::Mmap,
                 mtrackers: vec![],
                 file: None,
@@ -130,10 +142,7 @@ impl UserTaskContainer {
 
     pub async fn sys_munmap(&self, start: usize, len: usize) -> SysResult {
         debug!("sys_munmap @ start: {:#x}, len: {:#x}", start, len);
-        self.task.inner_map(|pcb| {
-            pcb.memset
-                .sub_area(start, start + len, &self.task.page_table);
-        });
+        self.task.mm().lock().sub_area(start, start + len);
         Ok(0)
     }
 
@@ -1,5 +1,7 @@
 mod fd;
+mod futex;
 mod mm;
+mod ptrace;
 mod shm;
 mod signal;
 mod socket;
@@ -24,6 +26,8 @@ impl UserTaskContainer {
         let sysno = Sysno::new(call_id).ok_or(Errno::EINVAL)?;
         match sysno {
             Sysno::getcwd => self.sys_getcwd(args[0].into(), args[1] as _).await,
+            Sysno::chroot => self.sys_chroot(args[0].into()).await,
+            Sysno::umask => self.sys_umask(args[0] as _).await,
             Sysno::chdir => self.sys_chdir(args[0].into()).await,
             Sysno::openat => {
                 self.sys_openat(args[0] as _, args[1].into(), args[2] as _, args[3] as _)
@@ -53,6 +57,7 @@ impl UserTaskContainer {
             Sysno::getpid => self.sys_getpid().await,
             Sysno::pipe2 => self.sys_pipe2(args[0].into(), args[1] as _).await,
             Sysno::gettimeofday => self.sys_gettimeofday(args[0].into(), args[1] as _).await,
+            Sysno::settimeofday => self.sys_settimeofday(args[0].into(), args[1] as _).await,
             Sysno::nanosleep => self.sys_nanosleep(args[0].into(), args[1].into()).await,
             Sysno::uname => self.sys_uname(args[0].into()).await,
             Sysno::unlinkat => {
@@ -65,9 +70,20 @@ impl UserTaskContainer {
             }
             Sysno::fstat => self.sys_fstat(args[0] as _, args[1].into()).await,
             Sysno::wait4 => {
-                self.sys_wait4(args[0] as _, args[1].into(), args[2] as _)
+                self.sys_wait4(args[0] as _, args[1].into(), args[2] as _, args[3].into())
                     .await
             }
+            Sysno::ptrace => self.sys_ptrace(args[0], args[1], args[2], args[3]).await,
+            Sysno::waitid => {
+                self.sys_waitid(
+                    args[0] as _,
+                    args[1] as _,
+                    args[2].into(),
+                    args[3] as _,
+                    args[4].into(),
+                )
+                .await
+            }
             Sysno::sched_yield => self.sys_sched_yield().await,
             Sysno::getppid => self.sys_getppid().await,
             Sysno::mount => {
@@ -102,12 +118,35 @@ impl UserTaskContainer {
             Sysno::gettid => self.sys_gettid().await,
             Sysno::lseek => self.sys_lseek(args[0] as _, args[1] as _, args[2] as _),
             Sysno::clock_gettime => self.sys_clock_gettime(args[0] as _, args[1].into()).await,
-            Sysno::rt_sigtimedwait => self.sys_sigtimedwait().await,
+            Sysno::rt_sigtimedwait => {
+                self.sys_sigtimedwait(args[0].into(), args[1].into(), args[2].into())
+                    .await
+            }
+            Sysno::pidfd_open => self.sys_pidfd_open(args[0] as _, args[1] as _).await,
+            Sysno::pidfd_send_signal => {
+                self.sys_pidfd_send_signal(args[0] as _, args[1] as _, args[2].into(), args[3])
+                    .await
+            }
+            Sysno::eventfd2 => self.sys_eventfd2(args[0] as _, args[1] as _).await,
+            #[cfg(target_arch = "x86_64")]
+            Sysno::eventfd => self.sys_eventfd2(args[0] as _, 0).await,
+            Sysno::timerfd_create => self.sys_timerfd_create(args[0] as _, args[1] as _).await,
+            Sysno::timerfd_settime => {
+                self.sys_timerfd_settime(args[0] as _, args[1] as _, args[2].into(), args[3].into())
+                    .await
+            }
+            Sysno::timerfd_gettime => self.sys_timerfd_gettime(args[0] as _, args[1].into()).await,
+            Sysno::signalfd4 => {
+                self.sys_signalfd4(args[0] as _, args[1].into(), args[2], args[3])
+                    .await
+            }
             Sysno::rt_sigsuspend => self.sys_sigsuspend(args[0].into()).await,
             Sysno::prlimit64 => {
                 self.sys_prlimit64(args[0] as _, args[1] as _, args[2].into(), args[3].into())
                     .await
             }
+            Sysno::getrlimit => self.sys_getrlimit(args[0] as _, args[1].into()).await,
+            Sysno::setrlimit => self.sys_setrlimit(args[0] as _, args[1].into()).await,
             Sysno::readv => {
                 self.sys_readv(args[0] as _, args[1].into(), args[2] as _)
                     .await
@@ -139,7 +178,31 @@ impl UserTaskContainer {
             Sysno::getegid => self.sys_getegid().await,
             Sysno::getgid => self.sys_getgid().await,
             Sysno::getuid => self.sys_getuid().await,
-            Sysno::getpgid => self.sys_getpgid().await,
+            Sysno::getresuid => {
+                self.sys_getresuid(args[0].into(), args[1].into(), args[2].into())
+                    .await
+            }
+            Sysno::getresgid => {
+                self.sys_getresgid(args[0].into(), args[1].into(), args[2].into())
+                    .await
+            }
+            Sysno::setuid => self.sys_setuid(args[0] as _).await,
+            Sysno::setgid => self.sys_setgid(args[0] as _).await,
+            Sysno::setreuid => self.sys_setreuid(args[0] as _, args[1] as _).await,
+            Sysno::setregid => self.sys_setregid(args[0] as _, args[1] as _).await,
+            Sysno::setresuid => {
+                self.sys_setresuid(args[0] as _, args[1] as _, args[2] as _)
+                    .await
+            }
+            Sysno::setresgid => {
+                self.sys_setresgid(args[0] as _, args[1] as _, args[2] as _)
+                    .await
+            }
+            Sysno::setfsuid => self.sys_setfsuid(args[0] as _).await,
+            Sysno::setfsgid => self.sys_setfsgid(args[0] as _).await,
+            Sysno::getgroups => self.sys_getgroups(args[0] as _, args[1].into()).await,
+            Sysno::getpgid => self.sys_getpgid(args[0] as _).await,
+            Sysno::getsid => self.sys_getsid(args[0] as _).await,
             Sysno::ioctl => {
                 self.sys_ioctl(
                     args[0] as _,
@@ -172,7 +235,7 @@ impl UserTaskContainer {
             }
             Sysno::futex => {
                 self.sys_futex(
-                    args[0].into(),
+                    args[0] as _,
                     args[1] as _,
                     args[2] as _,
                     args[3] as _,
@@ -190,11 +253,21 @@ impl UserTaskContainer {
                     .await
             }
             Sysno::tkill => self.sys_tkill(args[0] as _, args[1] as _).await,
+            Sysno::sigaltstack => self.sys_sigaltstack(args[0].into(), args[1].into()).await,
+            Sysno::rt_sigqueueinfo => {
+                self.sys_rt_sigqueueinfo(args[0] as _, args[1] as _, args[2].into())
+                    .await
+            }
+            Sysno::rt_tgsigqueueinfo => {
+                self.sys_rt_tgsigqueueinfo(args[0] as _, args[1] as _, args[2] as _, args[3].into())
+                    .await
+            }
             Sysno::rt_sigreturn => self.sys_sigreturn().await,
+            Sysno::set_robust_list => self.sys_set_robust_list(args[0] as _, args[1] as _).await,
             Sysno::get_robust_list => {
-                warn!("SYS_GET_ROBUST_LIST @ ");
-                Ok(0)
-            } // always ok for now
+                self.sys_get_robust_list(args[0] as _, args[1].into(), args[2].into())
+                    .await
+            }
             Sysno::ppoll => {
                 self.sys_ppoll(args[0].into(), args[1] as _, args[2].into(), args[3] as _)
                     .await
@@ -272,7 +345,7 @@ impl UserTaskContainer {
             }
             Sysno::sysinfo => self.sys_info(args[0].into()).await,
             Sysno::msync => self.sys_msync(args[0], args[1], args[2] as _).await,
-            Sysno::exit_group => self.sys_exit_group(args[0]),
+            Sysno::exit_group => self.sys_exit_group(args[0]).await,
             Sysno::ftruncate => self.sys_ftruncate(args[0], args[1]).await,
             Sysno::shmget => {
                 self.sys_shmget(args[0] as _, args[1] as _, args[2] as _)
@@ -290,6 +363,18 @@ impl UserTaskContainer {
                 self.sys_setitimer(args[0] as _, args[1].into(), args[2].into())
                     .await
             }
+            Sysno::getitimer => self.sys_getitimer(args[0] as _, args[1].into()).await,
+            Sysno::timer_create => {
+                self.sys_timer_create(args[0] as _, args[1].into(), args[2].into())
+                    .await
+            }
+            Sysno::timer_settime => {
+                self.sys_timer_settime(args[0] as _, args[1] as _, args[2].into(), args[3].into())
+                    .await
+            }
+            Sysno::timer_gettime => self.sys_timer_gettime(args[0] as _, args[1].into()).await,
+            Sysno::timer_getoverrun => self.sys_timer_getoverrun(args[0] as _).await,
+            Sysno::timer_delete => self.sys_timer_delete(args[0] as _).await,
             Sysno::setsockopt => {
                 self.sys_setsockopt(
                     args[0] as _,
@@ -326,6 +411,7 @@ impl UserTaskContainer {
                     .await
             }
             Sysno::clock_getres => self.sys_clock_getres(args[0] as _, args[1].into()).await,
+            Sysno::clock_settime => self.sys_clock_settime(args[0] as _, args[1].into()).await,
             Sysno::clock_nanosleep => {
                 self.sys_clock_nanosleep(args[0] as _, args[1] as _, args[2].into(), args[3].into())
                     .await
@@ -372,7 +458,7 @@ impl UserTaskContainer {
                 self.sys_sched_getaffinity(args[0], args[1], args[2].into())
                     .await
             }
-            Sysno::setgroups => Ok(0),
+            Sysno::setgroups => self.sys_setgroups(args[0] as _, args[1].into()).await,
             Sysno::renameat2 => {
                 self.sys_renameat2(
                     args[0] as _,
@@ -449,6 +535,7 @@ impl UserTaskContainer {
                 self.sys_symlinkat(args[0].into(), AT_CWD, args[1].into())
                     .await
             }
+            Sysno::prctl => self.sys_prctl(args[0], args[1]).await,
             #[cfg(target_arch = "x86_64")]
             Sysno::arch_prctl => self.sys_arch_prctl(args[0], args[1]).await,
             #[cfg(target_arch = "x86_64")]
@@ -456,6 +543,8 @@ impl UserTaskContainer {
             #[cfg(target_arch = "x86_64")]
             Sysno::fork => self.sys_fork().await,
             #[cfg(target_arch = "x86_64")]
+            Sysno::vfork => self.sys_vfork().await,
+            #[cfg(target_arch = "x86_64")]
             Sysno::pipe => self.sys_pipe2(args[0].into(), 0).await,
             #[cfg(target_arch = "x86_64")]
             Sysno::unlink | Sysno::rmdir => self.sys_unlink(args[0].into()).await,
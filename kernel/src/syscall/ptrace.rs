@@ -0,0 +1,220 @@
+use super::{
+    types::{
+        fd::IoVec,
+        ptrace::{PtraceOptions, PtraceRequest, NT_PRSTATUS},
+        signal::SigInfo,
+    },
+    SysResult,
+};
+use crate::{
+    tasks::{
+        ptrace::{access_tracee, set_elf_gregs, Ptrace, PtraceStop, ELF_NGREG},
+        tid2user_task, UserTask,
+    },
+    user::UserTaskContainer,
+    utils::useref::UserRef,
+};
+use alloc::sync::Arc;
+use core::{cmp::min, mem::size_of};
+use log::debug;
+use num_traits::FromPrimitive;
+use signal::SignalFlags;
+use syscalls::Errno;
+
+type Regs = [usize; ELF_NGREG];
+
+impl UserTaskContainer {
+    /// The requests except TRACEME, ATTACH, SEIZE and KILL need the tracee
+    /// which is traced by the caller and stopped.
+    pub async fn sys_ptrace(
+        &self,
+        request: usize,
+        pid: usize,
+        addr: usize,
+        data: usize,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_ptrace @ request: {:#x}, pid: {}, addr: {:#x}, data: {:#x}",
+            self.tid, request, pid, addr, data
+        );
+        let request = PtraceRequest::from_usize(request).ok_or(Errno::EIO)?;
+        match request {
+            PtraceRequest::TraceMe => return self.ptrace_traceme(),
+            PtraceRequest::Attach | PtraceRequest::Seize => {
+                return self.ptrace_attach(request, pid, data)
+            }
+            _ => {}
+        }
+        let tracee = self.tracee(pid, request != PtraceRequest::Kill)?;
+        match request {
+            PtraceRequest::PeekText | PtraceRequest::PeekData => {
+                let mut word = [0u8; size_of::<usize>()];
+                access_tracee(&tracee, addr, &mut word, false)?;
+                *UserRef::<usize>::from(data).get_mut() = usize::from_ne_bytes(word);
+            }
+            PtraceRequest::PokeText | PtraceRequest::PokeData => {
+                access_tracee(&tracee, addr, &mut data.to_ne_bytes(), true)?;
+            }
+            // the user area starts with the registers.
+            PtraceRequest::PeekUser => {
+                let index = user_index(addr)?;
+                *UserRef::<usize>::from(data).get_mut() = tracee.ptrace_regs()[index];
+            }
+            PtraceRequest::PokeUser => {
+                let mut regs = tracee.ptrace_regs();
+                regs[user_index(addr)?] = data;
+                set_elf_gregs(&mut tracee.tcb.write().cx, &regs);
+            }
+            PtraceRequest::GetRegs => {
+                *UserRef::<Regs>::from(data).get_mut() = tracee.ptrace_regs();
+            }
+            PtraceRequest::SetRegs => {
+                let regs = UserRef::<Regs>::from(data).get_ref();
+                set_elf_gregs(&mut tracee.tcb.write().cx, regs);
+            }
+            PtraceRequest::GetRegSet | PtraceRequest::SetRegSet => {
+                if addr != NT_PRSTATUS {
+                    return Err(Errno::EINVAL);
+                }
+                let iov = UserRef::<IoVec>::from(data).get_mut();
+                let len = min(iov.len, size_of::<Regs>());
+                let buf = UserRef::<u8>::from(iov.base).slice_mut_with_len(len);
+                let mut regs = tracee.ptrace_regs();
+                let bytes = unsafe {
+                    core::slice::from_raw_parts_mut(regs.as_mut_ptr() as *mut u8, size_of::<Regs>())
+                };
+                match request == PtraceRequest::GetRegSet {
+                    true => buf.copy_from_slice(&bytes[..len]),
+                    false => {
+                        bytes[..len].copy_from_slice(buf);
+                        set_elf_gregs(&mut tracee.tcb.write().cx, &regs);
+                    }
+                }
+                iov.len = len;
+            }
+            PtraceRequest::Cont
+            | PtraceRequest::Syscall
+            | PtraceRequest::SingleStep
+            | PtraceRequest::Detach => {
+                if data > 64 {
+                    return Err(Errno::EIO);
+                }
+                // the breakpoints of the last step are left if it stopped by a signal.
+                tracee.ptrace_step_done();
+                if request == PtraceRequest::SingleStep {
+                    tracee.ptrace_step()?;
+                }
+                let mut tcb = tracee.tcb.write();
+                let trace = tcb.ptrace.as_mut().ok_or(Errno::ESRCH)?;
+                trace.trace_syscall = request == PtraceRequest::Syscall;
+                trace.signal = data;
+                trace.detached = request == PtraceRequest::Detach;
+                trace.stop = None;
+                drop(tcb);
+                if request == PtraceRequest::Detach {
+                    self.task
+                        .pcb
+                        .lock()
+                        .tracees
+                        .retain(|x| x.as_ptr() != Arc::as_ptr(&tracee));
+                }
+            }
+            PtraceRequest::Kill => tracee.send_signal(SignalFlags::SIGKILL),
+            PtraceRequest::SetOptions => {
+                let options = PtraceOptions::from_bits(data).ok_or(Errno::EINVAL)?;
+                if let Some(trace) = tracee.tcb.write().ptrace.as_mut() {
+                    trace.options = options;
+                }
+            }
+            PtraceRequest::GetEventMsg => {
+                let msg = tracee.tcb.read().ptrace.as_ref().map_or(0, |x| x.event_msg);
+                *UserRef::<usize>::from(data).get_mut() = msg;
+            }
+            PtraceRequest::GetSigInfo => {
+                let sigtrap = SignalFlags::SIGTRAP.num() as i32;
+                let stop = tracee.tcb.read().ptrace.as_ref().and_then(|x| x.stop);
+                *UserRef::<SigInfo>::from(data).get_mut() = match stop {
+                    // SI_USER
+                    Some(PtraceStop::Signal(signal)) => SigInfo::new(signal as _, 0),
+                    Some(PtraceStop::Syscall(_)) => SigInfo::new(sigtrap, sigtrap | 0x80),
+                    Some(PtraceStop::Event(event)) => {
+                        SigInfo::new(sigtrap, sigtrap | (event as i32) << 8)
+                    }
+                    None => return Err(Errno::EINVAL),
+                };
+            }
+            PtraceRequest::TraceMe | PtraceRequest::Attach | PtraceRequest::Seize => {
+                unreachable!()
+            }
+        }
+        Ok(0)
+    }
+
+    /// The parent becomes the tracer.
+    fn ptrace_traceme(&self) -> SysResult {
+        let parent = self.task.parent.read().upgrade().ok_or(Errno::EPERM)?;
+        let mut tcb = self.task.tcb.write();
+        if tcb.ptrace.is_some() {
+            return Err(Errno::EPERM);
+        }
+        tcb.ptrace = Some(Ptrace::new(&parent, PtraceOptions::empty()));
+        drop(tcb);
+        parent.pcb.lock().tracees.push(Arc::downgrade(&self.task));
+        Ok(0)
+    }
+
+    /// The tracer needs the same ids as the tracee unless it is privileged,
+    /// the tracee which isn't dumpable can't be attached. PTRACE_ATTACH stops the tracee by SIGSTOP, PTRACE_SEIZE doesn't.
+    fn ptrace_attach(&self, request: PtraceRequest, pid: usize, data: usize) -> SysResult {
+        let tracee = tid2user_task(pid).ok_or(Errno::ESRCH)?;
+        if Arc::ptr_eq(&tracee.pcb, &self.task.pcb) {
+            return Err(Errno::EPERM);
+        }
+        let cred = self.task.pcb.lock().cred.clone();
+        if !cred.may_access(&tracee.pcb.lock().cred) {
+            return Err(Errno::EPERM);
+        }
+        if !tracee.pcb.lock().dumpable {
+            return Err(Errno::EPERM);
+        }
+        let options = match request {
+            PtraceRequest::Seize => PtraceOptions::from_bits(data).ok_or(Errno::EIO)?,
+            _ => PtraceOptions::empty(),
+        };
+        let mut tcb = tracee.tcb.write();
+        if tcb.ptrace.is_some() {
+            return Err(Errno::EPERM);
+        }
+        tcb.ptrace = Some(Ptrace::new(&self.task, options));
+        drop(tcb);
+        self.task.pcb.lock().tracees.push(Arc::downgrade(&tracee));
+        if request == PtraceRequest::Attach {
+            tracee.send_signal(SignalFlags::SIGSTOP);
+        }
+        Ok(0)
+    }
+
+    /// The tracee traced by the caller, it is stopped unless `stopped` is false.
+    fn tracee(&self, pid: usize, stopped: bool) -> Result<Arc<UserTask>, Errno> {
+        let tracee = tid2user_task(pid).ok_or(Errno::ESRCH)?;
+        let tcb = tracee.tcb.read();
+        let trace = tcb.ptrace.as_ref().ok_or(Errno::ESRCH)?;
+        let is_tracer = trace
+            .tracer
+            .upgrade()
+            .is_some_and(|x| Arc::ptr_eq(&x.pcb, &self.task.pcb));
+        if !is_tracer || (stopped && trace.stop.is_none()) {
+            return Err(Errno::ESRCH);
+        }
+        drop(tcb);
+        Ok(tracee)
+    }
+}
+
+/// The register index of the offset in the user area.
+fn user_index(addr: usize) -> Result<usize, Errno> {
+    match addr % size_of::<usize>() == 0 && addr / size_of::<usize>() < ELF_NGREG {
+        true => Ok(addr / size_of::<usize>()),
+        false => Err(Errno::EIO),
+    }
+}
@@ -67,7 +67,7 @@ impl UserTaskContainer {
                     .map(x.0, vaddr + i * PAGE_SIZE, MappingFlags::URWX);
             });
         let size = trackers.as_ref().unwrap().trackers.len() * PAGE_SIZE;
-        self.task.pcb.lock().shms.push(MapedSharedMemory {
+        self.task.mm().lock().shms.push(MapedSharedMemory {
             key: shmid,
             mem: trackers.unwrap(),
             start: vaddr.raw(),
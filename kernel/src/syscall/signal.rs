@@ -1,11 +1,23 @@
-use executor::yield_now;
+use executor::{select, yield_now, Either};
+use fs::{file::File, TimeSpec};
 use log::debug;
+use polyhal_trap::trapframe::TrapFrameArgs;
 use signal::{SigAction, SigMaskHow, SigProcMask, SignalFlags};
 use syscalls::Errno;
+use vfscore::OpenFlags;
 
-use crate::{tasks::WaitSignal, user::UserTaskContainer, utils::useref::UserRef};
+use crate::{
+    tasks::{tid2user_task, PidFd, SignalFd, UserTask, WaitSignal},
+    user::UserTaskContainer,
+    utils::{time::current_nsec, useref::UserRef},
+};
+use alloc::sync::Arc;
 
-use super::SysResult;
+use super::{
+    time::WaitUntilsec,
+    types::signal::{SigInfo, SignalStack, SignalStackFlags, MINSIGSTKSZ, SI_TKILL, SI_USER},
+    SysResult,
+};
 
 /*
  * 忽略信号：不采取任何操作、有两个信号不能被忽略：SIGKILL和SIGSTOP。
@@ -54,15 +66,98 @@ use super::SysResult;
  *  }
  */
 
+/// SIGKILL and SIGSTOP can't be waited or blocked.
+const UNBLOCKABLE: usize =
+    SignalFlags::SIGKILL.bits() as usize | SignalFlags::SIGSTOP.bits() as usize;
+
 impl UserTaskContainer {
-    /// TODO: finish sigtimedwait
-    pub async fn sys_sigtimedwait(&self) -> SysResult {
-        debug!("sys_sigtimedwait @ ");
-        WaitSignal(self.task.clone()).await;
-        // let task = current_user_task();
-        // task.inner_map(|x| x.signal.has_signal());
-        // Err(LinuxError::EAGAIN)
-        Ok(0)
+    /// Wait for the signals in the set and take one of them, the other
+    /// unblocked signals interrupt the waiting.
+    pub async fn sys_sigtimedwait(
+        &self,
+        set: UserRef<SigProcMask>,
+        info: UserRef<SigInfo>,
+        timeout: UserRef<TimeSpec>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_sigtimedwait @ set: {}, info: {}, timeout: {}",
+            self.tid, set, info, timeout
+        );
+        let set = set.get_ref().mask & !UNBLOCKABLE;
+        // the deadline which overflows never comes.
+        let deadline = match timeout.is_valid() {
+            true => {
+                let timeout = timeout.get_ref();
+                if timeout.nsec >= 1_000_000_000 {
+                    return Err(Errno::EINVAL);
+                }
+                let deadline = timeout
+                    .sec
+                    .checked_mul(1_000_000_000)
+                    .and_then(|x| x.checked_add(timeout.nsec))
+                    .and_then(|x| x.checked_add(current_nsec()));
+                Some(deadline.unwrap_or(usize::MAX))
+            }
+            false => None,
+        };
+        loop {
+            if let Some(siginfo) = self.task.take_signal(set) {
+                if info.is_valid() {
+                    *info.get_mut() = siginfo;
+                }
+                return Ok(siginfo.signo as _);
+            }
+            // only the signals of the thread itself interrupt the waiting,
+            // the ones of the main thread are taken only if they are in the set.
+            let sigmask = self.task.tcb.read().sigmask.mask;
+            if self.task.tcb.read().signal.signal & !sigmask != 0 {
+                return Err(Errno::EINTR);
+            }
+            let wait = WaitSignal(self.task.clone(), set, !sigmask);
+            match deadline {
+                Some(deadline) => {
+                    if let Either::Right(_) = select(wait, WaitUntilsec(deadline)).await {
+                        return Err(Errno::EAGAIN);
+                    }
+                }
+                None => wait.await,
+            }
+        }
+    }
+
+    /// Create the signalfd or change the mask of it.
+    pub async fn sys_signalfd4(
+        &self,
+        fd: isize,
+        mask: UserRef<SigProcMask>,
+        _sizemask: usize,
+        flags: usize,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_signalfd4 @ fd: {}, mask: {}, flags: {:#x}",
+            self.tid, fd, mask, flags
+        );
+        // SFD_NONBLOCK and SFD_CLOEXEC are the same as the open flags.
+        let flags = OpenFlags::from_bits(flags as _).ok_or(Errno::EINVAL)?;
+        if !(OpenFlags::O_NONBLOCK | OpenFlags::O_CLOEXEC).contains(flags.clone()) {
+            return Err(Errno::EINVAL);
+        }
+        let mask = mask.get_ref().mask & !UNBLOCKABLE;
+        if fd != -1 {
+            let file = self.task.get_fd(fd as _).ok_or(Errno::EBADF)?;
+            let signalfd = file
+                .inner
+                .clone()
+                .downcast_arc::<SignalFd>()
+                .map_err(|_| Errno::EINVAL)?;
+            *signalfd.mask.lock() = mask;
+            return Ok(fd as _);
+        }
+        let fd = self.task.alloc_fd().ok_or(Errno::EMFILE)?;
+        let file = File::new_dev(Arc::new(SignalFd::new(mask)));
+        *file.flags.lock() |= flags;
+        self.task.set_fd(fd, file);
+        Ok(fd)
     }
 
     pub async fn sys_sigprocmask(
@@ -108,10 +203,10 @@ impl UserTaskContainer {
             signal, act, oldact
         );
         if oldact.is_valid() {
-            *oldact.get_mut() = self.task.pcb.lock().sigaction[sig];
+            *oldact.get_mut() = self.task.sighand().lock()[sig];
         }
         if act.is_valid() {
-            self.task.pcb.lock().sigaction[sig] = *act.get_mut();
+            self.task.sighand().lock()[sig] = *act.get_mut();
         }
         Ok(0)
     }
@@ -132,4 +227,159 @@ impl UserTaskContainer {
         // Err(LinuxError::EPERM)
         Ok(0)
     }
+
+    /// Send the signal with the siginfo given by the user, the si_code
+    /// must be negative unless it is sent to the current process.
+    pub async fn sys_rt_sigqueueinfo(
+        &self,
+        pid: usize,
+        signum: usize,
+        uinfo: UserRef<SigInfo>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_rt_sigqueueinfo @ pid: {}, signum: {}, uinfo: {}",
+            self.tid, pid, signum, uinfo
+        );
+        let task = tid2user_task(pid)
+            .filter(|x| x.process_id == pid)
+            .ok_or(Errno::ESRCH)?;
+        self.sigqueue(task, signum, uinfo, true)
+    }
+
+    pub async fn sys_rt_tgsigqueueinfo(
+        &self,
+        tgid: usize,
+        tid: usize,
+        signum: usize,
+        uinfo: UserRef<SigInfo>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_rt_tgsigqueueinfo @ tgid: {}, tid: {}, signum: {}, uinfo: {}",
+            self.tid, tgid, tid, signum, uinfo
+        );
+        let task = tid2user_task(tid)
+            .filter(|x| x.process_id == tgid)
+            .ok_or(Errno::ESRCH)?;
+        self.sigqueue(task, signum, uinfo, false)
+    }
+
+    /// Send the signal to the process referred by the pidfd, the siginfo
+    /// is the same as kill unless it is given.
+    pub async fn sys_pidfd_send_signal(
+        &self,
+        pidfd: usize,
+        signum: usize,
+        uinfo: UserRef<SigInfo>,
+        flags: usize,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_pidfd_send_signal @ pidfd: {}, signum: {}, uinfo: {}, flags: {:#x}",
+            self.tid, pidfd, signum, uinfo, flags
+        );
+        if flags != 0 || signum > 64 {
+            return Err(Errno::EINVAL);
+        }
+        let pidfd = self
+            .task
+            .get_fd(pidfd)
+            .ok_or(Errno::EBADF)?
+            .inner
+            .clone()
+            .downcast_arc::<PidFd>()
+            .map_err(|_| Errno::EBADF)?;
+        let task = pidfd
+            .task
+            .upgrade()
+            .filter(|_| !pidfd.exited())
+            .ok_or(Errno::ESRCH)?;
+        if !self.may_signal(&task) {
+            return Err(Errno::EPERM);
+        }
+        if uinfo.is_valid() {
+            if uinfo.get_ref().signo as usize != signum {
+                return Err(Errno::EINVAL);
+            }
+            return self.sigqueue(task, signum, uinfo, true);
+        }
+        if signum != 0 {
+            task.try_send_process_siginfo(self.kill_info(signum, SI_USER))?;
+        }
+        Ok(0)
+    }
+
+    /// The signal is sent to the thread which doesn't block it if it is
+    /// directed to the process. The queue of the real time signals is
+    /// limited by RLIMIT_SIGPENDING.
+    fn sigqueue(
+        &self,
+        task: Arc<UserTask>,
+        signum: usize,
+        uinfo: UserRef<SigInfo>,
+        process: bool,
+    ) -> SysResult {
+        if signum > 64 {
+            return Err(Errno::EINVAL);
+        }
+        if !self.may_signal(&task) {
+            return Err(Errno::EPERM);
+        }
+        let mut info = *uinfo.get_ref();
+        // the user can't pretend to be the kernel or kill.
+        if task.process_id != self.task.process_id && (info.code >= 0 || info.code == SI_TKILL) {
+            return Err(Errno::EPERM);
+        }
+        if signum != 0 {
+            info.signo = signum as _;
+            match process {
+                true => task.try_send_process_siginfo(info)?,
+                false => task.try_send_siginfo(info)?,
+            }
+        }
+        Ok(0)
+    }
+
+    /// Set or get the alternate signal stack of the thread, it can't be
+    /// changed when the thread is running on it.
+    pub async fn sys_sigaltstack(
+        &self,
+        ss: UserRef<SignalStack>,
+        old_ss: UserRef<SignalStack>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_sigaltstack @ ss: {}, old_ss: {}",
+            self.tid, ss, old_ss
+        );
+        let mut tcb = self.task.tcb.write();
+        let on_stack = tcb.sigaltstack.contains(tcb.cx[TrapFrameArgs::SP]);
+        if old_ss.is_valid() {
+            let mut stack = tcb.sigaltstack;
+            if on_stack {
+                stack.flags |= SignalStackFlags::ONSTACK;
+            }
+            *old_ss.get_mut() = stack;
+        }
+        if ss.is_valid() {
+            let mut stack = *ss.get_ref();
+            // SS_ONSTACK is accepted as 0 for the old programs.
+            let mode = stack.flags - SignalStackFlags::AUTODISARM;
+            if on_stack {
+                return Err(Errno::EPERM);
+            }
+            if !mode.is_empty()
+                && mode != SignalStackFlags::DISABLE
+                && mode != SignalStackFlags::ONSTACK
+            {
+                return Err(Errno::EINVAL);
+            }
+            if mode == SignalStackFlags::DISABLE {
+                stack = SignalStack::disabled();
+            } else if stack.size < MINSIGSTKSZ {
+                return Err(Errno::ENOMEM);
+            } else {
+                stack.flags &= SignalStackFlags::AUTODISARM;
+            }
+            tcb.sigaltstack = stack;
+        }
+        Ok(0)
+    }
 }
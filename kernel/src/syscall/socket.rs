@@ -1,5 +1,6 @@
 use super::SysResult;
 use crate::socket::{self, NetType};
+use crate::tasks::interruptible;
 use crate::user::socket_pair::create_socket_pair;
 use crate::user::UserTaskContainer;
 use crate::utils::useref::UserRef;
@@ -187,25 +188,23 @@ impl UserTaskContainer {
             .map_err(|_| Errno::EINVAL)?;
         debug!("flags: {:?}", file.flags.lock());
         let fd = self.task.alloc_fd().ok_or(Errno::EMFILE)?;
-        loop {
-            if let Ok(new_socket) = socket.inner.accept() {
-                self.task.set_fd(
-                    fd,
-                    File::new_dev(Socket::new_with_inner(
-                        socket.domain,
-                        socket.net_type,
-                        new_socket,
-                    )),
-                );
-                break;
-            }
-
-            if self.task.tcb.read().signal.has_signal() {
-                return Err(Errno::EINTR);
+        let accept = async {
+            loop {
+                if let Ok(new_socket) = socket.inner.accept() {
+                    break Ok(new_socket);
+                }
+                yield_now().await;
             }
-
-            yield_now().await;
-        }
+        };
+        let new_socket = interruptible(self.task.clone(), accept).await?;
+        self.task.set_fd(
+            fd,
+            File::new_dev(Socket::new_with_inner(
+                socket.domain,
+                socket.net_type,
+                new_socket,
+            )),
+        );
         Ok(fd)
     }
 
@@ -475,24 +474,28 @@ impl UserTaskContainer {
             .downcast_arc::<Socket>()
             .map_err(|_| Errno::EINVAL)?;
         let fd = self.task.alloc_fd().ok_or(Errno::EMFILE)?;
-        loop {
-            if let Ok(new_socket) = socket.inner.accept() {
-                let sa = socket_addr.get_mut();
-                sa.family = 2;
-                sa.in_port = new_socket.get_remote().unwrap().port();
-                sa.addr = new_socket.get_remote().unwrap().ip().clone();
-                let new_file = File::new_dev(Socket::new_with_inner(
-                    socket.domain,
-                    socket.net_type,
-                    new_socket,
-                ));
-                *new_file.flags.lock() = flags;
-                self.task.set_fd(fd, new_file);
-                break Ok(fd);
-            } else if file.flags.lock().contains(OpenFlags::O_NONBLOCK) {
-                break Err(Errno::EAGAIN);
+        let accept = async {
+            loop {
+                if let Ok(new_socket) = socket.inner.accept() {
+                    break Ok(new_socket);
+                } else if file.flags.lock().contains(OpenFlags::O_NONBLOCK) {
+                    break Err(Errno::EAGAIN);
+                }
+                yield_now().await;
             }
-            yield_now().await;
-        }
+        };
+        let new_socket = interruptible(self.task.clone(), accept).await?;
+        let sa = socket_addr.get_mut();
+        sa.family = 2;
+        sa.in_port = new_socket.get_remote().unwrap().port();
+        sa.addr = *new_socket.get_remote().unwrap().ip();
+        let new_file = File::new_dev(Socket::new_with_inner(
+            socket.domain,
+            socket.net_type,
+            new_socket,
+        ));
+        *new_file.flags.lock() = flags;
+        self.task.set_fd(fd, new_file);
+        Ok(fd)
     }
 }
@@ -1,9 +1,19 @@
 use super::{
-    types::sys::{Rlimit, UTSname},
+    types::sys::{
+        PrctlOption, Rlimit, UTSname, NR_OPEN, RLIMIT_NOFILE, RLIM_NLIMITS, TASK_COMM_LEN,
+    },
     SysResult,
 };
-use crate::{user::UserTaskContainer, utils::useref::UserRef};
-use log::{debug, warn};
+use crate::{
+    tasks::{cred::NGROUPS_MAX, processes, tid2user_task, UserTask},
+    user::UserTaskContainer,
+    utils::useref::UserRef,
+};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::cmp::min;
+use log::debug;
+use num_traits::FromPrimitive;
+use syscalls::Errno;
 
 impl UserTaskContainer {
     pub async fn sys_uname(&self, uts_ptr: UserRef<UTSname>) -> SysResult {
@@ -33,7 +43,8 @@ impl UserTaskContainer {
         Ok(0)
     }
 
-    /// TODO: FINISH sys_getrlimit
+    /// The soft limit can't exceed the hard limit, only the privileged task
+    /// can raise the hard limit. The old limit is read before setting.
     pub async fn sys_prlimit64(
         &self,
         pid: usize,
@@ -42,51 +53,353 @@ impl UserTaskContainer {
         old_limit: UserRef<Rlimit>,
     ) -> SysResult {
         debug!(
-            "sys_getrlimit @ pid: {}, resource: {}, new_limit: {}, old_limit: {}",
+            "sys_prlimit64 @ pid: {}, resource: {}, new_limit: {}, old_limit: {}",
             pid, resource, new_limit, old_limit
         );
-        match resource {
-            7 => {
-                if new_limit.is_valid() {
-                    let rlimit = new_limit.get_mut();
-                    self.task.inner_map(|x| {
-                        x.rlimits[7] = rlimit.max;
-                    })
-                }
-                if old_limit.is_valid() {
-                    let rlimit = old_limit.get_mut();
-                    rlimit.max = self.task.inner_map(|inner| inner.rlimits[7]);
-                    rlimit.curr = rlimit.max;
-                }
+        if resource >= RLIM_NLIMITS {
+            return Err(Errno::EINVAL);
+        }
+        let task = match pid {
+            0 => self.task.clone(),
+            _ => tid2user_task(pid).ok_or(Errno::ESRCH)?,
+        };
+        let cred = self.task.pcb.lock().cred.clone();
+        if !Arc::ptr_eq(&task.pcb, &self.task.pcb) && !cred.may_access(&task.pcb.lock().cred) {
+            return Err(Errno::EPERM);
+        }
+        let new = match new_limit.is_valid() {
+            true => Some(*new_limit.get_ref()),
+            false => None,
+        };
+        let mut pcb = task.pcb.lock();
+        let old = pcb.rlimits[resource];
+        if let Some(new) = new {
+            if new.curr > new.max || (resource == RLIMIT_NOFILE && new.max > NR_OPEN) {
+                return Err(Errno::EINVAL);
             }
-            _ => {
-                warn!("need to finish prlimit64: resource {}", resource)
+            if new.max > old.max && !cred.privileged() {
+                return Err(Errno::EPERM);
             }
+            pcb.rlimits[resource] = new;
+        }
+        drop(pcb);
+        if old_limit.is_valid() {
+            *old_limit.get_mut() = old;
         }
         Ok(0)
     }
 
+    pub async fn sys_getrlimit(&self, resource: usize, rlimit: UserRef<Rlimit>) -> SysResult {
+        self.sys_prlimit64(0, resource, UserRef::from(0), rlimit)
+            .await
+    }
+
+    pub async fn sys_setrlimit(&self, resource: usize, rlimit: UserRef<Rlimit>) -> SysResult {
+        self.sys_prlimit64(0, resource, rlimit, UserRef::from(0))
+            .await
+    }
+
     pub async fn sys_geteuid(&self) -> SysResult {
-        Ok(0)
+        Ok(self.task.pcb.lock().cred.euid as _)
     }
 
     pub async fn sys_getegid(&self) -> SysResult {
-        Ok(0)
+        Ok(self.task.pcb.lock().cred.egid as _)
     }
 
     pub async fn sys_getgid(&self) -> SysResult {
-        Ok(0)
+        Ok(self.task.pcb.lock().cred.gid as _)
     }
 
     pub async fn sys_getuid(&self) -> SysResult {
+        Ok(self.task.pcb.lock().cred.uid as _)
+    }
+
+    pub async fn sys_getresuid(
+        &self,
+        ruid: UserRef<u32>,
+        euid: UserRef<u32>,
+        suid: UserRef<u32>,
+    ) -> SysResult {
+        debug!(
+            "sys_getresuid @ ruid: {}, euid: {}, suid: {}",
+            ruid, euid, suid
+        );
+        if !ruid.is_valid() || !euid.is_valid() || !suid.is_valid() {
+            return Err(Errno::EFAULT);
+        }
+        let cred = self.task.pcb.lock().cred.clone();
+        *ruid.get_mut() = cred.uid;
+        *euid.get_mut() = cred.euid;
+        *suid.get_mut() = cred.suid;
         Ok(0)
     }
 
-    pub async fn sys_getpgid(&self) -> SysResult {
+    pub async fn sys_getresgid(
+        &self,
+        rgid: UserRef<u32>,
+        egid: UserRef<u32>,
+        sgid: UserRef<u32>,
+    ) -> SysResult {
+        debug!(
+            "sys_getresgid @ rgid: {}, egid: {}, sgid: {}",
+            rgid, egid, sgid
+        );
+        if !rgid.is_valid() || !egid.is_valid() || !sgid.is_valid() {
+            return Err(Errno::EFAULT);
+        }
+        let cred = self.task.pcb.lock().cred.clone();
+        *rgid.get_mut() = cred.gid;
+        *egid.get_mut() = cred.egid;
+        *sgid.get_mut() = cred.sgid;
         Ok(0)
     }
 
-    pub async fn sys_setpgid(&self, _pid: usize, _pgid: usize) -> SysResult {
+    /// The privileged process sets all the uids,
+    /// others can only set the euid to the real or saved uid.
+    pub async fn sys_setuid(&self, uid: u32) -> SysResult {
+        debug!("sys_setuid @ uid: {}", uid);
+        let cred = &mut self.task.pcb.lock().cred;
+        if cred.privileged() {
+            cred.uid = uid;
+            cred.suid = uid;
+        } else if uid != cred.uid && uid != cred.suid {
+            return Err(Errno::EPERM);
+        }
+        cred.euid = uid;
+        cred.fsuid = uid;
+        Ok(0)
+    }
+
+    pub async fn sys_setgid(&self, gid: u32) -> SysResult {
+        debug!("sys_setgid @ gid: {}", gid);
+        let cred = &mut self.task.pcb.lock().cred;
+        if cred.privileged() {
+            cred.gid = gid;
+            cred.sgid = gid;
+        } else if gid != cred.gid && gid != cred.sgid {
+            return Err(Errno::EPERM);
+        }
+        cred.egid = gid;
+        cred.fsgid = gid;
+        Ok(0)
+    }
+
+    /// -1 keeps the id, the saved uid is set to the new euid if the real
+    /// uid is set or the euid is set to a value not equal to the real uid.
+    pub async fn sys_setreuid(&self, ruid: u32, euid: u32) -> SysResult {
+        debug!("sys_setreuid @ ruid: {}, euid: {}", ruid, euid);
+        let cred = &mut self.task.pcb.lock().cred;
+        if !cred.privileged()
+            && ((ruid != u32::MAX && ruid != cred.uid && ruid != cred.euid)
+                || (euid != u32::MAX && ![cred.uid, cred.euid, cred.suid].contains(&euid)))
+        {
+            return Err(Errno::EPERM);
+        }
+        let old_uid = cred.uid;
+        if ruid != u32::MAX {
+            cred.uid = ruid;
+        }
+        if euid != u32::MAX {
+            cred.euid = euid;
+        }
+        if ruid != u32::MAX || (euid != u32::MAX && euid != old_uid) {
+            cred.suid = cred.euid;
+        }
+        cred.fsuid = cred.euid;
+        Ok(0)
+    }
+
+    pub async fn sys_setregid(&self, rgid: u32, egid: u32) -> SysResult {
+        debug!("sys_setregid @ rgid: {}, egid: {}", rgid, egid);
+        let cred = &mut self.task.pcb.lock().cred;
+        if !cred.privileged()
+            && ((rgid != u32::MAX && rgid != cred.gid && rgid != cred.egid)
+                || (egid != u32::MAX && ![cred.gid, cred.egid, cred.sgid].contains(&egid)))
+        {
+            return Err(Errno::EPERM);
+        }
+        let old_gid = cred.gid;
+        if rgid != u32::MAX {
+            cred.gid = rgid;
+        }
+        if egid != u32::MAX {
+            cred.egid = egid;
+        }
+        if rgid != u32::MAX || (egid != u32::MAX && egid != old_gid) {
+            cred.sgid = cred.egid;
+        }
+        cred.fsgid = cred.egid;
+        Ok(0)
+    }
+
+    /// -1 keeps the id, the unprivileged process can only set the ids
+    /// to one of the current real, effective and saved uid.
+    pub async fn sys_setresuid(&self, ruid: u32, euid: u32, suid: u32) -> SysResult {
+        debug!(
+            "sys_setresuid @ ruid: {}, euid: {}, suid: {}",
+            ruid, euid, suid
+        );
+        let cred = &mut self.task.pcb.lock().cred;
+        let allowed = [cred.uid, cred.euid, cred.suid];
+        if !cred.privileged()
+            && [ruid, euid, suid]
+                .iter()
+                .any(|x| *x != u32::MAX && !allowed.contains(x))
+        {
+            return Err(Errno::EPERM);
+        }
+        if ruid != u32::MAX {
+            cred.uid = ruid;
+        }
+        if euid != u32::MAX {
+            cred.euid = euid;
+        }
+        if suid != u32::MAX {
+            cred.suid = suid;
+        }
+        cred.fsuid = cred.euid;
+        Ok(0)
+    }
+
+    pub async fn sys_setresgid(&self, rgid: u32, egid: u32, sgid: u32) -> SysResult {
+        debug!(
+            "sys_setresgid @ rgid: {}, egid: {}, sgid: {}",
+            rgid, egid, sgid
+        );
+        let cred = &mut self.task.pcb.lock().cred;
+        let allowed = [cred.gid, cred.egid, cred.sgid];
+        if !cred.privileged()
+            && [rgid, egid, sgid]
+                .iter()
+                .any(|x| *x != u32::MAX && !allowed.contains(x))
+        {
+            return Err(Errno::EPERM);
+        }
+        if rgid != u32::MAX {
+            cred.gid = rgid;
+        }
+        if egid != u32::MAX {
+            cred.egid = egid;
+        }
+        if sgid != u32::MAX {
+            cred.sgid = sgid;
+        }
+        cred.fsgid = cred.egid;
+        Ok(0)
+    }
+
+    /// Returns the old fsuid, it isn't changed if the fsuid isn't allowed.
+    pub async fn sys_setfsuid(&self, fsuid: u32) -> SysResult {
+        debug!("sys_setfsuid @ fsuid: {}", fsuid);
+        let cred = &mut self.task.pcb.lock().cred;
+        let old = cred.fsuid;
+        if cred.privileged() || [cred.uid, cred.euid, cred.suid, cred.fsuid].contains(&fsuid) {
+            cred.fsuid = fsuid;
+        }
+        Ok(old as _)
+    }
+
+    pub async fn sys_setfsgid(&self, fsgid: u32) -> SysResult {
+        debug!("sys_setfsgid @ fsgid: {}", fsgid);
+        let cred = &mut self.task.pcb.lock().cred;
+        let old = cred.fsgid;
+        if cred.privileged() || [cred.gid, cred.egid, cred.sgid, cred.fsgid].contains(&fsgid) {
+            cred.fsgid = fsgid;
+        }
+        Ok(old as _)
+    }
+
+    pub async fn sys_getgroups(&self, size: usize, list: UserRef<u32>) -> SysResult {
+        debug!("sys_getgroups @ size: {}, list: {}", size, list);
+        let groups = self.task.pcb.lock().cred.groups.clone();
+        if size == 0 {
+            return Ok(groups.len());
+        }
+        if size < groups.len() {
+            return Err(Errno::EINVAL);
+        }
+        if !list.is_valid() {
+            return Err(Errno::EFAULT);
+        }
+        list.slice_mut_with_len(groups.len())
+            .copy_from_slice(&groups);
+        Ok(groups.len())
+    }
+
+    pub async fn sys_setgroups(&self, size: usize, list: UserRef<u32>) -> SysResult {
+        debug!("sys_setgroups @ size: {}, list: {}", size, list);
+        if size > NGROUPS_MAX {
+            return Err(Errno::EINVAL);
+        }
+        if size > 0 && !list.is_valid() {
+            return Err(Errno::EFAULT);
+        }
+        let groups = match size {
+            0 => Vec::new(),
+            _ => list.slice_mut_with_len(size).to_vec(),
+        };
+        let cred = &mut self.task.pcb.lock().cred;
+        if !cred.privileged() {
+            return Err(Errno::EPERM);
+        }
+        cred.groups = groups;
+        Ok(0)
+    }
+
+    /// Get the process by the pid, 0 is the current process.
+    fn pid_task(&self, pid: usize) -> Result<Arc<UserTask>, Errno> {
+        match pid {
+            0 => Ok(self.task.clone()),
+            _ => tid2user_task(pid).ok_or(Errno::ESRCH),
+        }
+    }
+
+    pub async fn sys_getpgid(&self, pid: usize) -> SysResult {
+        debug!("sys_getpgid @ pid: {}", pid);
+        Ok(self.pid_task(pid)?.pcb.lock().pgid)
+    }
+
+    pub async fn sys_getsid(&self, pid: usize) -> SysResult {
+        debug!("sys_getsid @ pid: {}", pid);
+        Ok(self.pid_task(pid)?.pcb.lock().sid)
+    }
+
+    /// Move the process to the process group in the same session,
+    /// the process must be the current process or its child.
+    pub async fn sys_setpgid(&self, pid: usize, pgid: usize) -> SysResult {
+        debug!("sys_setpgid @ pid: {}, pgid: {}", pid, pgid);
+        let task = self.pid_task(pid)?;
+        let is_child = task
+            .parent
+            .read()
+            .upgrade()
+            .is_some_and(|x| x.process_id == self.task.process_id);
+        if task.process_id != self.task.process_id && !is_child {
+            return Err(Errno::ESRCH);
+        }
+        if is_child && task.pcb.lock().did_exec {
+            return Err(Errno::EACCES);
+        }
+        let pgid = match pgid {
+            0 => task.process_id,
+            _ => pgid,
+        };
+        let sid = self.task.pcb.lock().sid;
+        let task_sid = task.pcb.lock().sid;
+        // the session leader can't change its process group.
+        if task_sid != sid || task_sid == task.process_id {
+            return Err(Errno::EPERM);
+        }
+        // join a process group, it must be in the same session.
+        if pgid != task.process_id
+            && !processes()
+                .iter()
+                .any(|x| x.inner_map(|inner| inner.pgid == pgid && inner.sid == sid))
+        {
+            return Err(Errno::EPERM);
+        }
+        task.pcb.lock().pgid = pgid;
         Ok(0)
     }
 
@@ -144,6 +457,62 @@ impl UserTaskContainer {
         Ok(buf_len)
     }
 
+    pub async fn sys_prctl(&self, option: usize, arg2: usize) -> SysResult {
+        debug!(
+            "[task {}] sys_prctl @ option: {}, arg2: {:#x}",
+            self.tid, option, arg2
+        );
+        let option = PrctlOption::from_usize(option).ok_or(Errno::EINVAL)?;
+        match option {
+            PrctlOption::PR_SET_NAME => {
+                let name = UserRef::<u8>::from(arg2).slice_until_valid(|x| x != 0);
+                let len = min(name.len(), TASK_COMM_LEN - 1);
+                self.task.set_comm(&String::from_utf8_lossy(&name[..len]));
+            }
+            PrctlOption::PR_GET_NAME => {
+                let comm = self.task.comm();
+                let buf = UserRef::<u8>::from(arg2).slice_mut_with_len(TASK_COMM_LEN);
+                buf.fill(0);
+                buf[..comm.len()].copy_from_slice(comm.as_bytes());
+            }
+            PrctlOption::PR_SET_PDEATHSIG => {
+                if arg2 > 64 {
+                    return Err(Errno::EINVAL);
+                }
+                self.task.pcb.lock().pdeath_signal = arg2;
+            }
+            PrctlOption::PR_GET_PDEATHSIG => {
+                let signal = self.task.pcb.lock().pdeath_signal;
+                *UserRef::<i32>::from(arg2).get_mut() = signal as i32;
+            }
+            PrctlOption::PR_SET_DUMPABLE => {
+                if arg2 > 1 {
+                    return Err(Errno::EINVAL);
+                }
+                self.task.pcb.lock().dumpable = arg2 == 1;
+            }
+            PrctlOption::PR_GET_DUMPABLE => return Ok(self.task.pcb.lock().dumpable as usize),
+            // it can't be cleared once it is set.
+            PrctlOption::PR_SET_NO_NEW_PRIVS => {
+                if arg2 != 1 {
+                    return Err(Errno::EINVAL);
+                }
+                self.task.pcb.lock().no_new_privs = true;
+            }
+            PrctlOption::PR_GET_NO_NEW_PRIVS => {
+                return Ok(self.task.pcb.lock().no_new_privs as usize)
+            }
+            PrctlOption::PR_SET_CHILD_SUBREAPER => {
+                self.task.pcb.lock().child_subreaper = arg2 != 0;
+            }
+            PrctlOption::PR_GET_CHILD_SUBREAPER => {
+                let subreaper = self.task.pcb.lock().child_subreaper;
+                *UserRef::<i32>::from(arg2).get_mut() = subreaper as i32;
+            }
+        }
+        Ok(0)
+    }
+
     #[cfg(target_arch = "x86_64")]
     pub async fn sys_arch_prctl(&self, code: usize, addr: usize) -> SysResult {
         use super::types::sys::ArchPrctlCode;
@@ -1,34 +1,45 @@
-use super::{types::sys::Rusage, SysResult};
+use super::{
+    types::{
+        signal::{SigInfo, SignalFrame, SignalStack, SignalStackFlags, SI_TKILL, SI_USER},
+        sys::{Rusage, RLIMIT_NPROC, RUSAGE_CHILDREN, RUSAGE_SELF, RUSAGE_THREAD},
+    },
+    SysResult,
+};
 use crate::{
-    syscall::{
-        time::WaitUntilsec,
-        types::{
-            fd::{FutexFlags, AT_CWD},
-            task::CloneFlags,
-            time::TimeVal,
-        },
+    syscall::types::{
+        fd::AT_CWD,
+        ptrace::{PtraceOptions, PTRACE_EVENT_EXIT, PTRACE_EVENT_VFORK_DONE},
+        task::{CloneFlags, WaitOptions, P_ALL, P_PGID, P_PID},
+    },
+    tasks::{
+        cred::Access, exec::exec_with_process, interruptible, processes, tid2user_task, wait_child,
+        PidFd, UserTask, WaitEvent, WaitPid, WaitTarget, WaitVfork,
     },
-    tasks::{exec::exec_with_process, futex_requeue, futex_wake, UserTask, WaitFutex, WaitPid},
     user::{entry::user_entry, UserTaskContainer},
-    utils::{time::current_nsec, useref::UserRef},
+    utils::useref::UserRef,
 };
 use alloc::{
     string::{String, ToString},
     sync::Arc,
-    sync::Weak,
     vec::Vec,
 };
-use core::cmp;
-use executor::{select, thread, tid2task, yield_now, AsyncTask};
-use fs::TimeSpec;
-use log::{debug, warn};
-use num_traits::FromPrimitive;
-use polyhal::Time;
+use core::{cmp, mem::replace};
+use executor::{thread, yield_now, AsyncTask};
+use fs::file::File;
+use log::debug;
+#[cfg(target_arch = "x86_64")]
+use log::warn;
 use polyhal_trap::trapframe::TrapFrameArgs;
 use signal::SignalFlags;
 use syscalls::Errno;
 use vfscore::OpenFlags;
 
+/// The rusage of the waited child, it contains the children reaped by it.
+fn child_rusage(child: &UserTask) -> Rusage {
+    let tms = child.pcb.lock().tms;
+    Rusage::new(tms.utime + tms.cutime, tms.stime + tms.cstime)
+}
+
 impl UserTaskContainer {
     pub async fn sys_chdir(&self, path_ptr: UserRef<i8>) -> SysResult {
         let path = path_ptr.get_cstr().map_err(|_| Errno::EINVAL)?;
@@ -36,18 +47,44 @@ impl UserTaskContainer {
         let new_dir = self.task.fd_open(AT_CWD, path, OpenFlags::O_RDONLY)?;
         match new_dir.file_type()? {
             fs::FileType::Directory => {
-                self.task.pcb.lock().curr_dir = Arc::new(new_dir);
+                let cred = self.task.pcb.lock().cred.clone();
+                cred.check_file(&new_dir, Access::EXEC)?;
+                self.task.fs().lock().curr_dir = Arc::new(new_dir);
+                Ok(0)
+            }
+            _ => Err(Errno::ENOTDIR),
+        }
+    }
+
+    /// Change the root directory, the paths are resolved in it.
+    pub async fn sys_chroot(&self, path_ptr: UserRef<i8>) -> SysResult {
+        let path = path_ptr.get_cstr().map_err(|_| Errno::EINVAL)?;
+        debug!("sys_chroot @ path: {}", path);
+        if !self.task.pcb.lock().cred.privileged() {
+            return Err(Errno::EPERM);
+        }
+        let new_root = self.task.fd_open(AT_CWD, path, OpenFlags::O_RDONLY)?;
+        match new_root.file_type()? {
+            fs::FileType::Directory => {
+                self.task.fs().lock().root = new_root.path_buf();
                 Ok(0)
             }
             _ => Err(Errno::ENOTDIR),
         }
     }
 
+    pub async fn sys_umask(&self, mask: u32) -> SysResult {
+        debug!("sys_umask @ mask: {:#o}", mask);
+        let old = replace(&mut self.task.fs().lock().umask, mask & 0o777);
+        Ok(old as _)
+    }
+
     pub async fn sys_getcwd(&self, buf_ptr: UserRef<u8>, size: usize) -> SysResult {
         debug!("sys_getcwd @ buffer_ptr{} size: {}", buf_ptr, size);
         let buffer = buf_ptr.slice_mut_with_len(size);
-        let curr_path = self.task.pcb.lock().curr_dir.clone();
-        let path = curr_path.path();
+        // the path in the root directory.
+        let fs = self.task.fs().lock().clone();
+        let path = fs.curr_dir.path_buf().trim_start(&fs.root).path();
         let bytes = path.as_bytes();
         let len = cmp::min(bytes.len(), size);
         buffer[..len].copy_from_slice(&bytes[..len]);
@@ -56,7 +93,16 @@ impl UserTaskContainer {
     }
 
     pub async fn sys_exit(&self, exit_code: isize) -> SysResult {
-        debug!("sys_exit @ exit_code: {}  task_id: {}", exit_code, self.tid);
+        debug!(
+            "sys_exit @ exit_code: {}  task_id: {}  comm: {}",
+            exit_code,
+            self.tid,
+            self.task.comm()
+        );
+        let status = (exit_code as usize & 0xff) << 8;
+        self.task
+            .ptrace_event(PtraceOptions::TRACEEXIT, PTRACE_EVENT_EXIT, status)
+            .await;
         // current_task().as_user_task().unwrap().exit(exit_code as _);
         self.task.thread_exit(exit_code as _);
         Ok(0)
@@ -99,7 +145,7 @@ impl UserTaskContainer {
             self.task.exit(0);
             return Ok(0);
         }
-        let curr_dir = self.task.pcb.lock().curr_dir.clone();
+        let curr_dir = self.task.fs().lock().curr_dir.clone();
         exec_with_process(
             self.task.clone(),
             curr_dir.path_buf(),
@@ -109,6 +155,7 @@ impl UserTaskContainer {
         )
         .await?;
         self.task.before_run();
+        self.task.ptrace_exec().await;
         Ok(0)
     }
 
@@ -131,11 +178,30 @@ impl UserTaskContainer {
             self.tid, flags, stack, ptid, tls, ctid
         );
 
+        // the threads share the signal handlers, the handlers need the memory.
+        if (flags.contains(CloneFlags::CLONE_THREAD) && !flags.contains(CloneFlags::CLONE_SIGHAND))
+            || (flags.contains(CloneFlags::CLONE_SIGHAND) && !flags.contains(CloneFlags::CLONE_VM))
+        {
+            return Err(Errno::EINVAL);
+        }
+
+        // RLIMIT_NPROC counts the processes of the real user.
+        if !flags.contains(CloneFlags::CLONE_THREAD) {
+            let cred = self.task.pcb.lock().cred.clone();
+            let count = processes()
+                .iter()
+                .filter(|x| x.pcb.lock().cred.uid == cred.uid)
+                .count();
+            if count >= self.task.rlimit(RLIMIT_NPROC) && !cred.privileged() {
+                return Err(Errno::EAGAIN);
+            }
+        }
+
         let new_task = match flags.contains(CloneFlags::CLONE_THREAD) {
             true => self.task.clone().thread_clone(),
             // false => curr_task.clone().fork(user_entry()),
             // use cow(Copy On Write) to save memory.
-            false => self.task.clone().cow_fork(),
+            false => self.task.clone().cow_fork(&flags),
         };
 
         let clear_child_tid = flags
@@ -161,8 +227,24 @@ impl UserTaskContainer {
         }
         new_tcb.exit_signal = sig as u8;
         drop(new_tcb);
+        let traced = self.task.ptrace_clone(&new_task, &flags);
         yield_now().await;
         thread::spawn(new_task.clone(), user_entry());
+        if let Some((event, option)) = traced {
+            self.task
+                .ptrace_event(option, event, new_task.task_id)
+                .await;
+        }
+        if flags.contains(CloneFlags::CLONE_VFORK) {
+            WaitVfork(new_task.clone()).await;
+            self.task
+                .ptrace_event(
+                    PtraceOptions::TRACEVFORKDONE,
+                    PTRACE_EVENT_VFORK_DONE,
+                    new_task.task_id,
+                )
+                .await;
+        }
         Ok(new_task.task_id)
     }
 
@@ -172,90 +254,131 @@ impl UserTaskContainer {
         self.sys_clone(0x11, 0, 0.into(), 0, 0.into()).await
     }
 
+    #[cfg(target_arch = "x86_64")]
+    pub async fn sys_vfork(&self) -> SysResult {
+        let flags = CloneFlags::CLONE_VM | CloneFlags::CLONE_VFORK;
+        self.sys_clone(flags.bits() | 0x11, 0, 0.into(), 0, 0.into())
+            .await
+    }
+
     pub async fn sys_wait4(
         &self,
         pid: isize,           // 指定进程ID，可为-1等待任何子进程；
         status: UserRef<i32>, // 接收状态的指针；
         options: usize,       // WNOHANG，WUNTRACED，WCONTINUED；
+        rusage: UserRef<Rusage>,
     ) -> SysResult {
         debug!(
-            "[task {}] sys_wait4 @ pid: {}, status: {}, options: {}",
-            self.tid, pid, status, options
+            "[task {}] sys_wait4 @ pid: {}, status: {}, options: {:#x}, rusage: {}",
+            self.tid, pid, status, options, rusage
         );
-
-        // return LinuxError::ECHILD if there has no child process.
-        if self.task.inner_map(|inner| inner.children.len()) == 0 {
-            return Err(Errno::ECHILD);
+        let options = WaitOptions::from_bits(options).ok_or(Errno::EINVAL)?;
+        if options.intersects(WaitOptions::WEXITED | WaitOptions::WNOWAIT) {
+            return Err(Errno::EINVAL);
         }
+        let target = match pid {
+            -1 => WaitTarget::Any,
+            0 => WaitTarget::Pgid(self.task.pcb.lock().pgid),
+            _ if pid < 0 => WaitTarget::Pgid(-pid as _),
+            _ => WaitTarget::Pid(pid as _),
+        };
+        let Some((child, event)) = self
+            .wait_event(target, options | WaitOptions::WEXITED)
+            .await?
+        else {
+            return Ok(0);
+        };
+        let wstatus = match event {
+            WaitEvent::Exited(wstatus) => wstatus,
+            WaitEvent::Stopped(signal) => ((signal as i32) << 8) | 0x7f,
+            WaitEvent::Continued => 0xffff,
+            WaitEvent::Traced(wstatus) => wstatus,
+        };
+        if status.is_valid() {
+            *status.get_mut() = wstatus;
+        }
+        if rusage.is_valid() {
+            *rusage.get_mut() = child_rusage(&child);
+        }
+        Ok(child.task_id)
+    }
 
-        if pid != -1 {
-            self.task
-                .inner_map(|inner| {
-                    inner
-                        .children
-                        .iter()
-                        .find(|x| x.task_id == pid as usize)
-                        .map(|x| x.clone())
-                })
-                .ok_or(Errno::ECHILD)?;
+    pub async fn sys_waitid(
+        &self,
+        idtype: usize,
+        id: usize,
+        infop: UserRef<SigInfo>,
+        options: usize,
+        rusage: UserRef<Rusage>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_waitid @ idtype: {}, id: {}, infop: {}, options: {:#x}, rusage: {}",
+            self.tid, idtype, id, infop, options, rusage
+        );
+        let options = WaitOptions::from_bits(options).ok_or(Errno::EINVAL)?;
+        if !options
+            .intersects(WaitOptions::WEXITED | WaitOptions::WSTOPPED | WaitOptions::WCONTINUED)
+        {
+            return Err(Errno::EINVAL);
         }
-        if options == 0 || options == 2 || options == 3 || options == 10 {
-            debug!(
-                "children:{:?}",
-                self.task.pcb.lock().children.iter().count()
-            );
-            let child_task = WaitPid(self.task.clone(), pid).await?;
-
-            debug!(
-                "wait ok: {}  waiter: {}",
-                child_task.task_id, self.task.task_id
-            );
-            // release the task resources
-            self.task
-                .pcb
-                .lock()
-                .children
-                .retain(|x| x.task_id != child_task.task_id);
-            child_task.release();
-            debug!("wait pid: {}", child_task.exit_code().unwrap());
-
-            if status.is_valid() {
-                *status.get_mut() = (child_task.exit_code().unwrap() as i32) << 8;
+        let target = match idtype {
+            P_ALL => WaitTarget::Any,
+            P_PID if id as i32 > 0 => WaitTarget::Pid(id),
+            // pgid 0 is the process group of the caller.
+            P_PGID if id == 0 => WaitTarget::Pgid(self.task.pcb.lock().pgid),
+            P_PGID if id as i32 > 0 => WaitTarget::Pgid(id),
+            _ => return Err(Errno::EINVAL),
+        };
+        let waited = self.wait_event(target, options).await?;
+        // si_pid is 0 if there are no children changed with WNOHANG.
+        let info = match &waited {
+            Some((child, event)) => child.child_info(event),
+            None => SigInfo::new(0, 0),
+        };
+        if infop.is_valid() {
+            *infop.get_mut() = info;
+        }
+        if let (Some((child, _)), true) = (&waited, rusage.is_valid()) {
+            *rusage.get_mut() = child_rusage(child);
+        }
+        Ok(0)
+    }
+
+    /// Wait the state change of the children, the exited child is reaped
+    /// unless WNOWAIT is set. None if there are no changes with WNOHANG.
+    async fn wait_event(
+        &self,
+        target: WaitTarget,
+        options: WaitOptions,
+    ) -> Result<Option<(Arc<UserTask>, WaitEvent)>, Errno> {
+        let waited = match options.contains(WaitOptions::WNOHANG) {
+            true => wait_child(&self.task, target, options)?,
+            false => {
+                let wait = WaitPid(self.task.clone(), target, options);
+                Some(interruptible(self.task.clone(), wait).await?)
             }
-            Ok(child_task.task_id)
-        } else if options == 1 {
-            let child_task = self
-                .task
-                .pcb
-                .lock()
-                .children
-                .iter()
-                .find(|x| x.task_id == pid as usize || pid == -1)
-                .cloned();
-            let exit = child_task.clone().map_or(None, |x| x.exit_code());
-            match exit {
-                Some(t1) => {
-                    let child_task = child_task.unwrap();
-                    // Release task.
-                    self.task
-                        .pcb
-                        .lock()
-                        .children
-                        .retain(|x| x.task_id != child_task.task_id);
-                    child_task.release();
-                    if status.is_valid() {
-                        *status.get_mut() = (t1 as i32) << 8;
-                    }
-                    // TIPS: This is a small change.
-                    Ok(child_task.task_id)
-                    // Ok(0)
-                }
-                None => Ok(0),
+        };
+        if let Some((child, WaitEvent::Exited(_))) = &waited {
+            debug!("wait ok: {}  waiter: {}", child.task_id, self.task.task_id);
+            let mut pcb = self.task.pcb.lock();
+            let is_child = pcb.children.iter().any(|x| Arc::ptr_eq(x, child));
+            if !options.contains(WaitOptions::WNOWAIT) {
+                pcb.tracees.retain(|x| x.as_ptr() != Arc::as_ptr(child));
+            }
+            drop(pcb);
+            // the tracee which isn't the child is reaped by its parent.
+            if !options.contains(WaitOptions::WNOWAIT) && is_child {
+                // release the task resources, the times are added to the parent.
+                let child_tms = child.pcb.lock().tms;
+                let mut pcb = self.task.pcb.lock();
+                pcb.children.retain(|x| x.task_id != child.task_id);
+                pcb.tms.cutime += child_tms.utime + child_tms.cutime;
+                pcb.tms.cstime += child_tms.stime + child_tms.cstime;
+                drop(pcb);
+                child.release();
             }
-        } else {
-            warn!("wait4 unsupported options: {}", options);
-            Err(Errno::EPERM)
         }
+        Ok(waited)
     }
 
     pub async fn sys_sched_yield(&self) -> SysResult {
@@ -309,76 +432,16 @@ impl UserTaskContainer {
         Ok(self.tid)
     }
 
-    pub async fn sys_futex(
-        &self,
-        uaddr_ptr: UserRef<i32>,
-        op: usize,
-        value: usize,
-        value2: usize,
-        uaddr2: usize,
-        value3: usize,
-    ) -> SysResult {
-        let op = if op >= 0x80 { op - 0x80 } else { op };
-        debug!(
-            "[task {}] sys_futex @ uaddr: {} op: {} value: {:#x}, value2: {:#x}, uaddr2: {:#x} , value3: {:#x}",
-            self.tid, uaddr_ptr, op, value, value2, uaddr2, value3
-        );
-        let uaddr = uaddr_ptr.get_mut();
-        let flags = FromPrimitive::from_usize(op).ok_or(Errno::EINVAL)?;
-        debug!(
-            "sys_futex @ uaddr: {:#x} flags: {:?} value: {}",
-            uaddr, flags, value
-        );
+    /// The siginfo of the signal sent by the current process.
+    pub fn kill_info(&self, signum: usize, code: i32) -> SigInfo {
+        let uid = self.task.pcb.lock().cred.uid;
+        SigInfo::kill(signum, code, self.task.process_id, uid)
+    }
 
-        match flags {
-            FutexFlags::Wait => {
-                if *uaddr == value as _ {
-                    let futex_table = self.task.pcb.lock().futex_table.clone();
-                    let mut table = futex_table.lock();
-                    match table.get_mut(&uaddr_ptr.addr()) {
-                        Some(t) => t.push(self.tid),
-                        None => {
-                            table.insert(uaddr_ptr.addr(), vec![self.tid]);
-                        }
-                    }
-                    drop(table);
-                    let wait_func = WaitFutex(futex_table.clone(), self.tid);
-                    if value2 != 0 {
-                        let timeout = UserRef::<TimeSpec>::from(value2).get_mut();
-                        match select(wait_func, WaitUntilsec(current_nsec() + timeout.to_nsec()))
-                            .await
-                        {
-                            executor::Either::Left((res, _)) => res,
-                            executor::Either::Right(_) => Err(Errno::ETIMEDOUT),
-                        }
-                    } else {
-                        wait_func.await
-                    }
-                    // wait_func.await
-                } else {
-                    Err(Errno::EAGAIN)
-                }
-            }
-            FutexFlags::Wake => {
-                let futex_table = self.task.pcb.lock().futex_table.clone();
-                let count = futex_wake(futex_table, uaddr_ptr.addr(), value);
-                yield_now().await;
-                Ok(count)
-            }
-            FutexFlags::Requeue => {
-                let futex_table = self.task.pcb.lock().futex_table.clone();
-                Ok(futex_requeue(
-                    futex_table,
-                    uaddr_ptr.addr(),
-                    value,
-                    uaddr2,
-                    value2,
-                ))
-            }
-            _ => {
-                return Err(Errno::EPERM);
-            }
-        }
+    /// The current process can send signals to the task.
+    pub fn may_signal(&self, task: &UserTask) -> bool {
+        let cred = self.task.pcb.lock().cred.clone();
+        cred.may_signal(&task.pcb.lock().cred)
     }
 
     pub async fn sys_tkill(&self, tid: usize, signum: usize) -> SysResult {
@@ -399,16 +462,8 @@ impl UserTaskContainer {
 
         match child {
             Some(child) => {
-                let target_signal = SignalFlags::from_num(signum);
                 let child_task = child.upgrade().unwrap();
-                let mut child_tcb = child_task.tcb.write();
-                if !child_tcb.signal.has_sig(target_signal.clone()) {
-                    child_tcb.signal.add_signal(target_signal);
-                } else {
-                    if let Some(index) = target_signal.real_time_index() {
-                        child_tcb.signal_queue[index] += 1;
-                    }
-                }
+                child_task.send_siginfo(self.kill_info(signum, SI_TKILL));
                 // let signal = child
                 //     .upgrade().unwrap()
                 //     .tcb
@@ -421,32 +476,56 @@ impl UserTaskContainer {
         }
     }
 
+    /// Restore the context, the mask and the alternate stack from the
+    /// [SignalFrame] at the stack pointer, the handler has returned to
+    /// the trampoline, so the stack pointer is the same as the entry.
     pub async fn sys_sigreturn(&self) -> SysResult {
-        debug!("sys_sigreturn @ ");
-        Ok(0)
+        let mut tcb = self.task.tcb.write();
+        let sp = tcb.cx[TrapFrameArgs::SP];
+        debug!("[task {}] sys_sigreturn @ frame: {:#x}", self.tid, sp);
+        let frame = UserRef::<SignalFrame>::from(sp).get_ref();
+        let cx = &frame.ucontext;
+        cx.restore_ctx(&mut tcb.cx);
+        tcb.cx[TrapFrameArgs::SEPC] = cx.pc();
+        // SIGKILL and SIGSTOP can't be blocked.
+        tcb.sigmask = cx.sig_mask;
+        tcb.sigmask.mask &= !((SignalFlags::SIGKILL | SignalFlags::SIGSTOP).bits() as usize);
+        // the alternate stack disabled by SS_AUTODISARM is enabled again.
+        if !tcb.sigaltstack.contains(tcb.cx[TrapFrameArgs::SP]) {
+            let mut stack = cx.stack;
+            stack.flags -= SignalStackFlags::ONSTACK;
+            tcb.sigaltstack = match stack.flags.contains(SignalStackFlags::DISABLE) {
+                true => SignalStack::disabled(),
+                false => stack,
+            };
+        }
+        // the return value is the restored register.
+        Ok(tcb.cx[TrapFrameArgs::RET])
     }
 
-    pub async fn sys_getrusage(&self, who: usize, usage_ptr: UserRef<Rusage>) -> SysResult {
+    pub async fn sys_getrusage(&self, who: isize, usage_ptr: UserRef<Rusage>) -> SysResult {
         debug!("sys_getrusgae @ who: {}, usage_ptr: {}", who, usage_ptr);
-        // let Rusage
-        let rusage = usage_ptr.get_mut();
-
         let tms = self.task.inner_map(|inner| inner.tms);
-        let stime = Time::new(tms.stime as _);
-        let utime = Time::new(tms.utime as _);
-        rusage.ru_stime = TimeVal {
-            sec: stime.to_usec() / 1000_000,
-            usec: stime.to_usec() % 1000_000,
-        };
-        rusage.ru_utime = TimeVal {
-            sec: utime.to_usec() / 1000_000,
-            usec: utime.to_usec() % 1000_000,
+        let thread_tms = self.task.tcb.read().tms;
+        *usage_ptr.get_mut() = match who {
+            RUSAGE_SELF => Rusage::new(tms.utime, tms.stime),
+            RUSAGE_THREAD => Rusage::new(thread_tms.utime, thread_tms.stime),
+            RUSAGE_CHILDREN => Rusage::new(tms.cutime, tms.cstime),
+            _ => return Err(Errno::EINVAL),
         };
         Ok(0)
     }
 
-    pub fn sys_exit_group(&self, exit_code: usize) -> SysResult {
-        debug!("sys_exit_group @ exit_code: {}", exit_code);
+    pub async fn sys_exit_group(&self, exit_code: usize) -> SysResult {
+        debug!(
+            "sys_exit_group @ exit_code: {}  comm: {}",
+            exit_code,
+            self.task.comm()
+        );
+        let status = (exit_code & 0xff) << 8;
+        self.task
+            .ptrace_event(PtraceOptions::TRACEEXIT, PTRACE_EVENT_EXIT, status)
+            .await;
         // let children = user_task.pcb.lock().children.clone();
         // for ctask in children.iter().filter(|x| x.task_id != user_task.task_id) {
         //     ctask.exit(exit_code);
@@ -456,34 +535,100 @@ impl UserTaskContainer {
         // Err(LinuxError::EPERM)
     }
 
-    pub async fn sys_kill(&self, pid: usize, signum: usize) -> SysResult {
-        let signal = SignalFlags::from_num(signum);
+    /// Open the close-on-exec pidfd of the process, PIDFD_NONBLOCK is O_NONBLOCK.
+    pub async fn sys_pidfd_open(&self, pid: usize, flags: usize) -> SysResult {
         debug!(
-            "[task {}] sys_kill @ pid: {}, signum: {:?}",
-            self.tid, pid, signal
+            "[task {}] sys_pidfd_open @ pid: {}, flags: {:#x}",
+            self.tid, pid, flags
         );
+        let flags = OpenFlags::from_bits(flags as _).ok_or(Errno::EINVAL)?;
+        if pid == 0 || !OpenFlags::O_NONBLOCK.contains(flags.clone()) {
+            return Err(Errno::EINVAL);
+        }
+        let task = tid2user_task(pid).ok_or(Errno::ESRCH)?;
+        // the thread which isn't the leader isn't a process.
+        if task.process_id != pid {
+            return Err(Errno::EINVAL);
+        }
+        let fd = self.task.alloc_fd().ok_or(Errno::EMFILE)?;
+        let file = File::new_dev(Arc::new(PidFd::new(Arc::downgrade(&task))));
+        *file.flags.lock() |= flags | OpenFlags::O_CLOEXEC;
+        self.task.set_fd(fd, file);
+        Ok(fd)
+    }
 
-        let user_task = match tid2task(pid) {
-            Some(task) => task.downcast_arc::<UserTask>().map_err(|_| Errno::ESRCH),
-            None => Err(Errno::ESRCH),
-        }?;
+    /// pid > 0 is the process, 0 is the current process group,
+    /// -1 is all the processes except init and < -1 is the process group -pid.
+    /// signum 0 only checks the processes.
+    pub async fn sys_kill(&self, pid: isize, signum: usize) -> SysResult {
+        debug!(
+            "[task {}] sys_kill @ pid: {}, signum: {}",
+            self.tid, pid, signum
+        );
+        if signum > 64 {
+            return Err(Errno::EINVAL);
+        }
 
-        user_task.tcb.write().signal.add_signal(signal.clone());
+        let targets: Vec<Arc<UserTask>> = match pid {
+            0 => {
+                let pgid = self.task.pcb.lock().pgid;
+                processes()
+                    .into_iter()
+                    .filter(|x| x.pcb.lock().pgid == pgid)
+                    .collect()
+            }
+            // all the processes except init and the caller.
+            -1 => processes()
+                .into_iter()
+                .filter(|x| x.process_id != 1 && x.process_id != self.task.process_id)
+                .collect(),
+            _ if pid < 0 => processes()
+                .into_iter()
+                .filter(|x| x.pcb.lock().pgid == -pid as usize)
+                .collect(),
+            _ => tid2user_task(pid as usize).into_iter().collect(),
+        };
+        if targets.is_empty() {
+            return Err(Errno::ESRCH);
+        }
+        // it succeeds if one of the targets gets the signal.
+        let permitted: Vec<_> = targets.iter().filter(|x| self.may_signal(x)).collect();
+        if permitted.is_empty() {
+            return Err(Errno::EPERM);
+        }
+
+        if signum != 0 {
+            let info = self.kill_info(signum, SI_USER);
+            let sent = permitted
+                .iter()
+                .filter(|x| x.try_send_process_siginfo(info).is_ok())
+                .count();
+            if sent == 0 {
+                return Err(Errno::EAGAIN);
+            }
+        }
 
         yield_now().await;
 
         Ok(0)
     }
 
+    /// Create a new session and a new process group, the current process
+    /// is the leader of both. The process group leader can't do it.
     pub async fn sys_setsid(&self) -> SysResult {
         debug!("[task {}] sys_setsid", self.tid);
-        let parent = self.task.parent.read().clone();
-
-        if let Some(parent) = parent.upgrade() {
-            parent.pcb.lock().children.retain(|x| x.task_id != self.tid);
-            *self.task.parent.write() = Weak::<UserTask>::new();
+        // the caller is a group leader or its pid is used by a process group.
+        let pid = self.task.process_id;
+        if processes()
+            .iter()
+            .any(|x| x.inner_map(|inner| inner.pgid == pid))
+        {
+            return Err(Errno::EPERM);
         }
-        Ok(0)
+        let mut pcb = self.task.pcb.lock();
+        pcb.pgid = self.task.process_id;
+        pcb.sid = self.task.process_id;
+        Ok(self.task.process_id)
     }
 
     pub async fn sys_sched_getaffinity(
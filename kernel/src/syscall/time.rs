@@ -1,25 +1,37 @@
 use super::{
-    types::time::{ITimerVal, TimeVal, TMS},
+    types::{
+        signal::{SigEvent, SIGEV_NONE, SIGEV_SIGNAL, SIGEV_THREAD, SIGEV_THREAD_ID},
+        time::{
+            ITimerSpec, ITimerVal, PosixTimer, TimeVal, CLOCK_BOOTTIME, CLOCK_MONOTONIC,
+            CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME, CLOCK_THREAD_CPUTIME_ID, ITIMER_PROF,
+            ITIMER_REAL, ITIMER_VIRTUAL, TFD_TIMER_ABSTIME, TFD_TIMER_CANCEL_ON_SET, TIMER_ABSTIME,
+            TMS,
+        },
+    },
     SysResult,
 };
 use crate::{
-    tasks::WaitHandleAbleSignal,
+    tasks::{tid2user_task, TimerFd, WaitHandleAbleSignal},
     user::UserTaskContainer,
     utils::{
-        time::{current_nsec, current_timeval},
+        time::{
+            clock_nsec, clock_resolution, cputime_nsec, current_nsec, current_timeval, set_realtime,
+        },
         useref::UserRef,
     },
 };
+use alloc::sync::Arc;
 use core::{
+    cmp::min,
     future::Future,
-    ops::Add,
     pin::Pin,
     task::{Context, Poll},
 };
 use executor::select;
-use fs::TimeSpec;
-use log::{debug, warn};
+use fs::{file::File, OpenFlags, TimeSpec};
+use log::debug;
 use polyhal::time::Time;
+use signal::SignalFlags;
 use syscalls::Errno;
 
 impl UserTaskContainer {
@@ -36,6 +48,30 @@ impl UserTaskContainer {
         Ok(0)
     }
 
+    /// Set CLOCK_REALTIME, the timezone is obsolete and ignored.
+    pub async fn sys_settimeofday(
+        &self,
+        tv_ptr: UserRef<TimeVal>,
+        timezone_ptr: usize,
+    ) -> SysResult {
+        debug!(
+            "sys_settimeofday @ tv_ptr: {}, timezone: {:#x}",
+            tv_ptr, timezone_ptr
+        );
+        if !tv_ptr.is_valid() {
+            return Ok(0);
+        }
+        let tv = *tv_ptr.get_ref();
+        if tv.usec >= 1_000_000 {
+            return Err(Errno::EINVAL);
+        }
+        if !self.task.pcb.lock().cred.privileged() {
+            return Err(Errno::EPERM);
+        }
+        set_realtime(tv.to_nsec())?;
+        Ok(0)
+    }
+
     pub async fn sys_nanosleep(
         &self,
         req_ptr: UserRef<TimeSpec>,
@@ -80,24 +116,30 @@ impl UserTaskContainer {
             self.tid, clock_id, times_ptr
         );
 
-        let ns = match clock_id {
-            0 => current_nsec(),        // CLOCK_REALTIME
-            1 => Time::now().to_nsec(), // CLOCK_MONOTONIC
-            2 => {
-                warn!("CLOCK_PROCESS_CPUTIME_ID not implemented");
-                0
-            }
-            3 => {
-                warn!("CLOCK_THREAD_CPUTIME_ID not implemented");
-                0
-            }
-            _ => return Err(Errno::EINVAL),
-        };
+        let ns = self.task.clock_nsec(clock_id).ok_or(Errno::EINVAL)?;
 
-        *times_ptr.get_mut() = TimeSpec {
-            sec: ns / 1_000_000_000,
-            nsec: ns % 1_000_000_000,
-        };
+        *times_ptr.get_mut() = nsec_to_timespec(ns);
+        Ok(0)
+    }
+
+    /// Only CLOCK_REALTIME can be set, the other clocks count from the boot.
+    pub async fn sys_clock_settime(
+        &self,
+        clock_id: usize,
+        times_ptr: UserRef<TimeSpec>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_clock_settime @ clock_id: {}, times_ptr: {}",
+            self.tid, clock_id, times_ptr
+        );
+        let times = times_ptr.get_ref();
+        if clock_id != CLOCK_REALTIME || times.nsec >= 1_000_000_000 {
+            return Err(Errno::EINVAL);
+        }
+        if !self.task.pcb.lock().cred.privileged() {
+            return Err(Errno::EPERM);
+        }
+        set_realtime(times.to_nsec())?;
         Ok(0)
     }
 
@@ -108,11 +150,38 @@ impl UserTaskContainer {
         times_ptr: UserRef<TimeSpec>,
     ) -> SysResult {
         debug!("clock_getres @ {} {:#x?}", clock_id, times_ptr);
+        clock_nsec(clock_id, &TMS::default()).ok_or(Errno::EINVAL)?;
         if times_ptr.is_valid() {
-            *times_ptr.get_mut() = TimeSpec { sec: 0, nsec: 1 };
+            *times_ptr.get_mut() = nsec_to_timespec(clock_resolution());
         }
         Ok(0)
     }
+
+    /// The current time of the clock of ITIMER_REAL, ITIMER_VIRTUAL or ITIMER_PROF.
+    fn itimer_clock(&self, which: usize) -> Result<usize, Errno> {
+        let tms = self.task.pcb.lock().tms;
+        match which {
+            ITIMER_REAL => Ok(current_nsec()),
+            ITIMER_VIRTUAL => Ok(cputime_nsec(tms.utime)),
+            ITIMER_PROF => Ok(cputime_nsec(tms.utime + tms.stime)),
+            _ => Err(Errno::EINVAL),
+        }
+    }
+
+    pub async fn sys_getitimer(&self, which: usize, curr_ptr: UserRef<ITimerVal>) -> SysResult {
+        debug!(
+            "[task {}] sys_getitimer @ which: {} curr_ptr: {}",
+            self.tid, which, curr_ptr
+        );
+        let now = self.itimer_clock(which)?;
+        let timer = self.task.pcb.lock().timer[which];
+        *curr_ptr.get_mut() = ITimerVal {
+            interval: TimeVal::from_nsec(timer.interval),
+            value: TimeVal::from_nsec(timer.remain(now)),
+        };
+        Ok(0)
+    }
+
     pub async fn sys_setitimer(
         &self,
         which: usize,
@@ -123,26 +192,231 @@ impl UserTaskContainer {
             "[task {}] sys_setitimer @ which: {} times_ptr: {} old_timer_ptr: {}",
             self.tid, which, times_ptr, old_timer_ptr
         );
+        let now = self.itimer_clock(which)?;
+        let mut pcb = self.task.pcb.lock();
+        let timer = &mut pcb.timer[which];
+        if old_timer_ptr.is_valid() {
+            *old_timer_ptr.get_mut() = ITimerVal {
+                interval: TimeVal::from_nsec(timer.interval),
+                value: TimeVal::from_nsec(timer.remain(now)),
+            };
+        }
+        if times_ptr.is_valid() {
+            let new_timer = times_ptr.get_ref();
+            let deadline = match new_timer.value.to_nsec() {
+                0 => 0,
+                value => now + value,
+            };
+            timer.arm(deadline, new_timer.interval.to_nsec());
+        }
+        Ok(0)
+    }
 
-        if which == 0 {
-            let mut pcb = self.task.pcb.lock();
-            if old_timer_ptr.is_valid() {
-                *old_timer_ptr.get_mut() = pcb.timer[0].timer;
-            }
-
-            if times_ptr.is_valid() {
-                let new_timer = times_ptr.get_ref();
-                pcb.timer[0].timer = *new_timer;
-                pcb.timer[0].next = current_timeval().add(pcb.timer[0].timer.value);
-                if new_timer.value.sec == 0 && new_timer.value.usec == 0 {
-                    pcb.timer[0].next = Default::default();
-                    pcb.timer[0].last = Default::default();
+    pub async fn sys_timer_create(
+        &self,
+        clock_id: usize,
+        sevp: UserRef<SigEvent>,
+        timerid_ptr: UserRef<i32>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_timer_create @ clock_id: {}, sevp: {}, timerid_ptr: {}",
+            self.tid, clock_id, sevp, timerid_ptr
+        );
+        let mut pcb = self.task.pcb.lock();
+        clock_nsec(clock_id, &pcb.tms).ok_or(Errno::EINVAL)?;
+        let timerid = (0..).find(|x| !pcb.posix_timers.contains_key(x)).unwrap();
+        // the default is SIGEV_SIGNAL with SIGALRM, sigev_value is the timer id.
+        let event = match sevp.is_valid() {
+            true => *sevp.get_ref(),
+            false => SigEvent::new(SignalFlags::SIGALRM.num(), SIGEV_SIGNAL, timerid),
+        };
+        match event.notify {
+            SIGEV_NONE => {}
+            SIGEV_SIGNAL | SIGEV_THREAD | SIGEV_THREAD_ID => {
+                if !(1..=64).contains(&event.signo) {
+                    return Err(Errno::EINVAL);
                 }
             }
-            Ok(0)
-        } else {
-            Err(Errno::EPERM)
+            _ => return Err(Errno::EINVAL),
+        }
+        // the thread must be in the same process.
+        if event.notify == SIGEV_THREAD_ID {
+            tid2user_task(event.tid as _)
+                .filter(|x| Arc::ptr_eq(&x.pcb, &self.task.pcb))
+                .ok_or(Errno::EINVAL)?;
         }
+        pcb.posix_timers.insert(
+            timerid,
+            PosixTimer {
+                clock: clock_id,
+                event,
+                timer: Default::default(),
+                overrun: 0,
+            },
+        );
+        *timerid_ptr.get_mut() = timerid as _;
+        Ok(0)
+    }
+
+    pub async fn sys_timer_settime(
+        &self,
+        timerid: usize,
+        flags: usize,
+        new_ptr: UserRef<ITimerSpec>,
+        old_ptr: UserRef<ITimerSpec>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_timer_settime @ timerid: {}, flags: {:#x}, new_ptr: {}, old_ptr: {}",
+            self.tid, timerid, flags, new_ptr, old_ptr
+        );
+        let new = *new_ptr.get_ref();
+        if new.value.nsec >= 1_000_000_000 || new.interval.nsec >= 1_000_000_000 {
+            return Err(Errno::EINVAL);
+        }
+        let mut pcb = self.task.pcb.lock();
+        let tms = pcb.tms;
+        let posix = pcb.posix_timers.get_mut(&timerid).ok_or(Errno::EINVAL)?;
+        let now = clock_nsec(posix.clock, &tms).ok_or(Errno::EINVAL)?;
+        if old_ptr.is_valid() {
+            *old_ptr.get_mut() = ITimerSpec {
+                interval: nsec_to_timespec(posix.timer.interval),
+                value: nsec_to_timespec(posix.timer.remain(now)),
+            };
+        }
+        let deadline = match new.value.to_nsec() {
+            0 => 0,
+            // the expired absolute time expires at the next check.
+            value if flags & TIMER_ABSTIME != 0 => value,
+            value => now + value,
+        };
+        posix.timer.arm(deadline, new.interval.to_nsec());
+        posix.overrun = 0;
+        Ok(0)
+    }
+
+    pub async fn sys_timer_gettime(
+        &self,
+        timerid: usize,
+        curr_ptr: UserRef<ITimerSpec>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_timer_gettime @ timerid: {}, curr_ptr: {}",
+            self.tid, timerid, curr_ptr
+        );
+        let pcb = self.task.pcb.lock();
+        let posix = pcb.posix_timers.get(&timerid).ok_or(Errno::EINVAL)?;
+        let now = clock_nsec(posix.clock, &pcb.tms).ok_or(Errno::EINVAL)?;
+        *curr_ptr.get_mut() = ITimerSpec {
+            interval: nsec_to_timespec(posix.timer.interval),
+            value: nsec_to_timespec(posix.timer.remain(now)),
+        };
+        Ok(0)
+    }
+
+    pub async fn sys_timer_getoverrun(&self, timerid: usize) -> SysResult {
+        debug!(
+            "[task {}] sys_timer_getoverrun @ timerid: {}",
+            self.tid, timerid
+        );
+        let pcb = self.task.pcb.lock();
+        let posix = pcb.posix_timers.get(&timerid).ok_or(Errno::EINVAL)?;
+        Ok(min(posix.overrun, i32::MAX as usize))
+    }
+
+    pub async fn sys_timer_delete(&self, timerid: usize) -> SysResult {
+        debug!(
+            "[task {}] sys_timer_delete @ timerid: {}",
+            self.tid, timerid
+        );
+        self.task
+            .pcb
+            .lock()
+            .posix_timers
+            .remove(&timerid)
+            .ok_or(Errno::EINVAL)?;
+        Ok(0)
+    }
+
+    pub async fn sys_timerfd_create(&self, clock_id: usize, flags: usize) -> SysResult {
+        debug!(
+            "[task {}] sys_timerfd_create @ clock_id: {}, flags: {:#x}",
+            self.tid, clock_id, flags
+        );
+        if !matches!(clock_id, CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_BOOTTIME) {
+            return Err(Errno::EINVAL);
+        }
+        // TFD_NONBLOCK and TFD_CLOEXEC are the same as the open flags.
+        let flags = OpenFlags::from_bits(flags as _).ok_or(Errno::EINVAL)?;
+        if !(OpenFlags::O_NONBLOCK | OpenFlags::O_CLOEXEC).contains(flags.clone()) {
+            return Err(Errno::EINVAL);
+        }
+        let fd = self.task.alloc_fd().ok_or(Errno::EMFILE)?;
+        let file = File::new_dev(Arc::new(TimerFd::new(clock_id)));
+        *file.flags.lock() |= flags;
+        self.task.set_fd(fd, file);
+        Ok(fd)
+    }
+
+    fn get_timerfd(&self, fd: usize) -> Result<Arc<TimerFd>, Errno> {
+        self.task
+            .get_fd(fd)
+            .ok_or(Errno::EBADF)?
+            .inner
+            .clone()
+            .downcast_arc::<TimerFd>()
+            .map_err(|_| Errno::EINVAL)
+    }
+
+    pub async fn sys_timerfd_settime(
+        &self,
+        fd: usize,
+        flags: usize,
+        new_ptr: UserRef<ITimerSpec>,
+        old_ptr: UserRef<ITimerSpec>,
+    ) -> SysResult {
+        debug!(
+            "[task {}] sys_timerfd_settime @ fd: {}, flags: {:#x}, new_ptr: {}, old_ptr: {}",
+            self.tid, fd, flags, new_ptr, old_ptr
+        );
+        if flags & !(TFD_TIMER_ABSTIME | TFD_TIMER_CANCEL_ON_SET) != 0 {
+            return Err(Errno::EINVAL);
+        }
+        let timerfd = self.get_timerfd(fd)?;
+        let new = *new_ptr.get_ref();
+        if new.value.nsec >= 1_000_000_000 || new.interval.nsec >= 1_000_000_000 {
+            return Err(Errno::EINVAL);
+        }
+        let now = timerfd.now();
+        let mut inner = timerfd.inner.lock();
+        if old_ptr.is_valid() {
+            *old_ptr.get_mut() = ITimerSpec {
+                interval: nsec_to_timespec(inner.timer.interval),
+                value: nsec_to_timespec(inner.timer.remain(now)),
+            };
+        }
+        let deadline = match new.value.to_nsec() {
+            0 => 0,
+            value if flags & TFD_TIMER_ABSTIME != 0 => value,
+            value => now + value,
+        };
+        inner.timer.arm(deadline, new.interval.to_nsec());
+        // the expirations of the old setting are discarded.
+        inner.ticks = 0;
+        Ok(0)
+    }
+
+    pub async fn sys_timerfd_gettime(&self, fd: usize, curr_ptr: UserRef<ITimerSpec>) -> SysResult {
+        debug!(
+            "[task {}] sys_timerfd_gettime @ fd: {}, curr_ptr: {}",
+            self.tid, fd, curr_ptr
+        );
+        let timerfd = self.get_timerfd(fd)?;
+        let timer = timerfd.inner.lock().timer;
+        *curr_ptr.get_mut() = ITimerSpec {
+            interval: nsec_to_timespec(timer.interval),
+            value: nsec_to_timespec(timer.remain(timerfd.now())),
+        };
+        Ok(0)
     }
 
     pub async fn sys_clock_nanosleep(
@@ -157,20 +431,36 @@ impl UserTaskContainer {
             self.tid, clock_id, flags, req_ptr, rem_ptr
         );
 
-        if flags == 1 {
-            let req = req_ptr.get_mut();
-            WaitUntilsec(req.sec * 1_000_000_000 + req.nsec).await;
-            if rem_ptr.is_valid() {
-                *rem_ptr.get_mut() = Default::default();
+        // the CPU time doesn't pass while sleeping.
+        match clock_id {
+            CLOCK_THREAD_CPUTIME_ID => return Err(Errno::EINVAL),
+            CLOCK_PROCESS_CPUTIME_ID => return Err(Errno::EOPNOTSUPP),
+            _ => {}
+        }
+        let now = self.task.clock_nsec(clock_id).ok_or(Errno::EINVAL)?;
+        let req = req_ptr.get_ref().to_nsec();
+        // the absolute time of the clock is converted to the time since the boot.
+        let deadline = match flags & TIMER_ABSTIME != 0 {
+            true => current_nsec() + req.saturating_sub(now),
+            false => current_nsec() + req,
+        };
+        debug!("nano sleep until {} nseconds", deadline);
+        match select(
+            WaitHandleAbleSignal(self.task.clone()),
+            WaitUntilsec(deadline),
+        )
+        .await
+        {
+            executor::Either::Right(_) => Ok(0),
+            executor::Either::Left(_) => {
+                // the relative sleep reports the time it didn't sleep.
+                if flags & TIMER_ABSTIME == 0 && rem_ptr.is_valid() {
+                    let remain = deadline.saturating_sub(current_nsec());
+                    *rem_ptr.get_mut() = nsec_to_timespec(remain);
+                }
+                Err(Errno::EINTR)
             }
-        } else {
-            let ns = current_nsec();
-            let req = req_ptr.get_mut();
-            debug!("nano sleep {} nseconds", req.sec * 1_000_000_000 + req.nsec);
-            WaitUntilsec(ns + req.sec * 1_000_000_000 + req.nsec).await;
         }
-
-        Ok(0)
     }
 }
 
@@ -189,6 +479,13 @@ impl Future for WaitUntilsec {
     }
 }
 
+pub fn nsec_to_timespec(ns: usize) -> TimeSpec {
+    TimeSpec {
+        sec: ns / 1_000_000_000,
+        nsec: ns % 1_000_000_000,
+    }
+}
+
 #[allow(dead_code)]
 pub fn wait_ms(ms: usize) -> WaitUntilsec {
     WaitUntilsec(current_nsec() + ms * 0x1000_0000)
@@ -38,6 +38,31 @@ pub enum FutexFlags {
     UnlockPi = 7,
     TrylockPi = 8,
     WaitBitset = 9,
+    WakeBitset = 10,
+}
+
+/// The flags in the futex op, the rest is the command.
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+pub const FUTEX_CLOCK_REALTIME: usize = 256;
+pub const FUTEX_BITSET_MATCH_ANY: u32 = u32::MAX;
+
+/// The bits of the futex word of the PI and robust futexes.
+pub const FUTEX_WAITERS: u32 = 0x8000_0000;
+pub const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+pub const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+
+/// The max count of the entries walked in the robust list.
+pub const ROBUST_LIST_LIMIT: usize = 2048;
+
+/// The head of the robust list registered by set_robust_list. The list
+/// links the `next` of the locks, the futex word is at `futex_offset`
+/// from the entry, the low bit of the entry marks the PI futex.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RobustListHead {
+    pub list: usize,
+    pub futex_offset: isize,
+    pub list_op_pending: usize,
 }
 
 #[repr(C)]
@@ -46,3 +71,6 @@ pub struct IoVec {
     pub base: usize,
     pub len: usize,
 }
+
+/// The read of the eventfd decreases the counter by 1.
+pub const EFD_SEMAPHORE: usize = 1;
@@ -2,6 +2,7 @@ pub mod elf;
 pub mod fd;
 pub mod mm;
 pub mod poll;
+pub mod ptrace;
 pub mod signal;
 pub mod sys;
 pub mod task;
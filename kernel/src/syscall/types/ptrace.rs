@@ -0,0 +1,53 @@
+use num_derive::FromPrimitive;
+
+#[derive(Debug, Clone, Copy, PartialEq, FromPrimitive)]
+#[repr(usize)]
+pub enum PtraceRequest {
+    TraceMe = 0,
+    PeekText = 1,
+    PeekData = 2,
+    PeekUser = 3,
+    PokeText = 4,
+    PokeData = 5,
+    PokeUser = 6,
+    Cont = 7,
+    Kill = 8,
+    SingleStep = 9,
+    GetRegs = 12,
+    SetRegs = 13,
+    Attach = 16,
+    Detach = 17,
+    Syscall = 24,
+    SetOptions = 0x4200,
+    GetEventMsg = 0x4201,
+    GetSigInfo = 0x4202,
+    GetRegSet = 0x4204,
+    SetRegSet = 0x4205,
+    Seize = 0x4206,
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    pub struct PtraceOptions: usize {
+        const TRACESYSGOOD  = 0x00000001;
+        const TRACEFORK     = 0x00000002;
+        const TRACEVFORK    = 0x00000004;
+        const TRACECLONE    = 0x00000008;
+        const TRACEEXEC     = 0x00000010;
+        const TRACEVFORKDONE = 0x00000020;
+        const TRACEEXIT     = 0x00000040;
+        const EXITKILL      = 0x00100000;
+    }
+}
+
+/// The events of the event stops, the wait status is
+/// `(SIGTRAP | event << 8) << 8 | 0x7f`.
+pub const PTRACE_EVENT_FORK: usize = 1;
+pub const PTRACE_EVENT_VFORK: usize = 2;
+pub const PTRACE_EVENT_CLONE: usize = 3;
+pub const PTRACE_EVENT_EXEC: usize = 4;
+pub const PTRACE_EVENT_VFORK_DONE: usize = 5;
+pub const PTRACE_EVENT_EXIT: usize = 6;
+
+/// The regset of the general registers in PTRACE_GETREGSET.
+pub const NT_PRSTATUS: usize = 1;
@@ -1,8 +1,8 @@
 use polyhal_trap::trapframe::TrapFrame;
-use signal::SigProcMask;
+use signal::{SigProcMask, SignalFlags};
 
 bitflags! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct SignalStackFlags : u32 {
         const ONSTACK = 1;
         const DISABLE = 2;
@@ -10,14 +10,33 @@ bitflags! {
     }
 }
 
+/// The minimum size of the alternate signal stack.
+pub const MINSIGSTKSZ: usize = 2048;
+
 #[repr(C)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct SignalStack {
     pub sp: usize,
     pub flags: SignalStackFlags,
     pub size: usize,
 }
 
+impl SignalStack {
+    /// The alternate signal stack is disabled by default.
+    pub const fn disabled() -> Self {
+        Self {
+            sp: 0,
+            flags: SignalStackFlags::DISABLE,
+            size: 0,
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, sp: usize) -> bool {
+        !self.flags.contains(SignalStackFlags::DISABLE) && sp > self.sp && sp <= self.sp + self.size
+    }
+}
+
 cfg_if! {
     if #[cfg(target_arch = "x86_64")] {
         #[repr(C)]
@@ -140,10 +159,12 @@ cfg_if! {
 
             pub fn store_ctx(&mut self, ctx: &TrapFrame) {
                 self.regs = ctx.regs;
+                self.sp = ctx.sp;
             }
 
             pub fn restore_ctx(&self, ctx: &mut TrapFrame) {
                 ctx.regs = self.regs;
+                ctx.sp = self.sp;
             }
         }
     } else if #[cfg(target_arch = "loongarch64")] {
@@ -183,3 +204,196 @@ cfg_if! {
         }
     }
 }
+
+/// No SIGCHLD is sent when the children stop or continue.
+pub const SA_NOCLDSTOP: usize = 1;
+/// The handler takes the siginfo and the ucontext, sa_sigaction.
+pub const SA_SIGINFO: usize = 4;
+/// The handler runs on the alternate signal stack.
+pub const SA_ONSTACK: usize = 0x08000000;
+/// The syscall interrupted by the signal is restarted after the handler.
+pub const SA_RESTART: usize = 0x10000000;
+/// The signal isn't blocked while its handler runs.
+pub const SA_NODEFER: usize = 0x40000000;
+
+/// The si_code of the signals sent by the user or the kernel.
+pub const SI_USER: i32 = 0;
+pub const SI_KERNEL: i32 = 0x80;
+pub const SI_TIMER: i32 = -2;
+pub const SI_TKILL: i32 = -6;
+
+/// The si_code of SIGSEGV, SIGILL and SIGTRAP.
+pub const SEGV_MAPERR: i32 = 1;
+pub const SEGV_ACCERR: i32 = 2;
+pub const ILL_ILLOPC: i32 = 1;
+pub const TRAP_BRKPT: i32 = 1;
+pub const TRAP_TRACE: i32 = 2;
+
+/// The frame pushed to the user stack when the handler is called.
+#[repr(C)]
+pub struct SignalFrame {
+    pub info: SigInfo,
+    pub ucontext: SignalUserContext,
+}
+
+/// The si_code of SIGCHLD.
+pub const CLD_EXITED: i32 = 1;
+pub const CLD_KILLED: i32 = 2;
+pub const CLD_DUMPED: i32 = 3;
+pub const CLD_TRAPPED: i32 = 4;
+pub const CLD_STOPPED: i32 = 5;
+pub const CLD_CONTINUED: i32 = 6;
+
+/// The siginfo_t of linux, it is 128 bytes.
+/// The fields are the union, its layout depends on the signal and si_code.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    _pad: i32,
+    pub fields: [u32; 28],
+}
+
+impl SigInfo {
+    pub const fn new(signo: i32, code: i32) -> Self {
+        Self {
+            signo,
+            errno: 0,
+            code,
+            _pad: 0,
+            fields: [0; 28],
+        }
+    }
+
+    /// The siginfo of the signal sent by kill, tkill and sigqueue.
+    pub fn kill(signo: usize, code: i32, pid: usize, uid: u32) -> Self {
+        let mut info = Self::new(signo as _, code);
+        info.fields[0] = pid as _;
+        info.fields[1] = uid;
+        info
+    }
+
+    /// The siginfo of the fault, si_addr is the address of the fault.
+    pub fn fault(signo: usize, code: i32, addr: usize) -> Self {
+        let mut info = Self::new(signo as _, code);
+        info.fields[0] = addr as _;
+        info.fields[1] = (addr >> 32) as _;
+        info
+    }
+
+    /// The siginfo of the POSIX timer expiration, si_value is the sigev_value.
+    pub fn timer(signo: usize, timerid: usize, overrun: usize, value: usize) -> Self {
+        let mut info = Self::new(signo as _, SI_TIMER);
+        info.fields[0] = timerid as _;
+        info.fields[1] = overrun as _;
+        info.fields[2] = value as _;
+        info.fields[3] = (value >> 32) as _;
+        info
+    }
+
+    /// The siginfo of SIGCHLD, the times are clock ticks.
+    pub fn child(code: i32, pid: usize, uid: u32, status: i32, utime: u64, stime: u64) -> Self {
+        let mut info = Self::new(SignalFlags::SIGCHLD.num() as i32, code);
+        info.fields[0] = pid as _;
+        info.fields[1] = uid;
+        info.fields[2] = status as _;
+        // clock_t si_utime and si_stime are aligned to 8 bytes.
+        info.fields[4] = utime as _;
+        info.fields[5] = (utime >> 32) as _;
+        info.fields[6] = stime as _;
+        info.fields[7] = (stime >> 32) as _;
+        info
+    }
+}
+
+/// The sigevent of timer_create, it is 64 bytes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigEvent {
+    pub value: usize,
+    pub signo: i32,
+    pub notify: i32,
+    /// sigev_notify_thread_id of SIGEV_THREAD_ID.
+    pub tid: i32,
+    _pad: [i32; 11],
+}
+
+impl SigEvent {
+    pub const fn new(signo: usize, notify: i32, value: usize) -> Self {
+        Self {
+            value,
+            signo: signo as _,
+            notify,
+            tid: 0,
+            _pad: [0; 11],
+        }
+    }
+}
+
+/// The sigev_notify of SigEvent.
+pub const SIGEV_SIGNAL: i32 = 0;
+pub const SIGEV_NONE: i32 = 1;
+pub const SIGEV_THREAD: i32 = 2;
+pub const SIGEV_THREAD_ID: i32 = 4;
+
+/// The signalfd_siginfo read from the signalfd, it is 128 bytes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SignalfdSiginfo {
+    pub signo: u32,
+    pub errno: i32,
+    pub code: i32,
+    pub pid: u32,
+    pub uid: u32,
+    pub fd: i32,
+    pub tid: u32,
+    pub band: u32,
+    pub overrun: u32,
+    pub trapno: u32,
+    pub status: i32,
+    pub int: i32,
+    pub ptr: u64,
+    pub utime: u64,
+    pub stime: u64,
+    pub addr: u64,
+    pub addr_lsb: u16,
+    _pad: [u16; 23],
+}
+
+impl From<SigInfo> for SignalfdSiginfo {
+    fn from(info: SigInfo) -> Self {
+        let field64 = |i: usize| info.fields[i] as u64 | (info.fields[i + 1] as u64) << 32;
+        let mut ssi = Self {
+            signo: info.signo as _,
+            errno: info.errno,
+            code: info.code,
+            ..Default::default()
+        };
+        match info.signo {
+            // SIGILL, SIGTRAP, SIGBUS, SIGFPE and SIGSEGV from the faults.
+            4 | 5 | 7 | 8 | 11 if info.code > 0 => ssi.addr = field64(0),
+            17 => {
+                ssi.pid = info.fields[0];
+                ssi.uid = info.fields[1];
+                ssi.status = info.fields[2] as _;
+                ssi.utime = field64(4);
+                ssi.stime = field64(6);
+            }
+            _ if info.code == SI_TIMER => {
+                ssi.tid = info.fields[0];
+                ssi.overrun = info.fields[1];
+                ssi.int = info.fields[2] as _;
+                ssi.ptr = field64(2);
+            }
+            _ => {
+                ssi.pid = info.fields[0];
+                ssi.uid = info.fields[1];
+                ssi.int = info.fields[2] as _;
+                ssi.ptr = field64(2);
+            }
+        }
+        ssi
+    }
+}
@@ -1,5 +1,7 @@
 use num_derive::FromPrimitive;
 
+use polyhal::Time;
+
 use super::time::TimeVal;
 
 pub struct UTSname {
@@ -21,14 +23,62 @@ pub enum ArchPrctlCode {
     ARCH_GET_GS = 0x1004,
 }
 
+#[repr(usize)]
+#[derive(Debug, Clone, FromPrimitive)]
+#[allow(non_camel_case_types)]
+pub enum PrctlOption {
+    PR_SET_PDEATHSIG = 1,
+    PR_GET_PDEATHSIG = 2,
+    PR_GET_DUMPABLE = 3,
+    PR_SET_DUMPABLE = 4,
+    PR_SET_NAME = 15,
+    PR_GET_NAME = 16,
+    PR_SET_CHILD_SUBREAPER = 36,
+    PR_GET_CHILD_SUBREAPER = 37,
+    PR_SET_NO_NEW_PRIVS = 38,
+    PR_GET_NO_NEW_PRIVS = 39,
+}
+
+/// The length of the task name with the tailing zero.
+pub const TASK_COMM_LEN: usize = 16;
+
+/// The resources of getrlimit and setrlimit.
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_FSIZE: usize = 1;
+pub const RLIMIT_DATA: usize = 2;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_MEMLOCK: usize = 8;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIMIT_SIGPENDING: usize = 11;
+pub const RLIM_NLIMITS: usize = 16;
+pub const RLIM_INFINITY: usize = usize::MAX;
+/// The max of the hard limit of RLIMIT_NOFILE.
+pub const NR_OPEN: usize = 4096;
+
+/// The soft limit is `curr`, it can be raised up to the hard limit `max`.
 #[repr(C)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct Rlimit {
     pub curr: usize,
     pub max: usize,
 }
 
+impl Rlimit {
+    pub const fn new(curr: usize, max: usize) -> Self {
+        Self { curr, max }
+    }
+}
+
+/// The who of getrusage.
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+pub const RUSAGE_THREAD: isize = 1;
+
 #[repr(C)]
+#[derive(Default)]
 pub struct Rusage {
     pub ru_utime: TimeVal,
     pub ru_stime: TimeVal,
@@ -47,3 +97,21 @@ pub struct Rusage {
     pub ru_nvcsw: i64,
     pub ru_nivcsw: i64,
 }
+
+impl Rusage {
+    /// The rusage with the user and system times, they are the raw ticks.
+    pub fn new(utime: u64, stime: u64) -> Self {
+        let timeval = |x: u64| {
+            let usec = Time::new(x as _).to_usec();
+            TimeVal {
+                sec: usec / 1_000_000,
+                usec: usec % 1_000_000,
+            }
+        };
+        Self {
+            ru_utime: timeval(utime),
+            ru_stime: timeval(stime),
+            ..Default::default()
+        }
+    }
+}
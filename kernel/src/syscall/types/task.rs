@@ -28,3 +28,23 @@ bitflags! {
         const CLONE_IO	            = 0x80000000;
     }
 }
+
+bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    pub struct WaitOptions: usize {
+        const WNOHANG       = 0x00000001;
+        const WUNTRACED     = 0x00000002;
+        const WSTOPPED      = 0x00000002;
+        const WEXITED       = 0x00000004;
+        const WCONTINUED    = 0x00000008;
+        const WNOWAIT       = 0x01000000;
+        const __WNOTHREAD   = 0x20000000;
+        const __WALL        = 0x40000000;
+        const __WCLONE      = 0x80000000;
+    }
+}
+
+/// The idtype of waitid.
+pub const P_ALL: usize = 0;
+pub const P_PID: usize = 1;
+pub const P_PGID: usize = 2;
@@ -1,5 +1,9 @@
 use core::{cmp::Ordering, ops::Add};
 
+use fs::TimeSpec;
+
+use super::signal::SigEvent;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct TimeVal {
@@ -21,6 +25,19 @@ impl Add for TimeVal {
     }
 }
 
+impl TimeVal {
+    pub fn from_nsec(ns: usize) -> Self {
+        Self {
+            sec: ns / 1_000_000_000,
+            usec: ns % 1_000_000_000 / 1000,
+        }
+    }
+
+    pub fn to_nsec(self) -> usize {
+        self.sec * 1_000_000_000 + self.usec * 1000
+    }
+}
+
 impl PartialOrd for TimeVal {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         if self.sec > other.sec {
@@ -60,9 +77,87 @@ pub struct TMS {
     pub cstime: u64,
 }
 
+/// The interval timer, the times are the nanoseconds of its clock,
+/// it is disarmed if next is 0.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct ProcessTimer {
-    pub timer: ITimerVal,
-    pub next: TimeVal,
-    pub last: TimeVal,
+    pub interval: usize,
+    pub next: usize,
+}
+
+impl ProcessTimer {
+    /// Arm the timer to expire at the deadline, 0 disarms it.
+    pub fn arm(&mut self, deadline: usize, interval: usize) {
+        self.next = deadline;
+        self.interval = match deadline {
+            0 => 0,
+            _ => interval,
+        };
+    }
+
+    /// The remaining time until the next expiration.
+    pub fn remain(&self, now: usize) -> usize {
+        match self.next {
+            0 => 0,
+            // the expired timer is reported as 1ns until it is checked.
+            next => next.saturating_sub(now).max(1),
+        }
+    }
+
+    /// The count of the expirations since the last check, the periodic
+    /// timer is advanced past now.
+    pub fn expire(&mut self, now: usize) -> usize {
+        if self.next == 0 || now < self.next {
+            return 0;
+        }
+        match self.interval {
+            0 => {
+                self.next = 0;
+                1
+            }
+            interval => {
+                let count = (now - self.next) / interval + 1;
+                self.next += count * interval;
+                count
+            }
+        }
+    }
+}
+
+/// The POSIX timer created by timer_create.
+#[derive(Debug, Clone, Copy)]
+pub struct PosixTimer {
+    pub clock: usize,
+    pub event: SigEvent,
+    pub timer: ProcessTimer,
+    /// The overrun count of the last delivered expiration.
+    pub overrun: usize,
 }
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ITimerSpec {
+    pub interval: TimeSpec,
+    pub value: TimeSpec,
+}
+
+pub const ITIMER_REAL: usize = 0;
+pub const ITIMER_VIRTUAL: usize = 1;
+pub const ITIMER_PROF: usize = 2;
+
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+pub const CLOCK_THREAD_CPUTIME_ID: usize = 3;
+pub const CLOCK_MONOTONIC_RAW: usize = 4;
+pub const CLOCK_REALTIME_COARSE: usize = 5;
+pub const CLOCK_MONOTONIC_COARSE: usize = 6;
+pub const CLOCK_BOOTTIME: usize = 7;
+
+/// The time of timer_settime and clock_nanosleep is absolute.
+pub const TIMER_ABSTIME: usize = 1;
+
+/// The flags of timerfd_settime, TFD_TIMER_CANCEL_ON_SET is accepted but
+/// the realtime clock changes don't cancel the timer.
+pub const TFD_TIMER_ABSTIME: usize = 1;
+pub const TFD_TIMER_CANCEL_ON_SET: usize = 2;
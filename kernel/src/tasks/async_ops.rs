@@ -1,14 +1,25 @@
 use core::{cmp, future::Future, pin::Pin, task::Poll};
 
-use alloc::{sync::Arc, vec::Vec};
-use executor::AsyncTask;
+use alloc::{
+    boxed::Box,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use executor::{select, AsyncTask, Either};
 use polyhal::time::Time;
+use signal::SignalFlags;
 use sync::Mutex;
 use syscalls::Errno;
 
 use super::{
     current_user_task,
-    task::{FutexTable, UserTask},
+    ptrace::Ptrace,
+    task::{FutexTable, JobReport, UserTask},
+};
+use crate::syscall::types::{
+    fd::FUTEX_BITSET_MATCH_ANY,
+    signal::{CLD_CONTINUED, CLD_DUMPED, CLD_EXITED, CLD_KILLED, CLD_STOPPED, CLD_TRAPPED},
+    task::WaitOptions,
 };
 
 pub struct NextTick(usize);
@@ -26,63 +37,269 @@ impl Future for NextTick {
     }
 }
 
-pub struct WaitPid(pub Arc<UserTask>, pub isize);
+/// The state change of the child reported by wait4.
+pub enum WaitEvent {
+    /// The wait status of the exited child.
+    Exited(i32),
+    Stopped(usize),
+    Continued,
+    /// The wait status of the stopped tracee.
+    Traced(i32),
+}
+
+impl WaitEvent {
+    /// The si_code and si_status of SIGCHLD and waitid.
+    pub fn code_status(&self) -> (i32, i32) {
+        match *self {
+            WaitEvent::Exited(wstatus) if wstatus & 0x7f == 0 => {
+                (CLD_EXITED, (wstatus >> 8) & 0xff)
+            }
+            WaitEvent::Exited(wstatus) if wstatus & 0x80 != 0 => (CLD_DUMPED, wstatus & 0x7f),
+            WaitEvent::Exited(wstatus) => (CLD_KILLED, wstatus & 0x7f),
+            WaitEvent::Stopped(signal) => (CLD_STOPPED, signal as i32),
+            WaitEvent::Continued => (CLD_CONTINUED, SignalFlags::SIGCONT.num() as i32),
+            WaitEvent::Traced(wstatus) => (CLD_TRAPPED, wstatus >> 8),
+        }
+    }
+}
+
+/// The children waited by wait4 and waitid.
+#[derive(Debug, Clone, Copy)]
+pub enum WaitTarget {
+    Any,
+    Pid(usize),
+    Pgid(usize),
+}
+
+/// Find the child which can be waited, the job control report is consumed
+/// unless WNOWAIT is set. ECHILD if there are no such children.
+///
+/// The clone children (exit signal isn't SIGCHLD) are waited only
+/// with __WCLONE, __WALL waits all the children.
+///
+/// The tracees are waited by the tracer like the children, their ptrace
+/// stops are reported without WUNTRACED.
+pub fn wait_child(
+    task: &UserTask,
+    target: WaitTarget,
+    options: WaitOptions,
+) -> Result<Option<(Arc<UserTask>, WaitEvent)>, Errno> {
+    let waited = |x: &Arc<UserTask>| {
+        let in_target = match target {
+            WaitTarget::Any => true,
+            WaitTarget::Pid(pid) => x.task_id == pid,
+            WaitTarget::Pgid(pgid) => x.pcb.lock().pgid == pgid,
+        };
+        let is_clone = x.tcb.read().exit_signal as usize != SignalFlags::SIGCHLD.num();
+        in_target
+            && (options.contains(WaitOptions::__WALL)
+                || is_clone == options.contains(WaitOptions::__WCLONE))
+    };
+    let pcb = task.pcb.lock();
+    let children: Vec<Arc<UserTask>> = pcb.children.iter().filter(|x| waited(x)).cloned().collect();
+    let tracees: Vec<Arc<UserTask>> = pcb
+        .tracees
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter(waited)
+        .collect();
+    drop(pcb);
+    if children.is_empty() && tracees.is_empty() {
+        return Err(Errno::ECHILD);
+    }
+
+    if options.contains(WaitOptions::WEXITED) {
+        if let Some(child) = children.iter().find(|x| x.exit_code().is_some()) {
+            return Ok(Some((
+                child.clone(),
+                WaitEvent::Exited(child.wait_status()),
+            )));
+        }
+        // the tracee which isn't the child is removed by the waiter.
+        let exited =
+            |x: &&Arc<UserTask>| x.exit_code().is_some() || x.tcb.read().thread_exit_code.is_some();
+        if let Some(tracee) = tracees.iter().find(exited) {
+            return Ok(Some((
+                tracee.clone(),
+                WaitEvent::Exited(tracee.wait_status()),
+            )));
+        }
+    }
+    let traced = tracees.into_iter().find_map(|tracee| {
+        let mut tcb = tracee.tcb.write();
+        let trace = tcb.ptrace.as_mut().filter(|x| !x.reported)?;
+        let status = trace.wait_status()?;
+        if !options.contains(WaitOptions::WNOWAIT) {
+            trace.reported = true;
+        }
+        drop(tcb);
+        Some((tracee, WaitEvent::Traced(status)))
+    });
+    if traced.is_some() {
+        return Ok(traced);
+    }
+    Ok(children.into_iter().find_map(|child| {
+        let mut pcb = child.pcb.lock();
+        let event = match pcb.job_report {
+            Some(JobReport::Stopped(signal)) if options.contains(WaitOptions::WSTOPPED) => {
+                WaitEvent::Stopped(signal)
+            }
+            Some(JobReport::Continued) if options.contains(WaitOptions::WCONTINUED) => {
+                WaitEvent::Continued
+            }
+            _ => return None,
+        };
+        if !options.contains(WaitOptions::WNOWAIT) {
+            pcb.job_report = None;
+        }
+        drop(pcb);
+        Some((child, event))
+    }))
+}
+
+pub struct WaitPid(pub Arc<UserTask>, pub WaitTarget, pub WaitOptions);
 
 impl Future for WaitPid {
-    type Output = Result<Arc<UserTask>, Errno>;
+    type Output = Result<(Arc<UserTask>, WaitEvent), Errno>;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        match wait_child(&self.0, self.1, self.2) {
+            Ok(Some(res)) => Poll::Ready(Ok(res)),
+            Ok(None) => Poll::Pending,
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+}
+
+/// Wait until the stopped process is continued or killed.
+pub struct WaitContinue(pub Arc<UserTask>);
+
+impl Future for WaitContinue {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        let killed = self.0.tcb.read().signal.has_sig(SignalFlags::SIGKILL);
+        match self.0.is_stopped() && !killed && self.0.exit_code().is_none() {
+            true => Poll::Pending,
+            false => Poll::Ready(()),
+        }
+    }
+}
+
+/// Wait until the process exits.
+pub struct WaitExit(pub Arc<UserTask>);
+
+impl Future for WaitExit {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        match self.0.exit_code().is_some() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+/// Wait until the other threads of the process are parked, stopped or
+/// exited, they don't return to the user while the core is dumped.
+pub struct WaitCoreParked(pub Arc<UserTask>);
+
+impl Future for WaitCoreParked {
+    type Output = ();
 
     fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
-        let inner = self.0.pcb.lock();
-        let res = inner
-            .children
+        let (threads, stopped) = {
+            let pcb = self.0.pcb.lock();
+            (pcb.threads.clone(), pcb.stop_signal.is_some())
+        };
+        let parked = threads
             .iter()
-            .find(|x| (self.1 == -1 || x.task_id == self.1 as usize) && x.exit_code().is_some())
-            .cloned();
-        drop(inner);
-        match res {
-            Some(task) => Poll::Ready(Ok(task.clone())),
-            None => Poll::Pending,
+            .filter_map(Weak::upgrade)
+            .filter(|x| x.task_id != self.0.task_id)
+            .all(|x| {
+                let tcb = x.tcb.read();
+                let traced = tcb.ptrace.as_ref().is_some_and(|x| x.stop.is_some());
+                stopped || traced || tcb.core_parked || tcb.thread_exit_code.is_some()
+            });
+        match parked {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+/// Wait until the vfork child execs or exits, the parent is suspended
+/// because the child is running in its memory.
+pub struct WaitVfork(pub Arc<UserTask>);
+
+impl Future for WaitVfork {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        let exited = self.0.exit_code().is_some() || self.0.tcb.read().thread_exit_code.is_some();
+        match exited || self.0.pcb.lock().vfork_done {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+/// Wait until the tracee is resumed by the tracer,
+/// or it is killed or the tracer is gone.
+pub struct WaitPtrace(pub Arc<UserTask>);
+
+impl Future for WaitPtrace {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        let tcb = self.0.tcb.read();
+        let tracer = match &tcb.ptrace {
+            Some(trace) if trace.stop.is_some() => trace.tracer.clone(),
+            _ => return Poll::Ready(()),
+        };
+        let killed = tcb.signal.has_sig(SignalFlags::SIGKILL);
+        drop(tcb);
+        match !killed && Ptrace::tracer_alive(&tracer) && self.0.exit_code().is_none() {
+            true => Poll::Pending,
+            false => Poll::Ready(()),
         }
     }
 }
 
-pub struct WaitSignal(pub Arc<UserTask>);
+/// Wait until one of the signals in the set is pending for the thread, or
+/// one of the signals in the mask is pending at the thread itself.
+pub struct WaitSignal(pub Arc<UserTask>, pub usize, pub usize);
 
 impl Future for WaitSignal {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
-        match self.0.tcb.read().signal.has_signal() {
+        let own = self.0.tcb.read().signal.signal & self.2 != 0;
+        match own || self.0.has_pending(self.1) {
             true => Poll::Ready(()),
             false => Poll::Pending,
         }
     }
 }
 
-pub fn in_futex(futex_table: Arc<Mutex<FutexTable>>, task_id: usize) -> bool {
+pub fn in_futex<K>(futex_table: Arc<Mutex<FutexTable<K>>>, task_id: usize) -> bool {
     let futex_table = futex_table.lock();
     futex_table
         .values()
-        .find(|x| x.contains(&task_id))
-        .is_some()
+        .any(|x| x.iter().any(|x| x.tid == task_id))
 }
 
-pub struct WaitFutex(pub Arc<Mutex<FutexTable>>, pub usize);
+pub struct WaitFutex<K = usize>(pub Arc<Mutex<FutexTable<K>>>, pub usize);
 
-impl Future for WaitFutex {
+impl<K> Future for WaitFutex<K> {
     type Output = Result<usize, Errno>;
 
     fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
-        let signal = current_user_task().tcb.read().signal.clone();
         match in_futex(self.0.clone(), self.1) {
             true => {
-                if signal.has_signal() {
-                    self.0
-                        .lock()
-                        .values_mut()
-                        .find(|x| x.contains(&self.1))
-                        .map(|x| x.retain(|x| *x != self.1));
-                    Poll::Ready(Err(Errno::EINTR))
+                if current_user_task().interrupt_signal().is_some() {
+                    futex_dequeue(&self.0, self.1);
+                    Poll::Ready(Err(Errno::ERESTARTSYS))
                 } else {
                     Poll::Pending
                 }
@@ -92,61 +309,92 @@ impl Future for WaitFutex {
     }
 }
 
+/// Remove the waiter, it is used when the waiting is interrupted or timed out.
+/// It returns false if the waiter has been woken.
+pub fn futex_dequeue<K>(futex_table: &Mutex<FutexTable<K>>, task_id: usize) -> bool {
+    let mut futex_table = futex_table.lock();
+    let queued = futex_table
+        .values()
+        .any(|x| x.iter().any(|x| x.tid == task_id));
+    futex_table
+        .values_mut()
+        .for_each(|x| x.retain(|x| x.tid != task_id));
+    queued
+}
+
 pub struct WaitHandleAbleSignal(pub Arc<UserTask>);
 
 impl Future for WaitHandleAbleSignal {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
-        let task = &self.0;
-        let sig_mask = task.tcb.read().sigmask;
-        let has_signal = task.tcb.read().signal.mask(sig_mask).has_signal();
-
-        match has_signal {
-            true => Poll::Ready(()),
-            false => Poll::Pending,
+        match self.0.interrupt_signal() {
+            Some(_) => Poll::Ready(()),
+            None => Poll::Pending,
         }
     }
 }
 
+/// Run the blocking wait until it is interrupted by the signal, the syscall
+/// is restarted after the signal if the handler has SA_RESTART, or EINTR.
+pub async fn interruptible<T>(
+    task: Arc<UserTask>,
+    future: impl Future<Output = Result<T, Errno>>,
+) -> Result<T, Errno> {
+    match select(Box::pin(future), WaitHandleAbleSignal(task)).await {
+        Either::Left((res, _)) => res,
+        Either::Right(_) => Err(Errno::ERESTARTSYS),
+    }
+}
+
 pub fn futex_wake(futex_table: Arc<Mutex<FutexTable>>, uaddr: usize, wake_count: usize) -> usize {
-    let mut futex_table = futex_table.lock();
-    let que_size = futex_table.get_mut(&uaddr).map(|x| x.len()).unwrap_or(0);
-    if que_size == 0 {
-        0
-    } else {
-        let que = futex_table
-            .get_mut(&uaddr)
-            .map(|x| x.drain(..cmp::min(wake_count as usize, que_size)));
+    futex_wake_bitset(
+        &mut futex_table.lock(),
+        uaddr,
+        wake_count,
+        FUTEX_BITSET_MATCH_ANY,
+    )
+}
 
-        que.map(|x| x.count()).unwrap_or(0)
-    }
+/// Wake the waiters whose bitset matches, the table is locked by the caller.
+pub fn futex_wake_bitset<K: Ord>(
+    futex_table: &mut FutexTable<K>,
+    key: K,
+    wake_count: usize,
+    bitset: u32,
+) -> usize {
+    let Some(que) = futex_table.get_mut(&key) else {
+        return 0;
+    };
+    let mut count = 0;
+    que.retain(|x| match count < wake_count && x.bitset & bitset != 0 {
+        true => {
+            count += 1;
+            false
+        }
+        false => true,
+    });
+    count
 }
 
-pub fn futex_requeue(
-    futex_table: Arc<Mutex<FutexTable>>,
-    uaddr: usize,
+/// Wake `wake_count` waiters of the key and move at most `reque_count` of
+/// the rest to key2, returns the count of the woken and the requeued.
+pub fn futex_requeue<K: Ord>(
+    futex_table: Arc<Mutex<FutexTable<K>>>,
+    key: K,
     wake_count: usize,
-    uaddr2: usize,
+    key2: K,
     reque_count: usize,
-) -> usize {
+) -> (usize, usize) {
     let mut futex_table = futex_table.lock();
-
-    let waked_size = futex_table
-        .get_mut(&uaddr)
-        .map(|x| x.drain(..wake_count as usize).count())
-        .unwrap_or(0);
-
-    let reque: Option<Vec<_>> = futex_table
-        .get_mut(&uaddr)
-        .map(|x| x.drain(..reque_count as usize).collect());
-
-    if let Some(reque) = reque {
-        if !futex_table.contains_key(&uaddr2) {
-            futex_table.insert(uaddr2, vec![]);
-        }
-        futex_table.get_mut(&uaddr2).unwrap().extend(reque);
+    let Some(que) = futex_table.get_mut(&key) else {
+        return (0, 0);
+    };
+    let waked_size = que.drain(..cmp::min(wake_count, que.len())).count();
+    let reque: Vec<_> = que.drain(..cmp::min(reque_count, que.len())).collect();
+    let reque_size = reque.len();
+    if reque_size > 0 {
+        futex_table.entry(key2).or_default().extend(reque);
     }
-
-    waked_size
+    (waked_size, reque_size)
 }
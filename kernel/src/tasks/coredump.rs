@@ -0,0 +1,377 @@
+//! ELF core dumps for the signals whose default action is core.
+//!
+//! The core file has a PT_NOTE segment and a PT_LOAD segment for every run
+//! of the mapped pages. The notes are a NT_PRSTATUS for every thread, the
+//! thread that got the signal is the first one, and the NT_AUXV.
+//!
+//! The file is written to the path of `/proc/sys/kernel/core_pattern` and
+//! it is not larger than RLIMIT_CORE, no core is dumped if it is 0.
+
+use super::{
+    async_ops::{WaitCoreParked, WaitExit},
+    elf::{write_at, ElfHeader, ProgramHeader, ELF_MACHINE, PT_LOAD},
+    procdir::CORE_PATTERN_INO,
+    ptrace::{elf_gregs, ELF_NGREG},
+    UserTask,
+};
+use crate::{syscall::types::sys::RLIMIT_CORE, utils::time::realtime_nsec};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{cmp::min, mem::size_of};
+use devices::PAGE_SIZE;
+use executor::{current_task, AsyncTask};
+use fs::{file::File, pathbuf::PathBuf, INodeInterface};
+use log::{info, warn};
+use runtime::frame::FrameTracker;
+use sync::Mutex;
+use syscalls::Errno;
+use vfscore::{OpenFlags, Stat, StatMode, VfsResult};
+
+/// The default core pattern.
+/// The root filesystem can't be written, so the core is put at `/tmp`.
+const DEFAULT_CORE_PATTERN: &str = "/tmp/core.%p";
+
+static CORE_PATTERN: Mutex<String> = Mutex::new(String::new());
+
+const ET_CORE: u16 = 4;
+const PT_NOTE: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_AUXV: u32 = 6;
+const NOTE_NAME: &[u8; 8] = b"CORE\0\0\0\0";
+
+/// `struct elf_prstatus` in the NT_PRSTATUS note.
+#[repr(C)]
+struct ElfPrstatus {
+    signo: i32,
+    code: i32,
+    errno: i32,
+    cursig: u16,
+    _pad: u16,
+    sigpend: u64,
+    sighold: u64,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    /// utime, stime, cutime and cstime.
+    times: [[u64; 2]; 4],
+    reg: [usize; ELF_NGREG],
+    fpvalid: i32,
+}
+
+/// Set the default core pattern and add it to procfs.
+pub fn init() {
+    *CORE_PATTERN.lock() = String::from(DEFAULT_CORE_PATTERN);
+    procfs::register_node("sys/kernel/core_pattern", Arc::new(CorePatternNode));
+}
+
+/// Expand the core pattern, `%p` is the pid, `%s` is the signal
+/// and `%t` is the time in seconds.
+fn core_path(task: &UserTask, signal: usize) -> Option<PathBuf> {
+    let pattern = CORE_PATTERN.lock().clone();
+    // Don't support piping the core to a program.
+    if pattern.is_empty() || pattern.starts_with('|') {
+        return None;
+    }
+    let mut path = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('p') => path += &format!("{}", task.process_id),
+            Some('s') => path += &format!("{}", signal),
+            Some('e') => path += &task.comm(),
+            Some('t') => path += &format!("{}", realtime_nsec() / 1_000_000_000),
+            Some('%') => path.push('%'),
+            _ => {}
+        }
+    }
+    match path.starts_with('/') {
+        true => Some(path.as_str().into()),
+        false => Some(task.fs().lock().curr_dir.path_buf().join(&path)),
+    }
+}
+
+fn push_note(notes: &mut Vec<u8>, ntype: u32, desc: &[u8]) {
+    notes.extend_from_slice(&5u32.to_ne_bytes());
+    notes.extend_from_slice(&(desc.len() as u32).to_ne_bytes());
+    notes.extend_from_slice(&ntype.to_ne_bytes());
+    notes.extend_from_slice(NOTE_NAME);
+    notes.extend_from_slice(desc);
+    notes.resize(notes.len().next_multiple_of(4), 0);
+}
+
+fn prstatus(task: &UserTask, signal: usize) -> ElfPrstatus {
+    let (pgrp, sid) = {
+        let pcb = task.pcb.lock();
+        (pcb.pgid as i32, pcb.sid as i32)
+    };
+    let tcb = task.tcb.read();
+    let ppid = task
+        .parent
+        .read()
+        .upgrade()
+        .map(|x| x.process_id as i32)
+        .unwrap_or(0);
+    ElfPrstatus {
+        signo: signal as _,
+        code: 0,
+        errno: 0,
+        cursig: signal as _,
+        _pad: 0,
+        sigpend: tcb.signal.signal as _,
+        sighold: tcb.sigmask.mask as _,
+        pid: task.task_id as _,
+        ppid,
+        pgrp,
+        sid,
+        times: [[0; 2]; 4],
+        reg: elf_gregs(&tcb.cx),
+        fpvalid: 0,
+    }
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+/// Write the core of the task to the core pattern path.
+///
+/// The task is the thread which got the signal, the process exits after it.
+/// The other threads are stopped before the dump, only one thread dumps the
+/// core, false is returned to the others after the process exits.
+pub async fn dump_core(task: &Arc<UserTask>, signal: usize) -> bool {
+    let limit = task.rlimit(RLIMIT_CORE);
+    if limit == 0 || !task.pcb.lock().dumpable {
+        return true;
+    }
+    let Some(path) = core_path(task, signal) else {
+        return true;
+    };
+    let mut pcb = task.pcb.lock();
+    if pcb.core_dumping {
+        drop(pcb);
+        task.tcb.write().core_parked = true;
+        WaitExit(task.clone()).await;
+        return false;
+    }
+    pcb.core_dumping = true;
+    drop(pcb);
+    WaitCoreParked(task.clone()).await;
+
+    match write_core(task, signal, path.clone(), limit) {
+        Ok(size) => {
+            info!(
+                "dump core of {} to {}, size: {:#x}",
+                task.get_task_id(),
+                path,
+                size
+            );
+            task.pcb.lock().core_dumped = true;
+        }
+        Err(err) => warn!("can't dump core to {}: {:?}", path, err),
+    }
+    true
+}
+
+fn write_core(
+    task: &Arc<UserTask>,
+    signal: usize,
+    path: PathBuf,
+    limit: usize,
+) -> Result<usize, Errno> {
+    let cred = task.pcb.lock().cred.clone();
+    let mm = task.mm();
+    let mm = mm.lock();
+    // The pages of the memset and the shared memory, sorted by the address.
+    let mut pages: Vec<(usize, Arc<FrameTracker>)> = mm
+        .memset
+        .iter()
+        .flat_map(|area| area.mtrackers.iter())
+        .filter(|x| x.vaddr.raw() != 0)
+        .map(|x| (x.vaddr.raw(), x.tracker.clone()))
+        .collect();
+    mm.shms.iter().for_each(|shm| {
+        shm.mem
+            .trackers
+            .iter()
+            .enumerate()
+            .for_each(|(i, tracker)| {
+                pages.push((shm.start + i * PAGE_SIZE, tracker.clone()));
+            })
+    });
+    pages.sort_by_key(|x| x.0);
+    pages.dedup_by_key(|x| x.0);
+
+    // (vaddr, the index of the first page, page count)
+    let mut segments: Vec<(usize, usize, usize)> = Vec::new();
+    for (i, (vaddr, _)) in pages.iter().enumerate() {
+        match segments.last_mut() {
+            Some(last) if last.0 + last.2 * PAGE_SIZE == *vaddr => last.2 += 1,
+            _ => segments.push((*vaddr, i, 1)),
+        }
+    }
+
+    drop(mm);
+
+    let mut notes = Vec::new();
+    let pcb = task.pcb.lock();
+    let threads: Vec<Arc<UserTask>> = pcb.threads.iter().filter_map(|x| x.upgrade()).collect();
+    let auxv: Vec<usize> = pcb
+        .auxv
+        .iter()
+        .flat_map(|(key, value)| [*key, *value])
+        .chain([0, 0])
+        .collect();
+    drop(pcb);
+
+    push_note(&mut notes, NT_PRSTATUS, as_bytes(&prstatus(task, signal)));
+    threads
+        .iter()
+        .filter(|x| x.task_id != task.task_id)
+        .for_each(|x| push_note(&mut notes, NT_PRSTATUS, as_bytes(&prstatus(x, signal))));
+    let auxv_bytes =
+        unsafe { core::slice::from_raw_parts(auxv.as_ptr() as *const u8, auxv.len() * 8) };
+    push_note(&mut notes, NT_AUXV, auxv_bytes);
+
+    let phnum = segments.len() + 1;
+    let notes_off = size_of::<ElfHeader>() + phnum * size_of::<ProgramHeader>();
+    let data_off = (notes_off + notes.len()).next_multiple_of(PAGE_SIZE);
+
+    let mut headers = vec![0u8; notes_off];
+    let mut ident = [0u8; 16];
+    // magic, 64 bit, little endian, version 1.
+    ident[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    write_at(
+        &mut headers,
+        0,
+        ElfHeader {
+            ident,
+            etype: ET_CORE,
+            machine: ELF_MACHINE,
+            version: 1,
+            entry: 0,
+            phoff: size_of::<ElfHeader>() as _,
+            shoff: 0,
+            flags: 0,
+            ehsize: size_of::<ElfHeader>() as _,
+            phentsize: size_of::<ProgramHeader>() as _,
+            phnum: phnum as _,
+            shentsize: 64,
+            shnum: 0,
+            shstrndx: 0,
+        },
+    );
+    write_at(
+        &mut headers,
+        size_of::<ElfHeader>(),
+        ProgramHeader {
+            ptype: PT_NOTE,
+            flags: 0,
+            offset: notes_off as _,
+            vaddr: 0,
+            paddr: 0,
+            filesz: notes.len() as _,
+            memsz: 0,
+            align: 4,
+        },
+    );
+    for (i, (vaddr, first, count)) in segments.iter().enumerate() {
+        let size = count * PAGE_SIZE;
+        write_at(
+            &mut headers,
+            size_of::<ElfHeader>() + (i + 1) * size_of::<ProgramHeader>(),
+            ProgramHeader {
+                ptype: PT_LOAD,
+                flags: 0b111, // R | W | X
+                offset: (data_off + first * PAGE_SIZE) as _,
+                vaddr: *vaddr as _,
+                paddr: 0,
+                filesz: size as _,
+                memsz: size as _,
+                align: PAGE_SIZE as _,
+            },
+        );
+    }
+
+    // Don't write through a planted symlink, the new core belongs to the dumper.
+    let file = match File::open(path.clone(), OpenFlags::O_RDWR) {
+        Ok(file) => {
+            let mut stat = Stat::default();
+            file.stat(&mut stat)?;
+            if stat.mode.bits() & StatMode::TYPE_MASK.bits() == StatMode::LINK.bits() {
+                return Err(Errno::ELOOP);
+            }
+            file
+        }
+        Err(_) => {
+            let file = File::open(path, OpenFlags::O_RDWR | OpenFlags::O_CREAT)?;
+            // the filesystems without owners (fat32, devfs) are ignored.
+            let _ = file.chown(cred.fsuid, cred.fsgid);
+            file
+        }
+    };
+    file.truncate(0)?;
+
+    // Write until the limit, the rest of the core is dropped like linux.
+    let write = |offset: usize, buffer: &[u8]| -> Result<(), Errno> {
+        if offset < limit {
+            file.writeat(offset, &buffer[..min(buffer.len(), limit - offset)])?;
+        }
+        Ok(())
+    };
+    write(0, &headers)?;
+    write(notes_off, &notes)?;
+    for (i, (_, tracker)) in pages.iter().enumerate() {
+        write(
+            data_off + i * PAGE_SIZE,
+            tracker.0.slice_with_len(PAGE_SIZE),
+        )?;
+    }
+    Ok(min(data_off + pages.len() * PAGE_SIZE, limit))
+}
+
+/// The node at `/proc/sys/kernel/core_pattern`.
+pub struct CorePatternNode;
+
+impl INodeInterface for CorePatternNode {
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let pattern = format!("{}\n", CORE_PATTERN.lock());
+        let pattern = pattern.as_bytes();
+        if offset >= pattern.len() {
+            return Ok(0);
+        }
+        let rlen = min(buffer.len(), pattern.len() - offset);
+        buffer[..rlen].copy_from_slice(&pattern[offset..offset + rlen]);
+        Ok(rlen)
+    }
+
+    fn writeat(&self, _offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        // the pattern decides where all the cores are written.
+        let task = current_task()
+            .downcast_arc::<UserTask>()
+            .map_err(|_| Errno::EPERM)?;
+        if !task.pcb.lock().cred.privileged() {
+            return Err(Errno::EPERM);
+        }
+        let pattern = core::str::from_utf8(buffer).map_err(|_| Errno::EINVAL)?;
+        *CORE_PATTERN.lock() = String::from(pattern.trim_end_matches('\n'));
+        Ok(buffer.len())
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        stat.dev = 0;
+        stat.ino = CORE_PATTERN_INO;
+        stat.mode = StatMode::FILE | StatMode::from_bits_truncate(0o644);
+        stat.nlink = 1;
+        stat.uid = 0;
+        stat.gid = 0;
+        stat.size = 0;
+        stat.blksize = 512;
+        stat.blocks = 0;
+        stat.rdev = 0;
+        Ok(())
+    }
+}
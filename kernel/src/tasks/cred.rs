@@ -0,0 +1,177 @@
+//! The credentials of the process and the permission checks of the files.
+//!
+//! The files are checked by the fsuid and fsgid, the filesystems without
+//! access modes (fat32, devfs) aren't checked.
+
+use alloc::vec::Vec;
+use bitflags::bitflags;
+use fs::{file::File, pathbuf::PathBuf};
+use syscalls::Errno;
+use vfscore::{OpenFlags, Stat, StatMode, VfsResult};
+
+/// The max count of the supplementary groups.
+pub const NGROUPS_MAX: usize = 65536;
+
+bitflags! {
+    /// The access of the file, it is the same as the mode of faccessat.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Access: u32 {
+        const READ = 4;
+        const WRITE = 2;
+        const EXEC = 1;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub uid: u32,
+    pub euid: u32,
+    pub suid: u32,
+    pub fsuid: u32,
+    pub gid: u32,
+    pub egid: u32,
+    pub sgid: u32,
+    pub fsgid: u32,
+    /// supplementary groups
+    pub groups: Vec<u32>,
+}
+
+impl Credentials {
+    pub const fn root() -> Self {
+        Self {
+            uid: 0,
+            euid: 0,
+            suid: 0,
+            fsuid: 0,
+            gid: 0,
+            egid: 0,
+            sgid: 0,
+            fsgid: 0,
+            groups: Vec::new(),
+        }
+    }
+
+    /// The process can change its ids freely, like CAP_SETUID.
+    #[inline]
+    pub fn privileged(&self) -> bool {
+        self.euid == 0
+    }
+
+    #[inline]
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.fsgid == gid || self.groups.contains(&gid)
+    }
+
+    /// The credentials used by faccessat, the real ids are used to check.
+    pub fn real(&self) -> Self {
+        Self {
+            fsuid: self.uid,
+            fsgid: self.gid,
+            ..self.clone()
+        }
+    }
+
+    /// The caller can access the process of the target, like ptrace,
+    /// prlimit64 and get_robust_list. The real ids of the caller are the
+    /// same as all the ids of the target unless the caller is privileged.
+    pub fn may_access(&self, target: &Credentials) -> bool {
+        let same_uids = [target.uid, target.euid, target.suid]
+            .iter()
+            .all(|x| *x == self.uid);
+        let same_gids = [target.gid, target.egid, target.sgid]
+            .iter()
+            .all(|x| *x == self.gid);
+        self.privileged() || (same_uids && same_gids)
+    }
+
+    /// The caller can send signals to the process of the target. It is
+    /// looser than `may_access`, the real or effective uid of the caller
+    /// only needs to be the real or saved uid of the target.
+    pub fn may_signal(&self, target: &Credentials) -> bool {
+        self.may_access(target)
+            || [self.uid, self.euid]
+                .iter()
+                .any(|x| *x == target.uid || *x == target.suid)
+    }
+
+    /// The credentials after executing the file, the set-user-ID and
+    /// set-group-ID bits of the file change the effective ids.
+    pub fn exec(&self, stat: &Stat) -> Self {
+        let mut cred = self.clone();
+        if stat.mode.contains(StatMode::SET_UID) {
+            cred.euid = stat.uid;
+        }
+        // set-group-ID without group exec is mandatory locking, not setgid.
+        if stat.mode.contains(StatMode::SET_GID | StatMode::GROUP_EXEC) {
+            cred.egid = stat.gid;
+        }
+        cred.suid = cred.euid;
+        cred.fsuid = cred.euid;
+        cred.sgid = cred.egid;
+        cred.fsgid = cred.egid;
+        cred
+    }
+
+    /// Check the permission bits of the stat.
+    pub fn permits(&self, stat: &Stat, access: Access) -> bool {
+        let perm = stat.mode.bits() & 0o777;
+        // root can do anything, but it needs a exec bit to execute a file.
+        if self.fsuid == 0 {
+            let is_dir = stat.mode.bits() & StatMode::TYPE_MASK.bits() == StatMode::DIR.bits();
+            return !access.contains(Access::EXEC) || is_dir || perm & 0o111 != 0;
+        }
+        let bits = if self.fsuid == stat.uid {
+            perm >> 6
+        } else if self.in_group(stat.gid) {
+            perm >> 3
+        } else {
+            perm
+        };
+        bits & access.bits() == access.bits()
+    }
+
+    pub fn check_file(&self, file: &File, access: Access) -> VfsResult<()> {
+        if !file.has_modes() {
+            return Ok(());
+        }
+        let mut stat = Stat::default();
+        file.stat(&mut stat)?;
+        match self.permits(&stat, access) {
+            true => Ok(()),
+            false => Err(Errno::EACCES),
+        }
+    }
+
+    /// Check the search permission of the directories in the path.
+    pub fn check_search(&self, path: &PathBuf) -> VfsResult<()> {
+        let mut dir = PathBuf::new();
+        let search = |dir: &PathBuf| {
+            self.check_file(
+                &File::open(dir.clone(), OpenFlags::O_DIRECTORY)?,
+                Access::EXEC,
+            )
+        };
+        search(&dir)?;
+        for name in path.dir().iter() {
+            dir = dir.join(name);
+            search(&dir)?;
+        }
+        Ok(())
+    }
+
+    /// Check the access of the open flags.
+    pub fn check_open(&self, file: &File, flags: OpenFlags) -> VfsResult<()> {
+        if flags.contains(OpenFlags::O_PATH) {
+            return Ok(());
+        }
+        let mut access = match flags.bits() & OpenFlags::O_ACCMODE.bits() {
+            0 => Access::READ,
+            1 => Access::WRITE,
+            _ => Access::READ | Access::WRITE,
+        };
+        if flags.contains(OpenFlags::O_TRUNC) {
+            access |= Access::WRITE;
+        }
+        self.check_file(file, access)
+    }
+}
@@ -2,25 +2,70 @@ use crate::tasks::initproc::get_libc_path; // Add this import
 use crate::{consts::USER_STACK_INIT_SIZE, tasks::memset::MemType};
 use crate::{consts::USER_STACK_TOP, syscall::types::elf::elf};
 use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::mem::size_of;
 use devices::PAGE_SIZE;
 use executor::AsyncTask;
 use log::warn;
 use polyhal::va;
 use polyhal_trap::trapframe::{TrapFrame, TrapFrameArgs};
 use syscalls::Errno;
-use xmas_elf::{
-    program::Type,
-    sections::SectionData,
-    symbol_table::{DynEntry64, Entry},
-    ElfFile,
-};
+use xmas_elf::{program::Type, ElfFile};
 
-use super::task::UserTask;
+use super::{task::UserTask, vdso::map_vdso};
+
+cfg_if! {
+    if #[cfg(target_arch = "riscv64")] {
+        pub(super) const ELF_MACHINE: u16 = 243;
+    } else if #[cfg(target_arch = "aarch64")] {
+        pub(super) const ELF_MACHINE: u16 = 183;
+    } else if #[cfg(target_arch = "x86_64")] {
+        pub(super) const ELF_MACHINE: u16 = 62;
+    } else if #[cfg(target_arch = "loongarch64")] {
+        pub(super) const ELF_MACHINE: u16 = 258;
+    }
+}
+
+pub(super) const PT_LOAD: u32 = 1;
+
+/// The elf header written by the kernel, the vDSO and the core dump.
+#[repr(C)]
+pub(super) struct ElfHeader {
+    pub ident: [u8; 16],
+    pub etype: u16,
+    pub machine: u16,
+    pub version: u32,
+    pub entry: u64,
+    pub phoff: u64,
+    pub shoff: u64,
+    pub flags: u32,
+    pub ehsize: u16,
+    pub phentsize: u16,
+    pub phnum: u16,
+    pub shentsize: u16,
+    pub shnum: u16,
+    pub shstrndx: u16,
+}
+
+#[repr(C)]
+pub(super) struct ProgramHeader {
+    pub ptype: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub paddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+    pub align: u64,
+}
+
+/// Write the value at the offset of the buffer.
+pub(super) fn write_at<T>(buffer: &mut [u8], offset: usize, value: T) {
+    assert!(offset + size_of::<T>() <= buffer.len());
+    unsafe { (buffer.as_mut_ptr().add(offset) as *mut T).write_unaligned(value) }
+}
 
 pub trait ElfExtra {
     fn get_ph_addr(&self) -> Result<u64, Errno>;
-    fn dynsym(&self) -> Result<&[DynEntry64], &'static str>;
-    fn relocate(&self, base: usize) -> Result<usize, &str>;
 }
 
 impl ElfExtra for ElfFile<'_> {
@@ -43,56 +88,6 @@ impl ElfExtra for ElfFile<'_> {
             Err(Errno::EBADF)
         }
     }
-
-    fn dynsym(&self) -> Result<&[DynEntry64], &'static str> {
-        match self
-            .find_section_by_name(".dynsym")
-            .ok_or(".dynsym not found")?
-            .get_data(self)
-            .map_err(|_| "corrupted .dynsym")?
-        {
-            SectionData::DynSymbolTable64(dsym) => Ok(dsym),
-            _ => Err("bad .dynsym"),
-        }
-    }
-
-    fn relocate(&self, base: usize) -> Result<usize, &str> {
-        let data = self
-            .find_section_by_name(".rela.dyn")
-            .ok_or(".rela.dyn not found")?
-            .get_data(self)
-            .map_err(|_| "corrupted .rela.dyn")?;
-        let entries = match data {
-            SectionData::Rela64(entries) => entries,
-            _ => return Err("bad .rela.dyn"),
-        };
-        let dynsym = self.dynsym()?;
-        for entry in entries.iter() {
-            const REL_GOT: u32 = 6;
-            const REL_PLT: u32 = 7;
-            const REL_RELATIVE: u32 = 8;
-            const R_RISCV_64: u32 = 2;
-            const R_RISCV_RELATIVE: u32 = 3;
-            const R_AARCH64_RELATIVE: u32 = 0x403;
-            const R_AARCH64_GLOBAL_DATA: u32 = 0x401;
-
-            match entry.get_type() {
-                REL_GOT | REL_PLT | R_RISCV_64 | R_AARCH64_GLOBAL_DATA => {
-                    let dynsym = &dynsym[entry.get_symbol_table_index() as usize];
-                    if dynsym.shndx() == 0 {
-                        let name = dynsym.get_name(self)?;
-                        panic!("need to find symbol: {:?}", name);
-                    } else {
-                        base + dynsym.value() as usize
-                    };
-                }
-                REL_RELATIVE | R_RISCV_RELATIVE | R_AARCH64_RELATIVE => {}
-                t => unimplemented!("unknown type: {}", t),
-            }
-        }
-        // panic!("STOP");
-        Ok(base)
-    }
 }
 
 pub fn init_task_stack(
@@ -117,10 +112,12 @@ pub fn init_task_stack(
         user_task.get_task_id(),
         base + entry_point
     );
-    user_task.inner_map(|inner| {
-        inner.heap = heap_bottom;
-        inner.entry = base + entry_point;
-    });
+    let mm = user_task.mm();
+    let mut mm = mm.lock();
+    mm.heap = heap_bottom;
+    mm.brk_start = heap_bottom;
+    drop(mm);
+    user_task.pcb.lock().entry = base + entry_point;
 
     let mut tcb = user_task.tcb.write();
 
@@ -158,12 +155,18 @@ pub fn init_task_stack(
     auxv.insert(elf::AT_ENTRY, base + entry_point);
     auxv.insert(elf::AT_PHENT, ph_entry_size);
     auxv.insert(elf::AT_PHDR, base + ph_addr);
-    auxv.insert(elf::AT_GID, 0);
-    auxv.insert(elf::AT_EGID, 0);
-    auxv.insert(elf::AT_UID, 0);
-    auxv.insert(elf::AT_EUID, 0);
-    auxv.insert(elf::AT_SECURE, 0);
+    let cred = user_task.pcb.lock().cred.clone();
+    auxv.insert(elf::AT_GID, cred.gid as usize);
+    auxv.insert(elf::AT_EGID, cred.egid as usize);
+    auxv.insert(elf::AT_UID, cred.uid as usize);
+    auxv.insert(elf::AT_EUID, cred.euid as usize);
+    // the loader ignores LD_* envs for the set-user-ID programs.
+    let secure = cred.uid != cred.euid || cred.gid != cred.egid;
+    auxv.insert(elf::AT_SECURE, secure as usize);
     auxv.insert(elf::AT_RANDOM, random_ptr);
+    auxv.insert(elf::AT_SYSINFO_EHDR, map_vdso(&user_task));
+
+    user_task.inner_map(|inner| inner.auxv = auxv.iter().map(|(k, v)| (*k, *v)).collect());
 
     // auxv top
     user_task.push_num(0);
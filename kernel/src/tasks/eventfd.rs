@@ -0,0 +1,68 @@
+use core::mem::size_of;
+use sync::Mutex;
+use syscalls::Errno;
+use vfscore::{INodeInterface, PollEvent, VfsResult};
+
+/// The largest value of the counter, the write blocks if it overflows.
+const EVENTFD_MAX: u64 = u64::MAX - 1;
+
+/// The eventfd, the counter is read and reset, or decreased by 1 with
+/// EFD_SEMAPHORE. The read blocks when the counter is 0.
+pub struct EventFd {
+    pub count: Mutex<u64>,
+    pub semaphore: bool,
+}
+
+impl EventFd {
+    pub fn new(count: u64, semaphore: bool) -> Self {
+        Self {
+            count: Mutex::new(count),
+            semaphore,
+        }
+    }
+}
+
+impl INodeInterface for EventFd {
+    fn readat(&self, _offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        if buffer.len() < size_of::<u64>() {
+            return Err(Errno::EINVAL);
+        }
+        let mut count = self.count.lock();
+        let value = match (*count, self.semaphore) {
+            (0, _) => return Err(Errno::EWOULDBLOCK),
+            (_, true) => 1,
+            (value, false) => value,
+        };
+        *count -= value;
+        buffer[..size_of::<u64>()].copy_from_slice(&value.to_ne_bytes());
+        Ok(size_of::<u64>())
+    }
+
+    fn writeat(&self, _offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        if buffer.len() < size_of::<u64>() {
+            return Err(Errno::EINVAL);
+        }
+        let value = u64::from_ne_bytes(buffer[..size_of::<u64>()].try_into().unwrap());
+        if value == u64::MAX {
+            return Err(Errno::EINVAL);
+        }
+        let mut count = self.count.lock();
+        if *count > EVENTFD_MAX - value {
+            return Err(Errno::EWOULDBLOCK);
+        }
+        *count += value;
+        Ok(size_of::<u64>())
+    }
+
+    fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
+        let count = *self.count.lock();
+        let mut res = PollEvent::NONE;
+        if events.contains(PollEvent::POLLIN) && count > 0 {
+            res |= PollEvent::POLLIN;
+        }
+        if events.contains(PollEvent::POLLOUT) && count < EVENTFD_MAX {
+            res |= PollEvent::POLLOUT;
+        }
+        Ok(res)
+    }
+}
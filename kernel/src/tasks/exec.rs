@@ -1,28 +1,41 @@
 use super::UserTask;
-use crate::tasks::initproc::get_libc_path;
+use crate::tasks::cred::{Access, Credentials};
 use crate::{
     consts::USER_DYN_ADDR,
     tasks::{
         elf::{init_task_stack, ElfExtra},
+        procdir::TASK_CACHE_INO,
         MapTrack, MemArea, MemType,
     },
 };
-use alloc::{
-    boxed::Box,
-    string::{String, ToString},
-    sync::Arc,
-    vec::Vec,
-};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
 use async_recursion::async_recursion;
-use core::ops::{Add, Mul};
-use devices::{frame_alloc_much, FrameTracker, PAGE_SIZE};
-use fs::{file::File, pathbuf::PathBuf, OpenFlags};
+use core::{cmp::min, ops::Add};
+use devices::{frame_alloc, FrameTracker, PAGE_SIZE};
+use fs::{file::File, pathbuf::PathBuf, INodeInterface, OpenFlags, Stat, StatMode};
 use polyhal::MappingFlags;
 use sync::Mutex;
 use syscalls::Errno;
-use xmas_elf::program::{SegmentData, Type};
+use vfscore::VfsResult;
+use xmas_elf::{
+    header,
+    program::{ProgramHeader, Type},
+    ElfFile,
+};
+
+/// The max pages held by the task cache templates, the least recently
+/// used template is evicted when it is exceeded.
+const TASK_CACHE_MAX_PAGES: usize = 0x2000;
+
 pub struct TaskCacheTemplate {
     name: PathBuf,
+    /// The inode and the modify time of the file when it was cached.
+    /// The template is stale if the file was replaced or modified.
+    ino: u64,
+    mtime: usize,
+    /// The interpreter of the dynamic executable.
+    /// The executable is loaded by the interpreter, so there are no maps.
+    interp: Option<PathBuf>,
     entry: usize,
     maps: Vec<MemArea>,
     base: usize,
@@ -31,103 +44,339 @@ pub struct TaskCacheTemplate {
     ph_entry_size: usize,
     ph_addr: usize,
 }
+
+impl TaskCacheTemplate {
+    fn pages(&self) -> usize {
+        self.maps.iter().map(|x| x.mtrackers.len()).sum()
+    }
+
+    fn is_stale(&self, version: (u64, usize)) -> bool {
+        (self.ino, self.mtime) != version
+    }
+}
+
+/// Task cache templates, sorted by the last used time.
+/// The least recently used one is at the front.
 pub static TASK_CACHES: Mutex<Vec<TaskCacheTemplate>> = Mutex::new(Vec::new());
 
+/// Get the inode and the modify time of the file.
+fn file_version(file: &File) -> Result<(u64, usize), Errno> {
+    let mut stat = Stat::default();
+    file.stat(&mut stat)?;
+    Ok((stat.ino, stat.mtime.to_nsec()))
+}
+
+/// Push the template to the cache, evict the least recently used
+/// templates until the pages are in the budget.
+fn insert_task_cache(template: TaskCacheTemplate) -> Result<(), Errno> {
+    let pages = template.pages();
+    if pages > TASK_CACHE_MAX_PAGES {
+        return Err(Errno::ENOMEM);
+    }
+    let mut caches = TASK_CACHES.lock();
+    caches.retain(|x| x.name != template.name);
+    let mut used: usize = caches.iter().map(TaskCacheTemplate::pages).sum();
+    while used + pages > TASK_CACHE_MAX_PAGES {
+        let evicted = caches.remove(0);
+        debug!("evict task cache: {}", evicted.name);
+        used -= evicted.pages();
+    }
+    caches.push(template);
+    Ok(())
+}
+
+/// Drop all the task cache templates.
+pub fn flush_task_caches() {
+    TASK_CACHES.lock().clear();
+}
+
+/// Drop the templates of the file and the executables interpreted by it,
+/// it is called when the file may be written, renamed or removed. The
+/// inode and the modify time can't detect it on all the filesystems.
+pub fn invalidate_task_cache(path: &PathBuf) {
+    TASK_CACHES
+        .lock()
+        .retain(|x| x.name != *path && x.interp.as_ref() != Some(path));
+}
+
+/// The interpreter of the dynamic executable in its PT_INTERP, it is
+/// ENOENT if the interpreter doesn't exist in the filesystem.
+fn read_interp(file: &File, ph: ProgramHeader) -> Result<PathBuf, Errno> {
+    let len = ph.file_size() as usize;
+    if len == 0 || len > BINPRM_BUF_SIZE {
+        return Err(Errno::ENOEXEC);
+    }
+    let mut buffer = vec![0u8; len];
+    if file.readat(ph.offset() as usize, &mut buffer)? != len {
+        return Err(Errno::ENOEXEC);
+    }
+    let end = buffer.iter().position(|x| *x == 0).unwrap_or(len);
+    let interp = core::str::from_utf8(&buffer[..end]).map_err(|_| Errno::ENOEXEC)?;
+    File::open(interp.into(), OpenFlags::O_RDONLY).map_err(|_| Errno::ENOENT)?;
+    Ok(interp.into())
+}
+
 pub fn cache_task_template(path: PathBuf) -> Result<(), Errno> {
     let file = File::open(path.clone(), OpenFlags::O_RDONLY)?;
-    let file_size = file.file_size()?;
-    let frame_paddr = frame_alloc_much(file_size.div_ceil(PAGE_SIZE));
-    let buffer = frame_paddr.as_ref().unwrap()[0].slice_mut_with_len(file_size);
-    let rsize = file.readat(0, buffer)?;
-    assert_eq!(rsize, file_size);
-    // flush_dcache_range();
+    let (ino, mtime) = file_version(&file)?;
     // 读取elf信息
-    if let Ok(elf) = xmas_elf::ElfFile::new(&buffer) {
-        let elf_header = elf.header;
+    let buffer = read_elf_headers(&file)?;
+    let elf = xmas_elf::ElfFile::new(&buffer).map_err(|_| Errno::ENOEXEC)?;
+    let elf_header = elf.header;
+    let entry_point = elf.header.pt2.entry_point() as usize;
 
-        let entry_point = elf.header.pt2.entry_point() as usize;
-        // this assert ensures that the file is elf file.
-        assert_eq!(
-            elf_header.pt1.magic,
-            [0x7f, 0x45, 0x4c, 0x46],
-            "invalid elf!"
-        );
+    // check if it is libc, dlopen, it needs recurit.
+    let header = elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(Type::Interp));
 
-        // check if it is libc, dlopen, it needs recurit.
-        let header = elf
-            .program_iter()
-            .find(|ph| ph.get_type() == Ok(Type::Interp));
-
-        if let Some(_header) = header {
-            unimplemented!("can't cache dynamic file.");
+    // The dynamic executable is loaded by the interpreter,
+    // cache the interpreter and remember the relation.
+    if let Some(header) = header {
+        let interp = read_interp(&file, header)?;
+        if interp != path {
+            cache_task_template(interp.clone())?;
         }
+        return insert_task_cache(TaskCacheTemplate {
+            name: path,
+            ino,
+            mtime,
+            interp: Some(interp),
+            entry: entry_point,
+            maps: Vec::new(),
+            base: 0,
+            heap_bottom: 0,
+            ph_count: 0,
+            ph_entry_size: 0,
+            ph_addr: 0,
+        });
+    }
 
-        // 获取程序所有段之后的内存，4K 对齐后作为堆底
-        let heap_bottom = elf
-            .program_iter()
-            .map(|x| (x.virtual_addr() + x.mem_size()) as usize)
-            .max()
-            .unwrap()
-            .div_ceil(PAGE_SIZE)
-            .mul(PAGE_SIZE);
+    // 获取程序所有段之后的内存，4K 对齐后作为堆底
+    let base = load_base(&elf);
+    let heap_bottom = load_heap_bottom(&elf, base)?;
+    let mut maps = Vec::new();
 
-        let base = elf.relocate(USER_DYN_ADDR).unwrap_or(0);
-        let mut maps = Vec::new();
+    // map sections.
+    for ph in elf
+        .program_iter()
+        .filter(|x| x.get_type() == Ok(Type::Load))
+    {
+        let file_size = ph.file_size() as usize;
+        let mem_size = ph.mem_size() as usize;
+        let offset = ph.offset() as usize;
+        let virt_addr = base + ph.virtual_addr() as usize;
+        let vpn = virt_addr / PAGE_SIZE;
 
-        // map sections.
-        elf.program_iter()
-            .filter(|x| x.get_type().unwrap() == xmas_elf::program::Type::Load)
-            .for_each(|ph| {
-                let file_size = ph.file_size() as usize;
-                let mem_size = ph.mem_size() as usize;
-                let offset = ph.offset() as usize;
-                let virt_addr = base + ph.virtual_addr() as usize;
-                let vpn = virt_addr / PAGE_SIZE;
-
-                let page_count = (virt_addr + mem_size).div_ceil(PAGE_SIZE) - vpn;
-                let pages: Vec<Arc<FrameTracker>> = frame_alloc_much(page_count)
-                    .expect("can't alloc in cache task template")
-                    .into_iter()
-                    .map(|x| Arc::new(x))
-                    .collect();
-                let ppn_space = pages[0]
-                    .add(virt_addr % PAGE_SIZE)
-                    .slice_mut_with_len(file_size);
-
-                ppn_space.copy_from_slice(&buffer[offset..offset + file_size]);
-
-                maps.push(MemArea {
-                    mtype: MemType::CodeSection,
-                    mtrackers: pages
-                        .into_iter()
-                        .enumerate()
-                        .map(|(i, x)| MapTrack {
-                            vaddr: va!((vpn + i) * PAGE_SIZE),
-                            tracker: x,
-                            rwx: 0,
-                        })
-                        .collect(),
-                    file: None,
-                    offset: 0,
-                    start: vpn * PAGE_SIZE,
-                    len: page_count * PAGE_SIZE,
+        let page_count = (virt_addr + mem_size).div_ceil(PAGE_SIZE) - vpn;
+        let pages: Vec<Arc<FrameTracker>> = (0..page_count)
+            .map(|_| frame_alloc().map(Arc::new))
+            .collect::<Option<_>>()
+            .ok_or(Errno::ENOMEM)?;
+
+        // the frames are not contiguous, read the segment page by page.
+        let mut rsize = 0;
+        while rsize < file_size {
+            let vaddr = virt_addr + rsize;
+            let len = min(PAGE_SIZE - vaddr % PAGE_SIZE, file_size - rsize);
+            let page_space = pages[vaddr / PAGE_SIZE - vpn]
+                .add(vaddr % PAGE_SIZE)
+                .slice_mut_with_len(len);
+            if file.readat(offset + rsize, page_space)? != len {
+                return Err(Errno::EIO);
+            }
+            rsize += len;
+        }
+
+        maps.push(MemArea {
+            mtype: MemType::CodeSection,
+            mtrackers: pages
+                .into_iter()
+                .enumerate()
+                .map(|(i, x)| MapTrack {
+                    vaddr: va!((vpn + i) * PAGE_SIZE),
+                    tracker: x,
+                    rwx: 0,
                 })
-            });
-        TASK_CACHES.lock().push(TaskCacheTemplate {
-            name: path,
-            entry: entry_point,
-            maps,
-            base,
-            heap_bottom,
-            ph_count: elf_header.pt2.ph_count() as _,
-            ph_entry_size: elf_header.pt2.ph_entry_size() as _,
-            ph_addr: elf.get_ph_addr().unwrap_or(0) as _,
-        });
+                .collect(),
+            file: None,
+            offset: 0,
+            start: vpn * PAGE_SIZE,
+            len: page_count * PAGE_SIZE,
+        })
     }
-    Ok(())
+    insert_task_cache(TaskCacheTemplate {
+        name: path,
+        ino,
+        mtime,
+        interp: None,
+        entry: entry_point,
+        maps,
+        base,
+        heap_bottom,
+        ph_count: elf_header.pt2.ph_count() as _,
+        ph_entry_size: elf_header.pt2.ph_entry_size() as _,
+        ph_addr: elf.get_ph_addr().unwrap_or(0) as _,
+    })
+}
+
+/// The procfs node of the task cache templates.
+///
+/// Reading it lists the templates, writing a path caches the executable,
+/// writing `flush` drops all the templates.
+pub struct TaskCacheNode;
+
+impl INodeInterface for TaskCacheNode {
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let caches = TASK_CACHES.lock();
+        let used: usize = caches.iter().map(TaskCacheTemplate::pages).sum();
+        let mut info = format!("pages: {}/{}\n", used, TASK_CACHE_MAX_PAGES);
+        for cache in caches.iter() {
+            match &cache.interp {
+                Some(interp) => info += &format!("{} interp {}\n", cache.name, interp),
+                None => info += &format!("{} {}\n", cache.name, cache.pages()),
+            }
+        }
+        let info = info.as_bytes();
+        if offset >= info.len() {
+            return Ok(0);
+        }
+        let rlen = min(buffer.len(), info.len() - offset);
+        buffer[..rlen].copy_from_slice(&info[offset..offset + rlen]);
+        Ok(rlen)
+    }
+
+    fn writeat(&self, _offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        let cmd = core::str::from_utf8(buffer)
+            .map_err(|_| Errno::EINVAL)?
+            .trim();
+        match cmd {
+            "flush" => flush_task_caches(),
+            path if path.starts_with('/') => cache_task_template(path.into())?,
+            _ => return Err(Errno::EINVAL),
+        }
+        Ok(buffer.len())
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        stat.dev = 0;
+        stat.ino = TASK_CACHE_INO;
+        stat.mode = StatMode::FILE;
+        stat.nlink = 1;
+        stat.uid = 0;
+        stat.gid = 0;
+        stat.size = 0;
+        stat.blksize = 512;
+        stat.blocks = 0;
+        stat.rdev = 0;
+        Ok(())
+    }
+}
+
+/// The size of the elf64 header.
+const ELF_HEADER_SIZE: usize = 64;
+
+/// The max size of the elf header and the program headers, like linux.
+const ELF_MAX_HEADERS_SIZE: usize = 0x10000;
+
+/// Read the elf header and the program headers at the head of the file.
+///
+/// The segments are not in the buffer, so only the program headers can be
+/// used. The segments are read into their frames when they are loaded.
+fn read_elf_headers(file: &File) -> Result<Vec<u8>, Errno> {
+    let mut buffer = vec![0u8; ELF_HEADER_SIZE];
+    if file.readat(0, &mut buffer)? != ELF_HEADER_SIZE {
+        return Err(Errno::ENOEXEC);
+    }
+    let header = xmas_elf::header::parse_header(&buffer).map_err(|_| Errno::ENOEXEC)?;
+    // the values come from the file, they may overflow.
+    let ph_end = (header.pt2.ph_count() as usize)
+        .checked_mul(header.pt2.ph_entry_size() as usize)
+        .and_then(|x| x.checked_add(header.pt2.ph_offset().try_into().ok()?))
+        .ok_or(Errno::ENOEXEC)?;
+    if ph_end > ELF_MAX_HEADERS_SIZE {
+        return Err(Errno::ENOEXEC);
+    }
+    if ph_end > ELF_HEADER_SIZE {
+        buffer.resize(ph_end, 0);
+        if file.readat(ELF_HEADER_SIZE, &mut buffer[ELF_HEADER_SIZE..])? != ph_end - ELF_HEADER_SIZE
+        {
+            return Err(Errno::ENOEXEC);
+        }
+    }
+    Ok(buffer)
+}
+
+/// Get the load base of the elf file.
+/// The position independent file is loaded at [USER_DYN_ADDR].
+fn load_base(elf: &ElfFile) -> usize {
+    match elf.header.pt2.type_().as_type() {
+        header::Type::SharedObject => USER_DYN_ADDR,
+        _ => 0,
+    }
+}
+
+/// Check the PT_LOAD headers and get the heap bottom after the segments.
+///
+/// The headers come from the file, the segments must fit in their memory
+/// and the addresses after the load base can't overflow.
+fn load_heap_bottom(elf: &ElfFile, base: usize) -> Result<usize, Errno> {
+    let mut heap_bottom = None;
+    for ph in elf
+        .program_iter()
+        .filter(|x| x.get_type() == Ok(Type::Load))
+    {
+        if ph.file_size() > ph.mem_size() {
+            return Err(Errno::ENOEXEC);
+        }
+        let end = usize::try_from(ph.virtual_addr())
+            .ok()
+            .and_then(|x| x.checked_add(ph.mem_size().try_into().ok()?))
+            .and_then(|x| x.checked_next_multiple_of(PAGE_SIZE))
+            .ok_or(Errno::ENOEXEC)?;
+        base.checked_add(end).ok_or(Errno::ENOEXEC)?;
+        heap_bottom = heap_bottom.max(Some(end));
+    }
+    heap_bottom.ok_or(Errno::ENOEXEC)
+}
+
+/// The size of the header read from the file to detect the format,
+/// the same as `BINPRM_BUF_SIZE` in linux.
+const BINPRM_BUF_SIZE: usize = 256;
+
+/// The max depth of the interpreters, a script can use a script
+/// as the interpreter, but the nesting is limited like linux.
+const BINPRM_MAX_RECURSION: usize = 4;
+
+const ELF_MAGIC: [u8; 4] = [0x7f, 0x45, 0x4c, 0x46];
+
+/// Parse the `#!interpreter [arg]` line at the head of the script.
+///
+/// Like linux, everything after the interpreter is passed as a single
+/// argument, only the spaces and tabs around them are stripped.
+fn parse_shebang(head: &[u8]) -> Result<(String, Option<String>), Errno> {
+    let is_blank = |c: char| c == ' ' || c == '\t';
+    let (line, truncated) = match head[2..].iter().position(|x| *x == b'\n') {
+        Some(end) => (&head[2..2 + end], false),
+        None => (&head[2..], head.len() == BINPRM_BUF_SIZE),
+    };
+    let line = core::str::from_utf8(line).map_err(|_| Errno::ENOEXEC)?;
+    let line = line.trim_start_matches(is_blank);
+    let (interp, arg) = match line.find(is_blank) {
+        Some(idx) => (&line[..idx], line[idx..].trim_matches(is_blank)),
+        // the interpreter name itself doesn't fit in the buffer.
+        None if truncated => return Err(Errno::ENOEXEC),
+        None => (line, ""),
+    };
+    if interp.is_empty() {
+        return Err(Errno::ENOEXEC);
+    }
+    Ok((
+        String::from(interp),
+        (!arg.is_empty()).then(|| String::from(arg)),
+    ))
 }
 
-#[async_recursion(Sync)]
 pub async fn exec_with_process(
     task: Arc<UserTask>,
     curr_dir: PathBuf,
@@ -135,17 +384,81 @@ pub async fn exec_with_process(
     args: Vec<String>,
     envp: Vec<String>,
 ) -> Result<Arc<UserTask>, Errno> {
+    let comm = String::from(path.rsplit('/').next().unwrap_or(""));
+    let task = exec_binprm(task, curr_dir, path, args, envp, None, 0).await?;
+    task.set_comm(&comm);
+    Ok(task)
+}
+
+#[async_recursion(Sync)]
+async fn exec_binprm(
+    task: Arc<UserTask>,
+    curr_dir: PathBuf,
+    filename: String,
+    args: Vec<String>,
+    envp: Vec<String>,
+    // the credentials of the executable loaded by the interpreter.
+    cred: Option<Credentials>,
+    depth: usize,
+) -> Result<Arc<UserTask>, Errno> {
+    if depth > BINPRM_MAX_RECURSION {
+        return Err(Errno::ELOOP);
+    }
     // copy args, avoid free before pushing.
     // let path = String::from(path);
-    let path = curr_dir.join(&path);
+    let path = curr_dir.join(&filename);
+
+    // TODO: 运行程序的时候，判断当前的路径
+    let file = File::open(path.clone(), OpenFlags::O_RDONLY)
+        .map(Arc::new)?
+        .clone();
+    // 检查执行权限，并计算 set-user-ID 之后的 credentials
+    let (curr_cred, no_new_privs) = {
+        let pcb = task.pcb.lock();
+        (pcb.cred.clone(), pcb.no_new_privs)
+    };
+    // the tracer could take over the privileged process.
+    let traced = task.is_traced();
+    curr_cred.check_file(&file, Access::EXEC)?;
+    let mut stat = Stat::default();
+    file.stat(&mut stat)?;
+    if no_new_privs || traced {
+        stat.mode.remove(StatMode::SET_UID | StatMode::SET_GID);
+    }
+    let cred = cred.unwrap_or_else(|| curr_cred.exec(&stat));
 
-    let user_task = task.clone();
-    user_task.pcb.lock().memset.clear();
-    user_task.page_table.restore();
-    user_task.page_table.change();
+    let mut caches = TASK_CACHES.lock();
+    if let Some(idx) = caches.iter().position(|x| x.name == path) {
+        if caches[idx].is_stale(file_version(&file)?) {
+            debug!("drop stale task cache: {}", path);
+            caches.remove(idx);
+        } else {
+            // move to the tail, it is the most recently used now.
+            let cache_task = caches.remove(idx);
+            caches.push(cache_task);
+        }
+    }
+    if let Some(cache_task) = caches.last().filter(|x| x.name == path) {
+        if let Some(interp) = &cache_task.interp {
+            let interp = interp.path();
+            drop(caches);
+            let mut new_args = vec![interp.clone()];
+            new_args.extend(args);
+            return exec_binprm(
+                task,
+                curr_dir,
+                interp,
+                new_args,
+                envp,
+                Some(cred),
+                depth + 1,
+            )
+            .await;
+        }
+        let user_task = task.clone();
+        user_task.set_exec_cred(cred);
+        user_task.flush_exec();
 
-    let caches = TASK_CACHES.lock();
-    if let Some(cache_task) = caches.iter().find(|x| x.name == path) {
         init_task_stack(
             user_task.clone(),
             args,
@@ -159,11 +472,11 @@ pub async fn exec_with_process(
         );
 
         for area in &cache_task.maps {
-            user_task.inner_map(|pcb| {
-                pcb.memset
-                    .sub_area(area.start, area.start + area.len, &user_task.page_table);
-                pcb.memset.push(area.clone());
-            });
+            let mm = user_task.mm();
+            let mut mm = mm.lock();
+            mm.sub_area(area.start, area.start + area.len);
+            mm.memset.push(area.clone());
+            drop(mm);
             for mtracker in area.mtrackers.iter() {
                 user_task.map(mtracker.tracker.0, mtracker.vaddr, MappingFlags::URX);
             }
@@ -171,34 +484,32 @@ pub async fn exec_with_process(
         Ok(user_task)
     } else {
         drop(caches);
-        // TODO: 运行程序的时候，判断当前的路径
-        let file = File::open(path.clone(), OpenFlags::O_RDONLY)
-            .map(Arc::new)?
-            .clone();
-        let file_size = file.file_size()?;
-        let frame_ppn = frame_alloc_much(file_size.div_ceil(PAGE_SIZE));
-        let buffer = frame_ppn.as_ref().unwrap()[0].slice_mut_with_len(file_size);
-        let rsize = file.readat(0, buffer)?;
-        assert_eq!(rsize, file_size);
-        // flush_dcache_range();
+
+        // Check the format before destroying the old memory,
+        // the caller is still alive if the exec is failed.
+        let mut head = [0u8; BINPRM_BUF_SIZE];
+        let hlen = file.readat(0, &mut head)?;
+        let head = &head[..hlen];
+        if head.starts_with(b"#!") {
+            let (interp, arg) = parse_shebang(head)?;
+            // argv: interpreter [arg] filename argv[1..]
+            let mut new_args = vec![interp.clone()];
+            new_args.extend(arg);
+            new_args.push(filename);
+            new_args.extend(args.into_iter().skip(1));
+            // the set-user-ID bits of the scripts are ignored like linux.
+            return exec_binprm(task, curr_dir, interp, new_args, envp, None, depth + 1).await;
+        }
+        if !head.starts_with(&ELF_MAGIC) {
+            return Err(Errno::ENOEXEC);
+        }
+
         // 读取elf信息
-        let elf = if let Ok(elf) = xmas_elf::ElfFile::new(&buffer) {
-            elf
-        } else {
-            let mut new_args = vec!["busybox".to_string(), "sh".to_string()];
-            args.iter().for_each(|x| new_args.push(x.clone()));
-            return exec_with_process(task, curr_dir, String::from("busybox"), new_args, envp)
-                .await;
-        };
+        let buffer = read_elf_headers(&file)?;
+        let elf = xmas_elf::ElfFile::new(&buffer).map_err(|_| Errno::ENOEXEC)?;
         let elf_header = elf.header;
 
         let entry_point = elf.header.pt2.entry_point() as usize;
-        // this assert ensures that the file is elf file.
-        assert_eq!(
-            elf_header.pt1.magic,
-            [0x7f, 0x45, 0x4c, 0x46],
-            "invalid elf!"
-        );
         // WARRNING: this convert async task to user task.
         let user_task = task.clone();
 
@@ -207,25 +518,27 @@ pub async fn exec_with_process(
             .program_iter()
             .find(|ph| ph.get_type() == Ok(Type::Interp));
         if let Some(header) = header {
-            if let Ok(SegmentData::Undefined(_data)) = header.get_data(&elf) {
-                drop(frame_ppn);
-                let mut new_args = vec![String::from(&get_libc_path())];
-                new_args.extend(args);
-                return exec_with_process(task, curr_dir, new_args[0].clone(), new_args, envp)
-                    .await;
-            }
+            let mut new_args = vec![read_interp(&file, header)?.path()];
+            new_args.extend(args);
+            return exec_binprm(
+                task,
+                curr_dir,
+                new_args[0].clone(),
+                new_args,
+                envp,
+                Some(cred),
+                depth + 1,
+            )
+            .await;
         }
 
         // 获取程序所有段之后的内存，4K 对齐后作为堆底
-        let heap_bottom = elf
-            .program_iter()
-            .map(|x| (x.virtual_addr() + x.mem_size()) as usize)
-            .max()
-            .unwrap()
-            .div_ceil(PAGE_SIZE)
-            .mul(PAGE_SIZE);
+        let base = load_base(&elf);
+        let heap_bottom = load_heap_bottom(&elf, base)?;
+
+        user_task.set_exec_cred(cred);
+        user_task.flush_exec();
 
-        let base = elf.relocate(USER_DYN_ADDR).unwrap_or(0);
         init_task_stack(
             user_task.clone(),
             args,
@@ -239,29 +552,26 @@ pub async fn exec_with_process(
         );
 
         // map sections.
-        elf.program_iter()
-            .filter(|x| x.get_type().unwrap() == xmas_elf::program::Type::Load)
-            .for_each(|ph| {
-                let file_size = ph.file_size() as usize;
-                let mem_size = ph.mem_size() as usize;
-                let offset = ph.offset() as usize;
-                let virt_addr = base + ph.virtual_addr() as usize;
-                let vpn = virt_addr / PAGE_SIZE;
-
-                let page_count = (virt_addr + mem_size).div_ceil(PAGE_SIZE) - vpn;
-                let ppn_start =
-                    user_task.frame_alloc(va!(virt_addr).floor(), MemType::CodeSection, page_count);
-                let page_space = va!(virt_addr).slice_mut_with_len(file_size);
-                let ppn_space = ppn_start
-                    .expect("not have enough memory")
-                    .add(virt_addr % PAGE_SIZE)
-                    .slice_mut_with_len(file_size);
-
-                page_space.copy_from_slice(&buffer[offset..offset + file_size]);
-                assert_eq!(ppn_space, page_space);
-                assert_eq!(&buffer[offset..offset + file_size], ppn_space);
-                assert_eq!(&buffer[offset..offset + file_size], page_space);
-            });
+        for ph in elf
+            .program_iter()
+            .filter(|x| x.get_type() == Ok(Type::Load))
+        {
+            let file_size = ph.file_size() as usize;
+            let mem_size = ph.mem_size() as usize;
+            let offset = ph.offset() as usize;
+            let virt_addr = base + ph.virtual_addr() as usize;
+            let vpn = virt_addr / PAGE_SIZE;
+
+            let page_count = (virt_addr + mem_size).div_ceil(PAGE_SIZE) - vpn;
+            user_task
+                .frame_alloc(va!(virt_addr).floor(), MemType::CodeSection, page_count)
+                .ok_or(Errno::ENOMEM)?;
+            // read the segment into the mapped frames directly.
+            let page_space = va!(virt_addr).slice_mut_with_len(file_size);
+            if file.readat(offset, page_space)? != file_size {
+                return Err(Errno::EIO);
+            }
+        }
         Ok(user_task)
     }
 }
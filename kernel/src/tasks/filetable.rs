@@ -1,3 +1,7 @@
+use crate::syscall::types::sys::{
+    Rlimit, NR_OPEN, RLIMIT_CORE, RLIMIT_MEMLOCK, RLIMIT_NOFILE, RLIMIT_SIGPENDING, RLIMIT_STACK,
+    RLIM_INFINITY, RLIM_NLIMITS,
+};
 use alloc::{sync::Arc, vec::Vec};
 use core::ops::{Deref, DerefMut};
 use fs::file::File;
@@ -35,8 +39,14 @@ impl DerefMut for FileTable {
     }
 }
 
-pub fn rlimits_new() -> Vec<usize> {
-    let mut rlimits = vec![0usize; 8];
-    rlimits[7] = FILE_MAX;
+/// The default limits of the first process, the forked processes inherit
+/// the limits of the parent.
+pub fn rlimits_new() -> [Rlimit; RLIM_NLIMITS] {
+    let mut rlimits = [Rlimit::new(RLIM_INFINITY, RLIM_INFINITY); RLIM_NLIMITS];
+    rlimits[RLIMIT_STACK] = Rlimit::new(0x80_0000, RLIM_INFINITY);
+    rlimits[RLIMIT_CORE] = Rlimit::new(0, RLIM_INFINITY);
+    rlimits[RLIMIT_NOFILE] = Rlimit::new(FILE_MAX, NR_OPEN);
+    rlimits[RLIMIT_MEMLOCK] = Rlimit::new(0x80_0000, 0x80_0000);
+    rlimits[RLIMIT_SIGPENDING] = Rlimit::new(0x1000, 0x1000);
     rlimits
 }
@@ -14,6 +14,7 @@ use polyhal::{debug_console::DebugConsole, instruction::shutdown};
 use vfscore::INodeInterface;
 
 use crate::tasks::add_user_task;
+use crate::tasks::set_init_proc;
 use executor::AsyncTask;
 use sync::Mutex;
 
@@ -108,6 +109,8 @@ async fn command(cmd: &str, work_dir: PathBuf) {
             .expect("can't add task to excutor");
             curr_task.before_run();
             let task_id = task.get_task_id();
+            // the command is the init of the tasks it creates.
+            set_init_proc(&task);
             thread::spawn(task.clone(), user_entry());
 
             let task = tid2task(task_id).unwrap();
@@ -62,6 +62,7 @@ pub enum MemType {
     Mmap,
     Shared,
     ShareFile,
+    Vdso,
 }
 
 #[derive(Clone)]
@@ -1,32 +1,57 @@
 mod async_ops;
+pub mod coredump;
+pub mod cred;
 pub mod elf;
+mod eventfd;
 pub mod exec;
 mod filetable;
 mod initproc;
 mod memset;
+mod pidfd;
+mod procdir;
+pub mod ptrace;
 mod shm;
 mod signal;
 mod task;
+mod timerfd;
+pub mod vdso;
 
 use self::initproc::initproc;
-use crate::{consts::USER_WORK_DIR, syscall::NET_SERVER, user::entry::user_entry};
+use crate::{
+    consts::USER_WORK_DIR,
+    syscall::{
+        types::signal::{SigInfo, SI_KERNEL},
+        NET_SERVER,
+    },
+    user::entry::user_entry,
+};
+use ::signal::SignalFlags;
 use alloc::{
     string::String,
     sync::Weak,
     {sync::Arc, vec::Vec},
 };
 pub use async_ops::{
-    futex_requeue, futex_wake, WaitFutex, WaitHandleAbleSignal, WaitPid, WaitSignal,
+    futex_dequeue, futex_requeue, futex_wake, futex_wake_bitset, interruptible, wait_child,
+    WaitContinue, WaitEvent, WaitExit, WaitFutex, WaitHandleAbleSignal, WaitPid, WaitSignal,
+    WaitTarget, WaitVfork,
 };
 use devices::get_net_device;
+pub use eventfd::EventFd;
 use exec::exec_with_process;
-use executor::{current_task, thread, yield_now, AsyncTask, TaskId, DEFAULT_EXECUTOR};
+use executor::{
+    current_task, thread, tid2task, yield_now, AsyncTask, TaskId, DEFAULT_EXECUTOR, TASK_MAP,
+};
 use fs::pathbuf::PathBuf;
 pub use memset::{MapTrack, MemArea, MemType};
+pub use pidfd::PidFd;
 use polyhal::common::get_cpu_num;
 pub use shm::{MapedSharedMemory, SharedMemory, SHARED_MEMORY};
-pub use signal::SignalList;
-pub use task::UserTask;
+pub use signal::{SignalFd, SignalList};
+use sync::Mutex;
+use syscalls::Errno;
+pub use task::{FutexTable, FutexWaiter, UserTask, SHARED_FUTEX_TABLE};
+pub use timerfd::TimerFd;
 
 pub enum UserTaskControlFlow {
     Continue,
@@ -46,8 +71,101 @@ pub async fn handle_net() {
     }
 }
 
+/// Get all the alive processes, the threads are not included.
+pub fn processes() -> Vec<Arc<UserTask>> {
+    TASK_MAP
+        .lock()
+        .values()
+        .filter_map(|x| x.upgrade())
+        .filter_map(|x| x.downcast_arc::<UserTask>().ok())
+        .filter(|x| x.task_id == x.process_id && x.exit_code().is_none())
+        .collect()
+}
+
+/// The init process, the orphans are reparented to it.
+static INIT_PROC: Mutex<Weak<UserTask>> = Mutex::new(Weak::new());
+
+pub fn set_init_proc(task: &Arc<UserTask>) {
+    *INIT_PROC.lock() = Arc::downgrade(task);
+}
+
+pub fn init_proc() -> Option<Arc<UserTask>> {
+    INIT_PROC.lock().upgrade()
+}
+
+/// Get the user task by the task id.
+pub fn tid2user_task(tid: usize) -> Option<Arc<UserTask>> {
+    tid2task(tid)?.downcast_arc::<UserTask>().ok()
+}
+
+/// Send the signal to the process group, it is used by the tty.
+pub fn signal_pgrp(pgid: usize, signum: usize) {
+    processes()
+        .iter()
+        .filter(|x| x.pcb.lock().pgid == pgid)
+        .for_each(|x| x.send_process_siginfo(SigInfo::new(signum as _, SI_KERNEL)));
+}
+
+/// The process group is orphaned if no member has a parent in another
+/// process group of the same session, it can't be stopped by the tty.
+fn pgrp_orphaned(procs: &[Arc<UserTask>], pgid: usize, sid: usize) -> bool {
+    !procs.iter().filter(|x| x.pcb.lock().pgid == pgid).any(|x| {
+        x.parent.read().upgrade().is_some_and(|parent| {
+            let pcb = parent.pcb.lock();
+            pcb.pgid != pgid && pcb.sid == sid
+        })
+    })
+}
+
+/// The job control of the tty, pgid is the foreground process group.
+/// The background process group gets SIGTTIN at the read and SIGTTOU at
+/// the write, the syscall is restarted after it is continued.
+pub fn tty_job_control(pgid: usize, signum: usize) -> Result<(), Errno> {
+    // the kernel tasks have no process group.
+    let Ok(task) = current_task().downcast_arc::<UserTask>() else {
+        return Ok(());
+    };
+    let (own_pgid, sid) = {
+        let pcb = task.pcb.lock();
+        (pcb.pgid, pcb.sid)
+    };
+    if own_pgid == pgid {
+        return Ok(());
+    }
+    // only the session of the foreground process group is controlled.
+    let procs = processes();
+    let controlling = procs.iter().any(|x| {
+        let pcb = x.pcb.lock();
+        pcb.pgid == pgid && pcb.sid == sid
+    });
+    if !controlling {
+        return Ok(());
+    }
+    let signal = SignalFlags::from_num(signum);
+    let ignored = task.sighand().lock()[signum].handler == 1;
+    let blocked = task.tcb.read().sigmask.mask & signal.bits() as usize != 0;
+    if ignored || blocked {
+        // the write is allowed, the read fails.
+        return match signal == SignalFlags::SIGTTIN {
+            true => Err(Errno::EIO),
+            false => Ok(()),
+        };
+    }
+    if pgrp_orphaned(&procs, own_pgid, sid) {
+        return Err(Errno::EIO);
+    }
+    signal_pgrp(own_pgid, signum);
+    Err(Errno::ERESTARTSYS)
+}
+
 pub fn init() {
     DEFAULT_EXECUTOR.init(get_cpu_num());
+    vdso::init();
+    coredump::init();
+    devfs::set_signal_pgrp(signal_pgrp);
+    devfs::set_job_control(tty_job_control);
+    procfs::register_node("task_cache", Arc::new(exec::TaskCacheNode));
+    procdir::init();
     thread::spawn_blank(initproc());
     // #[cfg(feature = "net")]
     // thread::spawn_blank(KernelTask::new(handle_net()));
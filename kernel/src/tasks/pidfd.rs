@@ -0,0 +1,33 @@
+use super::UserTask;
+use alloc::sync::Weak;
+use executor::AsyncTask;
+use vfscore::{INodeInterface, PollEvent, VfsResult};
+
+/// The pidfd refers to the process, it is readable when the process exits.
+/// The process is reaped when the weak reference fails.
+pub struct PidFd {
+    pub task: Weak<UserTask>,
+}
+
+impl PidFd {
+    pub fn new(task: Weak<UserTask>) -> Self {
+        Self { task }
+    }
+
+    /// Whether the process has exited.
+    pub fn exited(&self) -> bool {
+        self.task
+            .upgrade()
+            .map_or(true, |task| task.exit_code().is_some())
+    }
+}
+
+impl INodeInterface for PidFd {
+    fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
+        let mut res = PollEvent::NONE;
+        if events.contains(PollEvent::POLLIN) && self.exited() {
+            res |= PollEvent::POLLIN;
+        }
+        Ok(res)
+    }
+}
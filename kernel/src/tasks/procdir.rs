@@ -0,0 +1,129 @@
+//! The process directories of the procfs, `/proc/<pid>` and `/proc/self`.
+//!
+//! Only `comm` is provided now, writing it renames the task like
+//! PR_SET_NAME.
+
+use super::{processes, tid2user_task, UserTask};
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::cmp::min;
+use executor::current_task;
+use fs::{Stat, StatMode};
+use syscalls::Errno;
+use vfscore::{DirEntry, FileType, INodeInterface, VfsResult};
+
+/// The inode numbers of the kernel nodes in the procfs, the builtin nodes
+/// of the procfs use 1.
+pub const TASK_CACHE_INO: u64 = 2;
+pub const CORE_PATTERN_INO: u64 = 3;
+/// The directory of the task `tid` uses `PROCESS_INO + tid * 2`,
+/// its `comm` uses the next one.
+const PROCESS_INO: u64 = 0x1000;
+
+pub fn init() {
+    procfs::set_process_dirs(lookup, entries);
+}
+
+fn lookup(name: &str) -> Option<Arc<dyn INodeInterface>> {
+    let task = match name {
+        "self" => current_task().downcast_arc::<UserTask>().ok()?,
+        _ => tid2user_task(name.parse().ok()?)?,
+    };
+    Some(Arc::new(ProcessDir(Arc::downgrade(&task))))
+}
+
+fn entries() -> Vec<DirEntry> {
+    let dir = |filename: String| DirEntry {
+        filename,
+        len: 0,
+        file_type: FileType::Directory,
+    };
+    processes()
+        .iter()
+        .map(|x| dir(x.task_id.to_string()))
+        .chain([dir(String::from("self"))])
+        .collect()
+}
+
+fn fill_stat(stat: &mut Stat, mode: StatMode, ino: u64) {
+    stat.dev = 0;
+    stat.ino = ino;
+    stat.mode = mode;
+    stat.nlink = 1;
+    stat.uid = 0;
+    stat.gid = 0;
+    stat.size = 0;
+    stat.blksize = 512;
+    stat.blocks = 0;
+    stat.rdev = 0;
+}
+
+/// The inode number of the directory of the task.
+fn process_ino(task: &Weak<UserTask>) -> VfsResult<u64> {
+    let task = task.upgrade().ok_or(Errno::ESRCH)?;
+    Ok(PROCESS_INO + task.task_id as u64 * 2)
+}
+
+pub struct ProcessDir(Weak<UserTask>);
+
+impl INodeInterface for ProcessDir {
+    fn lookup(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        match name {
+            "comm" => Ok(Arc::new(CommNode(self.0.clone()))),
+            _ => Err(Errno::ENOENT),
+        }
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        Ok(vec![DirEntry {
+            filename: String::from("comm"),
+            len: 0,
+            file_type: FileType::File,
+        }])
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        fill_stat(stat, StatMode::DIR, process_ino(&self.0)?);
+        Ok(())
+    }
+}
+
+/// The name of the task with a tailing newline. Only the same thread group
+/// or the one which can trace the task can rename it.
+pub struct CommNode(Weak<UserTask>);
+
+impl INodeInterface for CommNode {
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let task = self.0.upgrade().ok_or(Errno::ESRCH)?;
+        let info = task.comm() + "\n";
+        let info = info.as_bytes();
+        if offset >= info.len() {
+            return Ok(0);
+        }
+        let rlen = min(buffer.len(), info.len() - offset);
+        buffer[..rlen].copy_from_slice(&info[offset..offset + rlen]);
+        Ok(rlen)
+    }
+
+    fn writeat(&self, _offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        let task = self.0.upgrade().ok_or(Errno::ESRCH)?;
+        let curr = current_task()
+            .downcast_arc::<UserTask>()
+            .map_err(|_| Errno::EACCES)?;
+        let cred = curr.pcb.lock().cred.clone();
+        if !Arc::ptr_eq(&curr.pcb, &task.pcb) && !cred.may_access(&task.pcb.lock().cred) {
+            return Err(Errno::EACCES);
+        }
+        let name = String::from_utf8_lossy(buffer);
+        task.set_comm(name.trim_end_matches('\n'));
+        Ok(buffer.len())
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        fill_stat(stat, StatMode::FILE, process_ino(&self.0)? + 1);
+        Ok(())
+    }
+}
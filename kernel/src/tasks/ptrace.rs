@@ -0,0 +1,572 @@
+//! The process trace used by the debuggers and strace.
+//!
+//! The tracee stops at the signal-delivery-stops, the syscall stops and
+//! the event stops. The stop is reported to the tracer by wait4, then the
+//! tracee waits until the tracer resumes it by PTRACE_CONT or PTRACE_SYSCALL.
+//!
+//! PTRACE_SINGLESTEP writes temporary breakpoints at the instructions which
+//! may run next, they are restored at the breakpoint trap. It is emulated on
+//! riscv64 and loongarch64 only, the other threads sharing the memory may
+//! also hit the temporary breakpoints.
+
+use super::{
+    async_ops::WaitPtrace,
+    task::{MemSpace, UserTask},
+    MemType,
+};
+use crate::syscall::types::ptrace::{
+    PtraceOptions, PTRACE_EVENT_CLONE, PTRACE_EVENT_EXEC, PTRACE_EVENT_FORK, PTRACE_EVENT_VFORK,
+};
+use crate::syscall::types::task::CloneFlags;
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use cfg_if::cfg_if;
+use core::cmp::min;
+use devices::PAGE_SIZE;
+use executor::AsyncTask;
+use polyhal::{va, MappingFlags, VirtAddr};
+use polyhal_trap::trapframe::TrapFrame;
+use runtime::frame::frame_alloc;
+use signal::SignalFlags;
+use syscalls::Errno;
+
+cfg_if! {
+    if #[cfg(target_arch = "riscv64")] {
+        pub const ELF_NGREG: usize = 32;
+
+        /// pc, x1 - x31
+        pub fn elf_gregs(cx: &TrapFrame) -> [usize; ELF_NGREG] {
+            let mut regs = cx.x;
+            regs[0] = cx.sepc;
+            regs
+        }
+
+        pub fn set_elf_gregs(cx: &mut TrapFrame, regs: &[usize; ELF_NGREG]) {
+            cx.x[1..].copy_from_slice(&regs[1..]);
+            cx.sepc = regs[0];
+        }
+
+        /// The breakpoint trap skips the c.ebreak.
+        pub const BREAKPOINT_LEN: usize = 2;
+
+        use alloc::vec;
+
+        /// c.ebreak
+        const STEP_BREAK: &[u8] = &0x9002u16.to_le_bytes();
+
+        /// The jump target, or the next instruction and the branch target.
+        fn next_pcs(tracee: &UserTask, cx: &TrapFrame) -> Result<Vec<usize>, Errno> {
+            let pc = cx.sepc;
+            let reg = |x: usize| if x == 0 { 0 } else { cx.x[x] };
+            let mut bytes = [0u8; 4];
+            access_tracee(tracee, pc, &mut bytes[..2], false)?;
+            // the compressed instructions don't end with 0b11.
+            if bytes[0] & 0b11 != 0b11 {
+                let inst = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+                let field = |hi: usize, lo: usize| (inst >> lo) & ((1 << (hi - lo + 1)) - 1);
+                return Ok(match (inst & 0b11, field(15, 13)) {
+                    // c.j
+                    (0b01, 0b101) => {
+                        let imm = field(12, 12) << 11
+                            | field(11, 11) << 4
+                            | field(10, 9) << 8
+                            | field(8, 8) << 10
+                            | field(7, 7) << 6
+                            | field(6, 6) << 7
+                            | field(5, 3) << 1
+                            | field(2, 2) << 5;
+                        vec![pc.wrapping_add(sign_extend(imm, 12))]
+                    }
+                    // c.beqz, c.bnez
+                    (0b01, 0b110 | 0b111) => {
+                        let imm = field(12, 12) << 8
+                            | field(11, 10) << 3
+                            | field(6, 5) << 6
+                            | field(4, 3) << 1
+                            | field(2, 2) << 5;
+                        vec![pc + 2, pc.wrapping_add(sign_extend(imm, 9))]
+                    }
+                    // c.jr, c.jalr
+                    (0b10, 0b100) if field(11, 7) != 0 && field(6, 2) == 0 => {
+                        vec![reg(field(11, 7))]
+                    }
+                    _ => vec![pc + 2],
+                });
+            }
+            access_tracee(tracee, pc + 2, &mut bytes[2..], false)?;
+            let inst = u32::from_le_bytes(bytes) as usize;
+            let field = |hi: usize, lo: usize| (inst >> lo) & ((1 << (hi - lo + 1)) - 1);
+            Ok(match inst & 0x7f {
+                // jal
+                0x6f => {
+                    let imm = field(31, 31) << 20
+                        | field(19, 12) << 12
+                        | field(20, 20) << 11
+                        | field(30, 21) << 1;
+                    vec![pc.wrapping_add(sign_extend(imm, 21))]
+                }
+                // jalr
+                0x67 => vec![reg(field(19, 15)).wrapping_add(sign_extend(field(31, 20), 12)) & !1],
+                // beq, bne, blt, bge, bltu, bgeu
+                0x63 => {
+                    let imm = field(31, 31) << 12
+                        | field(7, 7) << 11
+                        | field(30, 25) << 5
+                        | field(11, 8) << 1;
+                    vec![pc + 4, pc.wrapping_add(sign_extend(imm, 13))]
+                }
+                _ => vec![pc + 4],
+            })
+        }
+    } else if #[cfg(target_arch = "aarch64")] {
+        pub const ELF_NGREG: usize = 34;
+
+        /// x0 - x30, sp, pc, pstate
+        pub fn elf_gregs(cx: &TrapFrame) -> [usize; ELF_NGREG] {
+            let mut regs = [0; ELF_NGREG];
+            regs[..31].copy_from_slice(&cx.regs);
+            regs[31] = cx.sp;
+            regs[32] = cx.elr;
+            regs[33] = cx.spsr;
+            regs
+        }
+
+        /// Only the condition flags of pstate can be changed.
+        pub fn set_elf_gregs(cx: &mut TrapFrame, regs: &[usize; ELF_NGREG]) {
+            const NZCV: usize = 0xf000_0000;
+            cx.regs.copy_from_slice(&regs[..31]);
+            cx.sp = regs[31];
+            cx.elr = regs[32];
+            cx.spsr = (cx.spsr & !NZCV) | (regs[33] & NZCV);
+        }
+
+        pub const BREAKPOINT_LEN: usize = 4;
+
+        const STEP_BREAK: &[u8] = &[];
+
+        /// The trap layer can't report the step traps.
+        fn next_pcs(_tracee: &UserTask, _cx: &TrapFrame) -> Result<Vec<usize>, Errno> {
+            Err(Errno::EIO)
+        }
+    } else if #[cfg(target_arch = "x86_64")] {
+        pub const ELF_NGREG: usize = 27;
+
+        /// The order of `struct user_regs_struct`, the segment registers
+        /// except cs and ss are not saved.
+        pub fn elf_gregs(cx: &TrapFrame) -> [usize; ELF_NGREG] {
+            [
+                cx.r15, cx.r14, cx.r13, cx.r12, cx.rbp, cx.rbx, cx.r11, cx.r10, cx.r9, cx.r8,
+                cx.rax, cx.rcx, cx.rdx, cx.rsi, cx.rdi, usize::MAX, cx.rip, cx.cs, cx.rflags,
+                cx.rsp, cx.ss, cx.fs_base, cx.gs_base, 0, 0, 0, 0,
+            ]
+        }
+
+        /// The segment registers and orig_rax can't be changed, and the
+        /// trap flag is kept because the debug trap isn't handled.
+        pub fn set_elf_gregs(cx: &mut TrapFrame, regs: &[usize; ELF_NGREG]) {
+            const FLAG_MASK: usize = 0x54cd5;
+            [
+                cx.r15, cx.r14, cx.r13, cx.r12, cx.rbp, cx.rbx, cx.r11, cx.r10, cx.r9, cx.r8,
+                cx.rax, cx.rcx, cx.rdx, cx.rsi, cx.rdi,
+            ] = <[usize; 15]>::try_from(&regs[..15]).unwrap();
+            cx.rip = regs[16];
+            cx.rflags = (cx.rflags & !FLAG_MASK) | (regs[18] & FLAG_MASK);
+            cx.rsp = regs[19];
+            cx.fs_base = regs[21];
+            cx.gs_base = regs[22];
+        }
+
+        /// The rip is after the int3 when SIGTRAP is sent.
+        pub const BREAKPOINT_LEN: usize = 0;
+
+        const STEP_BREAK: &[u8] = &[];
+
+        /// The debug trap isn't handled.
+        fn next_pcs(_tracee: &UserTask, _cx: &TrapFrame) -> Result<Vec<usize>, Errno> {
+            Err(Errno::EIO)
+        }
+    } else if #[cfg(target_arch = "loongarch64")] {
+        pub const ELF_NGREG: usize = 45;
+
+        /// r0 - r31, orig_a0, era, badv, reserved
+        pub fn elf_gregs(cx: &TrapFrame) -> [usize; ELF_NGREG] {
+            let mut regs = [0; ELF_NGREG];
+            regs[..32].copy_from_slice(&cx.regs);
+            regs[32] = cx.regs[4];
+            regs[33] = cx.era;
+            regs
+        }
+
+        pub fn set_elf_gregs(cx: &mut TrapFrame, regs: &[usize; ELF_NGREG]) {
+            cx.regs[1..].copy_from_slice(&regs[1..32]);
+            cx.era = regs[33];
+        }
+
+        pub const BREAKPOINT_LEN: usize = 4;
+
+        use alloc::vec;
+
+        /// break 0
+        const STEP_BREAK: &[u8] = &0x002a_0000u32.to_le_bytes();
+
+        /// The jump target, or the next instruction and the branch target.
+        fn next_pcs(tracee: &UserTask, cx: &TrapFrame) -> Result<Vec<usize>, Errno> {
+            let pc = cx.era;
+            let mut bytes = [0u8; 4];
+            access_tracee(tracee, pc, &mut bytes, false)?;
+            let inst = u32::from_le_bytes(bytes) as usize;
+            let field = |hi: usize, lo: usize| (inst >> lo) & ((1 << (hi - lo + 1)) - 1);
+            let reg = |x: usize| if x == 0 { 0 } else { cx.regs[x] };
+            Ok(match field(31, 26) {
+                // beqz, bnez, bceqz, bcnez
+                0x10..=0x12 => {
+                    let offs = field(4, 0) << 16 | field(25, 10);
+                    vec![pc + 4, pc.wrapping_add(sign_extend(offs << 2, 23))]
+                }
+                // jirl
+                0x13 => vec![reg(field(9, 5)).wrapping_add(sign_extend(field(25, 10) << 2, 18))],
+                // b, bl
+                0x14 | 0x15 => {
+                    let offs = field(9, 0) << 16 | field(25, 10);
+                    vec![pc.wrapping_add(sign_extend(offs << 2, 28))]
+                }
+                // beq, bne, blt, bge, bltu, bgeu
+                0x16..=0x1b => vec![pc + 4, pc.wrapping_add(sign_extend(field(25, 10) << 2, 18))],
+                _ => vec![pc + 4],
+            })
+        }
+    }
+}
+
+/// The state in which the tracee is stopped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PtraceStop {
+    /// The signal-delivery-stop, the tracer decides the delivered signal.
+    Signal(usize),
+    /// The syscall-enter-stop and the syscall-exit-stop of the syscall number.
+    Syscall(usize),
+    Event(usize),
+}
+
+pub struct Ptrace {
+    pub tracer: Weak<UserTask>,
+    pub options: PtraceOptions,
+    pub stop: Option<PtraceStop>,
+    /// The stop is reported by wait4.
+    pub reported: bool,
+    /// Stop at the syscalls, it is set by PTRACE_SYSCALL.
+    pub trace_syscall: bool,
+    /// The signal delivered after the tracee is resumed, 0 if it is suppressed.
+    pub signal: usize,
+    pub event_msg: usize,
+    pub detached: bool,
+    /// The addresses and the instructions replaced by PTRACE_SINGLESTEP.
+    pub step: Vec<(usize, [u8; 4])>,
+}
+
+impl Ptrace {
+    pub fn new(tracer: &Arc<UserTask>, options: PtraceOptions) -> Self {
+        Self {
+            tracer: Arc::downgrade(tracer),
+            options,
+            stop: None,
+            reported: false,
+            trace_syscall: false,
+            signal: 0,
+            event_msg: 0,
+            detached: false,
+            step: Vec::new(),
+        }
+    }
+
+    /// The status reported by wait4 if the tracee is stopped.
+    pub fn wait_status(&self) -> Option<i32> {
+        let sigtrap = SignalFlags::SIGTRAP.num() as i32;
+        let signal = match self.stop? {
+            PtraceStop::Signal(signal) => signal as i32,
+            PtraceStop::Syscall(_) if self.options.contains(PtraceOptions::TRACESYSGOOD) => {
+                sigtrap | 0x80
+            }
+            PtraceStop::Syscall(_) => sigtrap,
+            PtraceStop::Event(event) => sigtrap | (event as i32) << 8,
+        };
+        Some(signal << 8 | 0x7f)
+    }
+
+    /// The tracer is gone if it exited without detaching.
+    pub fn tracer_alive(tracer: &Weak<UserTask>) -> bool {
+        tracer.upgrade().is_some_and(|x| x.exit_code().is_none())
+    }
+}
+
+impl UserTask {
+    #[inline]
+    pub fn is_traced(&self) -> bool {
+        self.tcb.read().ptrace.is_some()
+    }
+
+    #[inline]
+    pub fn ptrace_options(&self) -> PtraceOptions {
+        self.tcb
+            .read()
+            .ptrace
+            .as_ref()
+            .map_or(PtraceOptions::empty(), |x| x.options)
+    }
+
+    #[inline]
+    pub fn ptrace_syscall(&self) -> bool {
+        self.tcb
+            .read()
+            .ptrace
+            .as_ref()
+            .is_some_and(|x| x.trace_syscall)
+    }
+
+    /// Stop the tracee and notify the tracer. It returns the signal given by
+    /// the tracer when the tracee is resumed, 0 if the signal is suppressed.
+    pub async fn ptrace_stop(self: &Arc<Self>, stop: PtraceStop) -> usize {
+        let signal = match stop {
+            PtraceStop::Signal(signal) => signal,
+            _ => 0,
+        };
+        // the syscall stop and the signal stop can't be taken at the same time.
+        WaitPtrace(self.clone()).await;
+        if self.exit_code().is_some() {
+            return signal;
+        }
+        let mut tcb = self.tcb.write();
+        let Some(trace) = tcb.ptrace.as_mut() else {
+            return signal;
+        };
+        trace.stop = Some(stop);
+        trace.reported = false;
+        trace.signal = signal;
+        let tracer = trace.tracer.upgrade();
+        drop(tcb);
+        if let Some(tracer) = tracer {
+            tracer.tcb.write().signal.add_signal(SignalFlags::SIGCHLD);
+        }
+
+        WaitPtrace(self.clone()).await;
+        let mut tcb = self.tcb.write();
+        let Some(trace) = tcb.ptrace.as_mut() else {
+            return signal;
+        };
+        trace.stop = None;
+        let signal = trace.signal;
+        if trace.detached || !Ptrace::tracer_alive(&trace.tracer) {
+            tcb.ptrace = None;
+        }
+        signal
+    }
+
+    /// The event stop if the tracer sets the option of the event.
+    pub async fn ptrace_event(self: &Arc<Self>, option: PtraceOptions, event: usize, msg: usize) {
+        if !self.ptrace_options().contains(option) {
+            return;
+        }
+        if let Some(trace) = self.tcb.write().ptrace.as_mut() {
+            trace.event_msg = msg;
+        }
+        self.ptrace_stop(PtraceStop::Event(event)).await;
+    }
+
+    /// The traced process gets SIGTRAP after exec unless the tracer
+    /// wants the exec event.
+    pub async fn ptrace_exec(self: &Arc<Self>) {
+        match self.ptrace_options().contains(PtraceOptions::TRACEEXEC) {
+            true => {
+                let pid = self.process_id;
+                self.ptrace_event(PtraceOptions::TRACEEXEC, PTRACE_EVENT_EXEC, pid)
+                    .await
+            }
+            false if self.is_traced() => self.send_signal(SignalFlags::SIGTRAP),
+            false => {}
+        }
+    }
+
+    /// The new child is traced by the tracer of the parent if the tracer
+    /// wants the fork, vfork or clone events or CLONE_PTRACE is set.
+    /// The child starts with SIGSTOP.
+    /// It returns the event and the option.
+    pub fn ptrace_clone(
+        &self,
+        child: &Arc<UserTask>,
+        flags: &CloneFlags,
+    ) -> Option<(usize, PtraceOptions)> {
+        let (event, option) = if flags.contains(CloneFlags::CLONE_VFORK) {
+            (PTRACE_EVENT_VFORK, PtraceOptions::TRACEVFORK)
+        } else if flags.bits() & 0xff == SignalFlags::SIGCHLD.num() {
+            (PTRACE_EVENT_FORK, PtraceOptions::TRACEFORK)
+        } else {
+            (PTRACE_EVENT_CLONE, PtraceOptions::TRACECLONE)
+        };
+        if flags.contains(CloneFlags::CLONE_UNTRACED) {
+            return None;
+        }
+        let tcb = self.tcb.read();
+        let trace = tcb.ptrace.as_ref()?;
+        if !trace.options.contains(option) && !flags.contains(CloneFlags::CLONE_PTRACE) {
+            return None;
+        }
+        let tracer = trace.tracer.upgrade()?;
+        let options = trace.options;
+        drop(tcb);
+        child.tcb.write().ptrace = Some(Ptrace::new(&tracer, options));
+        child.tcb.write().signal.add_signal(SignalFlags::SIGSTOP);
+        tracer.pcb.lock().tracees.push(Arc::downgrade(child));
+        Some((event, option))
+    }
+
+    /// Notify the tracer which isn't the parent that the tracee exited.
+    pub fn ptrace_exit(&self) {
+        let tracer = self.tcb.read().ptrace.as_ref().map(|x| x.tracer.clone());
+        let Some(tracer) = tracer.and_then(|x| x.upgrade()) else {
+            return;
+        };
+        let is_parent = self
+            .parent
+            .read()
+            .upgrade()
+            .is_some_and(|x| Arc::ptr_eq(&x, &tracer));
+        if !is_parent {
+            tracer.tcb.write().signal.add_signal(SignalFlags::SIGCHLD);
+        }
+    }
+
+    /// The tracees are detached when the tracer exits,
+    /// they are killed if PTRACE_O_EXITKILL is set.
+    pub fn release_tracees(&self) {
+        let tracees = core::mem::take(&mut self.pcb.lock().tracees);
+        for tracee in tracees.iter().filter_map(Weak::upgrade) {
+            tracee.ptrace_step_done();
+            let trace = tracee.tcb.write().ptrace.take();
+            if trace.is_some_and(|x| x.options.contains(PtraceOptions::EXITKILL)) {
+                tracee.send_signal(SignalFlags::SIGKILL);
+            }
+        }
+    }
+
+    /// Write the temporary breakpoints for PTRACE_SINGLESTEP.
+    pub fn ptrace_step(&self) -> Result<(), Errno> {
+        let cx = self.tcb.read().cx.clone();
+        let mut pcs = next_pcs(self, &cx)?;
+        pcs.sort_unstable();
+        pcs.dedup();
+        let mut step = Vec::new();
+        for pc in pcs {
+            let mut inst = [0u8; 4];
+            let written = access_tracee(self, pc, &mut inst[..STEP_BREAK.len()], false)
+                .and_then(|_| access_tracee(self, pc, &mut STEP_BREAK.to_vec(), true));
+            if let Err(err) = written {
+                restore_insts(self, step);
+                return Err(err);
+            }
+            step.push((pc, inst));
+        }
+        match self.tcb.write().ptrace.as_mut() {
+            Some(trace) => trace.step = step,
+            None => restore_insts(self, step),
+        }
+        Ok(())
+    }
+
+    /// Restore the instructions replaced by PTRACE_SINGLESTEP,
+    /// it returns true if the tracee was stepping.
+    pub fn ptrace_step_done(&self) -> bool {
+        let step = self
+            .tcb
+            .write()
+            .ptrace
+            .as_mut()
+            .map(|x| core::mem::take(&mut x.step))
+            .unwrap_or_default();
+        let stepped = !step.is_empty();
+        restore_insts(self, step);
+        stepped
+    }
+
+    /// The registers of the tracee in the order of NT_PRSTATUS.
+    pub fn ptrace_regs(&self) -> [usize; ELF_NGREG] {
+        let tcb = self.tcb.read();
+        #[allow(unused_mut)]
+        let mut regs = elf_gregs(&tcb.cx);
+        // orig_rax is the syscall number at the syscall stops.
+        #[cfg(target_arch = "x86_64")]
+        if let Some(PtraceStop::Syscall(sysno)) = tcb.ptrace.as_ref().and_then(|x| x.stop) {
+            regs[15] = sysno;
+        }
+        regs
+    }
+}
+
+/// Read or write the memory of the tracee. The page shared by copy on write
+/// is copied before it is written, so the other processes don't see it.
+pub fn access_tracee(
+    tracee: &UserTask,
+    addr: usize,
+    buf: &mut [u8],
+    write: bool,
+) -> Result<(), Errno> {
+    let mm = tracee.mm();
+    let mut mm = mm.lock();
+    let mut done = 0;
+    while done < buf.len() {
+        let vaddr = va!(addr + done);
+        let len = min(buf.len() - done, PAGE_SIZE - vaddr.raw() % PAGE_SIZE);
+        if write {
+            private_page(&mut mm, vaddr)?;
+        }
+        let (paddr, _) = mm.page_table.translate(vaddr).ok_or(Errno::EIO)?;
+        let mem = paddr.slice_mut_with_len::<u8>(len);
+        match write {
+            true => mem.copy_from_slice(&buf[done..done + len]),
+            false => buf[done..done + len].copy_from_slice(mem),
+        }
+        done += len;
+    }
+    Ok(())
+}
+
+/// Write back the instructions replaced by the temporary breakpoints.
+fn restore_insts(tracee: &UserTask, step: Vec<(usize, [u8; 4])>) {
+    for (pc, mut inst) in step {
+        let _ = access_tracee(tracee, pc, &mut inst[..STEP_BREAK.len()], true);
+    }
+}
+
+/// Extend the sign bit of the immediate which has `bits` bits.
+#[cfg(any(target_arch = "riscv64", target_arch = "loongarch64"))]
+fn sign_extend(imm: usize, bits: u32) -> usize {
+    let shift = usize::BITS - bits;
+    (((imm << shift) as isize) >> shift) as usize
+}
+
+/// The vdso is shared by all the processes, it can't be written.
+fn private_page(mm: &mut MemSpace, vaddr: VirtAddr) -> Result<(), Errno> {
+    let Some(area) = mm.memset.iter_mut().find(|x| x.contains(vaddr.raw())) else {
+        return Ok(());
+    };
+    match area.mtype {
+        MemType::Vdso => return Err(Errno::EIO),
+        MemType::Shared | MemType::ShareFile => return Ok(()),
+        _ => {}
+    }
+    let Some(track) = area.mtrackers.iter_mut().find(|x| x.vaddr == vaddr.floor()) else {
+        return Ok(());
+    };
+    if Arc::strong_count(&track.tracker) == 1 {
+        return Ok(());
+    }
+    let dst = frame_alloc().ok_or(Errno::ENOMEM)?;
+    dst.0
+        .slice_mut_with_len::<u8>(PAGE_SIZE)
+        .copy_from_slice(track.tracker.0.slice_with_len(PAGE_SIZE));
+    track.tracker = Arc::new(dst);
+    let paddr = track.tracker.0;
+    mm.map(paddr, vaddr.floor(), MappingFlags::URWX);
+    Ok(())
+}
@@ -1,4 +1,10 @@
+use super::current_user_task;
+use crate::syscall::types::signal::SignalfdSiginfo;
+use core::mem::size_of;
 use signal::{SigProcMask, SignalFlags};
+use sync::Mutex;
+use syscalls::Errno;
+use vfscore::{INodeInterface, PollEvent, VfsResult};
 
 #[derive(Debug, Clone)]
 pub struct SignalList {
@@ -42,3 +48,52 @@ impl SignalList {
         }
     }
 }
+
+/// The signalfd, reading it takes the pending signals in the mask of
+/// the reader, the signals are still delivered if they aren't blocked.
+pub struct SignalFd {
+    pub mask: Mutex<usize>,
+}
+
+impl SignalFd {
+    pub fn new(mask: usize) -> Self {
+        Self {
+            mask: Mutex::new(mask),
+        }
+    }
+}
+
+impl INodeInterface for SignalFd {
+    fn readat(&self, _offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        const SSI_SIZE: usize = size_of::<SignalfdSiginfo>();
+        if buffer.len() < SSI_SIZE {
+            return Err(Errno::EINVAL);
+        }
+        let task = current_user_task();
+        let mask = *self.mask.lock();
+        let mut rlen = 0;
+        while rlen + SSI_SIZE <= buffer.len() {
+            let Some(info) = task.take_signal(mask) else {
+                break;
+            };
+            let ssi = SignalfdSiginfo::from(info);
+            let bytes =
+                unsafe { core::slice::from_raw_parts(&ssi as *const _ as *const u8, SSI_SIZE) };
+            buffer[rlen..rlen + SSI_SIZE].copy_from_slice(bytes);
+            rlen += SSI_SIZE;
+        }
+        match rlen {
+            0 => Err(Errno::EWOULDBLOCK),
+            _ => Ok(rlen),
+        }
+    }
+
+    fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
+        let mut res = PollEvent::NONE;
+        if events.contains(PollEvent::POLLIN) && current_user_task().has_pending(*self.mask.lock())
+        {
+            res |= PollEvent::POLLIN;
+        }
+        Ok(res)
+    }
+}
@@ -1,54 +1,194 @@
 use super::{
+    async_ops::WaitEvent,
+    cred::{Access, Credentials},
     filetable::{rlimits_new, FileTable},
+    init_proc,
     memset::{MemSet, MemType},
+    ptrace::Ptrace,
     shm::MapedSharedMemory,
     SignalList,
 };
 use crate::{
     syscall::types::{
-        fd::AT_CWD,
-        time::{ProcessTimer, TMS},
+        fd::{
+            RobustListHead, AT_CWD, FUTEX_BITSET_MATCH_ANY, FUTEX_OWNER_DIED, FUTEX_TID_MASK,
+            FUTEX_WAITERS, ROBUST_LIST_LIMIT,
+        },
+        signal::{SigInfo, SignalStack, SA_NOCLDSTOP, SI_KERNEL},
+        sys::{Rlimit, RLIMIT_NOFILE, RLIMIT_SIGPENDING, RLIM_NLIMITS, TASK_COMM_LEN},
+        task::CloneFlags,
+        time::{PosixTimer, ProcessTimer, CLOCK_THREAD_CPUTIME_ID, TMS},
     },
     tasks::{
-        futex_wake,
+        futex_wake, futex_wake_bitset,
         memset::{MapTrack, MemArea},
     },
+    utils::time::clock_nsec,
 };
 use alloc::{
     collections::BTreeMap,
+    string::String,
     sync::{Arc, Weak},
     vec::Vec,
 };
-use core::{cmp::max, mem::size_of};
+use core::{
+    cmp::{max, min},
+    mem::size_of,
+    sync::atomic::{AtomicU32, Ordering},
+};
 use devices::PAGE_SIZE;
 use executor::{release_task, task::TaskType, task_id_alloc, AsyncTask, TaskId};
 use fs::{file::File, pathbuf::PathBuf, INodeInterface};
 use log::debug;
-use polyhal::{va, MappingFlags, MappingSize, PageTableWrapper, PhysAddr, VirtAddr};
+use polyhal::{va, MappingFlags, MappingSize, PageTableWrapper, PhysAddr, Time, VirtAddr};
 use polyhal_trap::trapframe::{TrapFrame, TrapFrameArgs};
 use runtime::frame::{alignup, frame_alloc_much};
-use signal::{SigAction, SigProcMask, SignalFlags, REAL_TIME_SIGNAL_NUM};
-use sync::{Mutex, MutexGuard, RwLock};
+use signal::{SigAction, SigDefault, SigProcMask, SignalFlags};
+use sync::{Lazy, Mutex, MutexGuard, RwLock};
 use syscalls::Errno;
-use vfscore::{OpenFlags, VfsResult};
+use vfscore::{OpenFlags, Stat, VfsResult};
 
-pub type FutexTable = BTreeMap<usize, Vec<usize>>;
+/// The waiters of the futex words. The private futexes are keyed by the
+/// user address in the table of the memory space, the shared futexes are
+/// keyed by [FutexKey] in [SHARED_FUTEX_TABLE].
+pub type FutexTable<K = usize> = BTreeMap<K, Vec<FutexWaiter>>;
 
-pub struct ProcessControlBlock {
+/// The key of the shared futex, the word in the shared memory is keyed by
+/// the frame, the word in the shared file mapping is keyed by the inode
+/// and the offset because the mappings have their own frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FutexKey {
+    Frame(usize),
+    /// the shared file mapping, (device, inode number, offset in the file).
+    File(u64, u64, usize),
+    /// the private memory, (memory space, user address).
+    Private(usize, usize),
+}
+
+pub static SHARED_FUTEX_TABLE: Lazy<Arc<Mutex<FutexTable<FutexKey>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(BTreeMap::new())));
+
+/// The waiter is woken only if the bitset of the waker matches.
+#[derive(Debug, Clone, Copy)]
+pub struct FutexWaiter {
+    pub tid: usize,
+    pub bitset: u32,
+}
+
+/// The job control state change which isn't reported by wait4.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JobReport {
+    Stopped(usize),
+    Continued,
+}
+
+/// The address space, it is shared by CLONE_VM.
+pub struct MemSpace {
+    pub page_table: Arc<PageTableWrapper>,
     pub memset: MemSet,
-    pub fd_table: FileTable,
-    pub curr_dir: Arc<File>,
     pub heap: usize,
+    /// The start of the heap, the heap size is limited by RLIMIT_DATA.
+    pub brk_start: usize,
+    pub shms: Vec<MapedSharedMemory>,
+    pub futex_table: Arc<Mutex<FutexTable>>,
+}
+
+impl MemSpace {
+    pub fn new() -> Self {
+        Self {
+            page_table: Arc::new(PageTableWrapper::alloc()),
+            memset: MemSet::new(vec![]),
+            heap: 0,
+            brk_start: 0,
+            shms: vec![],
+            futex_table: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// The size of the address space, the stack counts the mapped pages
+    /// only because it grows on demand.
+    pub fn mapped_size(&self) -> usize {
+        let areas: usize = self
+            .memset
+            .iter()
+            .map(|x| match x.mtype {
+                MemType::Stack => x.mtrackers.len() * PAGE_SIZE,
+                _ => x.len,
+            })
+            .sum();
+        areas + self.shms.iter().map(|x| x.size).sum::<usize>()
+    }
+
+    #[inline]
+    pub fn map(&self, paddr: PhysAddr, vaddr: VirtAddr, flags: MappingFlags) {
+        assert_eq!(paddr.raw() % PAGE_SIZE, 0);
+        assert_eq!(vaddr.raw() % PAGE_SIZE, 0);
+        // self.page_table.map(ppn, vpn, flags, 3);
+        self.page_table
+            .map_page(vaddr, paddr, flags, MappingSize::Page4KB);
+    }
+
+    pub fn sub_area(&mut self, start: usize, end: usize) {
+        self.memset.sub_area(start, end, &self.page_table);
+    }
+}
+
+/// The filesystem context, it is shared by CLONE_FS.
+#[derive(Clone)]
+pub struct FsContext {
+    pub curr_dir: Arc<File>,
+    /// The root directory changed by chroot.
+    pub root: PathBuf,
+    pub umask: u32,
+}
+
+pub type SigHandlers = [SigAction; 65];
+
+/// The parts shared by the clone flags are placed in the Arc,
+/// the threads share all of them.
+pub struct ProcessControlBlock {
+    pub mm: Arc<Mutex<MemSpace>>,
+    pub files: Arc<Mutex<FileTable>>,
+    pub fs: Arc<Mutex<FsContext>>,
+    pub sighand: Arc<Mutex<SigHandlers>>,
     pub entry: usize,
     pub children: Vec<Arc<UserTask>>,
     pub tms: TMS,
-    pub rlimits: Vec<usize>,
-    pub sigaction: [SigAction; 65],
-    pub futex_table: Arc<Mutex<FutexTable>>,
-    pub shms: Vec<MapedSharedMemory>,
+    pub rlimits: [Rlimit; RLIM_NLIMITS],
     pub timer: [ProcessTimer; 3],
+    /// The POSIX timers, they are deleted by exec.
+    pub posix_timers: BTreeMap<usize, PosixTimer>,
     pub threads: Vec<Weak<UserTask>>,
     pub exit_code: Option<usize>,
+    /// The signal which terminated the process, and whether the core is dumped.
+    pub term_signal: Option<usize>,
+    pub core_dumped: bool,
+    /// The auxv pushed at the stack, used by the core dump.
+    pub auxv: Vec<(usize, usize)>,
+    /// process group id and session id.
+    pub pgid: usize,
+    pub sid: usize,
+    /// The signal which stopped the process, None if it is running.
+    pub stop_signal: Option<usize>,
+    pub job_report: Option<JobReport>,
+    pub cred: Credentials,
+    /// The vfork parent is suspended until the child execs or exits.
+    pub vfork_done: bool,
+    /// The tasks traced by this process.
+    pub tracees: Vec<Weak<UserTask>>,
+    /// The orphans of the descendants are adopted by this process
+    /// instead of init, it is set by PR_SET_CHILD_SUBREAPER.
+    pub child_subreaper: bool,
+    /// The signal sent when the parent exits, set by PR_SET_PDEATHSIG.
+    pub pdeath_signal: usize,
+    /// The set-user-ID bits are ignored by exec if it is set.
+    pub no_new_privs: bool,
+    /// The core is dumped only if it is dumpable.
+    pub dumpable: bool,
+    /// A thread is dumping the core, the others are parked.
+    pub core_dumping: bool,
+    /// The process has executed a file, its parent can't set its pgid.
+    pub did_exec: bool,
 }
 
 pub struct ThreadControlBlock {
@@ -57,16 +197,51 @@ pub struct ThreadControlBlock {
     pub clear_child_tid: usize,
     pub set_child_tid: usize,
     pub signal: SignalList,
-    pub signal_queue: [usize; REAL_TIME_SIGNAL_NUM], // a queue for real time signals
+    /// The siginfo of the pending signals, the real time signals are queued.
+    pub sigqueue: Vec<SigInfo>,
     pub exit_signal: u8,
     pub thread_exit_code: Option<u32>,
+    pub ptrace: Option<Ptrace>,
+    /// The name of the task, it is the basename of the executable.
+    pub comm: String,
+    /// The head of the robust futex list, set by set_robust_list.
+    pub robust_list: usize,
+    /// The alternate signal stack, set by sigaltstack.
+    pub sigaltstack: SignalStack,
+    /// The thread doesn't return to the user while the core is dumped.
+    pub core_parked: bool,
+    /// The CPU time of the thread, the times of the children aren't used.
+    pub tms: TMS,
+}
+
+impl ThreadControlBlock {
+    /// Take the siginfo of the pending signal, the signal is still pending
+    /// if there are more queued real time signals.
+    /// The signal added without the siginfo is sent by the kernel.
+    pub fn take_siginfo(&mut self, signal: SignalFlags) -> SigInfo {
+        let signo = signal.num() as i32;
+        let info = match self.sigqueue.iter().position(|x| x.signo == signo) {
+            Some(index) => self.sigqueue.remove(index),
+            None => SigInfo::new(signo, SI_KERNEL),
+        };
+        if !self.sigqueue.iter().any(|x| x.signo == signo) {
+            self.signal.remove_signal(signal);
+        }
+        info
+    }
+
+    /// Discard the pending signals and their siginfo.
+    pub fn discard_signal(&mut self, signal: SignalFlags) {
+        self.sigqueue
+            .retain(|x| !signal.contains(SignalFlags::from_num(x.signo as _)));
+        self.signal.remove_signal(signal);
+    }
 }
 
 #[allow(dead_code)]
 pub struct UserTask {
     pub task_id: TaskId,
     pub process_id: TaskId,
-    pub page_table: Arc<PageTableWrapper>,
     pub pcb: Arc<Mutex<ProcessControlBlock>>,
     pub parent: RwLock<Weak<UserTask>>,
     pub tcb: RwLock<ThreadControlBlock>,
@@ -83,28 +258,45 @@ impl UserTask {
 impl UserTask {
     pub fn new(parent: Weak<UserTask>, work_dir: PathBuf) -> Arc<Self> {
         let task_id = task_id_alloc();
-        // initialize memset
-        let memset = MemSet::new(vec![]);
 
         let curr_dir = File::open(work_dir, OpenFlags::O_DIRECTORY)
             .map(Arc::new)
             .expect("dont' have the home dir");
+        let fs = FsContext {
+            curr_dir,
+            root: PathBuf::new(),
+            umask: 0o022,
+        };
 
         let inner = ProcessControlBlock {
-            memset,
-            fd_table: FileTable::new(),
-            curr_dir,
-            heap: 0,
+            mm: Arc::new(Mutex::new(MemSpace::new())),
+            files: Arc::new(Mutex::new(FileTable::new())),
+            fs: Arc::new(Mutex::new(fs)),
+            sighand: Arc::new(Mutex::new([SigAction::new(); 65])),
             children: Vec::new(),
             entry: 0,
             tms: Default::default(),
             rlimits: rlimits_new(),
-            sigaction: [SigAction::new(); 65],
-            futex_table: Arc::new(Mutex::new(BTreeMap::new())),
-            shms: vec![],
             timer: [Default::default(); 3],
+            posix_timers: BTreeMap::new(),
             exit_code: None,
+            term_signal: None,
+            core_dumped: false,
             threads: Vec::new(),
+            auxv: Vec::new(),
+            pgid: task_id,
+            sid: task_id,
+            stop_signal: None,
+            job_report: None,
+            cred: Credentials::root(),
+            vfork_done: false,
+            tracees: Vec::new(),
+            child_subreaper: false,
+            pdeath_signal: 0,
+            no_new_privs: false,
+            dumpable: true,
+            core_dumping: false,
+            did_exec: false,
         };
 
         let tcb = RwLock::new(ThreadControlBlock {
@@ -113,13 +305,18 @@ impl UserTask {
             clear_child_tid: 0,
             set_child_tid: 0,
             signal: SignalList::new(),
-            signal_queue: [0; REAL_TIME_SIGNAL_NUM],
+            sigqueue: Vec::new(),
             exit_signal: 0,
             thread_exit_code: Option::None,
+            ptrace: None,
+            comm: String::new(),
+            robust_list: 0,
+            sigaltstack: SignalStack::disabled(),
+            core_parked: false,
+            tms: TMS::default(),
         });
 
         let task = Arc::new(Self {
-            page_table: Arc::new(PageTableWrapper::alloc()),
             task_id,
             process_id: task_id,
             parent: RwLock::new(parent),
@@ -134,13 +331,41 @@ impl UserTask {
         f(&mut self.pcb.lock())
     }
 
+    /// The shared parts, the pcb is unlocked before locking them.
+    #[inline]
+    pub fn mm(&self) -> Arc<Mutex<MemSpace>> {
+        self.pcb.lock().mm.clone()
+    }
+
+    #[inline]
+    pub fn files(&self) -> Arc<Mutex<FileTable>> {
+        self.pcb.lock().files.clone()
+    }
+
+    #[inline]
+    pub fn fs(&self) -> Arc<Mutex<FsContext>> {
+        self.pcb.lock().fs.clone()
+    }
+
+    #[inline]
+    pub fn sighand(&self) -> Arc<Mutex<SigHandlers>> {
+        self.pcb.lock().sighand.clone()
+    }
+
+    /// The soft limit of the resource.
+    #[inline]
+    pub fn rlimit(&self, resource: usize) -> usize {
+        self.pcb.lock().rlimits[resource].curr
+    }
+
+    #[inline]
+    pub fn page_table(&self) -> Arc<PageTableWrapper> {
+        self.mm().lock().page_table.clone()
+    }
+
     #[inline]
     pub fn map(&self, paddr: PhysAddr, vaddr: VirtAddr, flags: MappingFlags) {
-        assert_eq!(paddr.raw() % PAGE_SIZE, 0);
-        assert_eq!(vaddr.raw() % PAGE_SIZE, 0);
-        // self.page_table.map(ppn, vpn, flags, 3);
-        self.page_table
-            .map_page(vaddr, paddr, flags, MappingSize::Page4KB);
+        self.mm().lock().map(paddr, vaddr, flags);
     }
 
     #[inline]
@@ -190,7 +415,8 @@ impl UserTask {
                 .filter(|x| x.vaddr.raw() != 0)
                 .for_each(|x| self.map(x.tracker.0, x.vaddr, MappingFlags::URWX));
         }
-        let mut inner = self.pcb.lock();
+        let mm = self.mm();
+        let mut inner = mm.lock();
         let ppn = trackers[0].tracker.0;
         if mtype == MemType::Stack {
             let finded_area = inner.memset.iter_mut().find(|x| x.mtype == mtype);
@@ -226,58 +452,49 @@ impl UserTask {
     }
 
     pub fn sbrk(&self, addr: usize) -> usize {
-        let curr_page = self.pcb.lock().heap.div_ceil(PAGE_SIZE);
+        let curr_page = self.heap().div_ceil(PAGE_SIZE);
         let after_page = addr.div_ceil(PAGE_SIZE);
         // 如果需要申请内存
         (curr_page..after_page).for_each(|i| {
             self.frame_alloc(va!(i * PAGE_SIZE), MemType::CodeSection, 1);
         });
-        self.pcb.lock().heap = addr;
+        self.mm().lock().heap = addr;
         addr
     }
 
     pub fn heap(&self) -> usize {
-        self.pcb.lock().heap
+        self.mm().lock().heap
     }
 
     #[inline]
     pub fn thread_exit(&self, exit_code: usize) {
+        self.exit_robust_list();
         let mut tcb_writer = self.tcb.write();
         let uaddr = tcb_writer.clear_child_tid;
         if uaddr != 0 {
             debug!("write addr: {:#x}", uaddr);
             let addr = self
-                .page_table
+                .page_table()
                 .translate(VirtAddr::from(uaddr))
                 .expect("can't find a valid addr")
                 .0;
             unsafe {
                 addr.get_mut_ptr::<u32>().write(0);
             }
-            futex_wake(self.pcb.lock().futex_table.clone(), uaddr, 1);
+            self.futex_wake_any(uaddr, 1);
         }
         tcb_writer.thread_exit_code = Some(exit_code as u32);
         let exit_signal = tcb_writer.exit_signal;
         drop(tcb_writer);
+        self.ptrace_exit();
 
         // recycle memory resouces if the pcb just used by this thread
         if Arc::strong_count(&self.pcb) == 1 {
-            self.pcb.lock().memset.clear();
-            self.pcb.lock().fd_table.clear();
-            self.pcb.lock().children.clear();
+            self.release_tracees();
+            self.release_parts();
+            self.reparent_children();
             self.pcb.lock().exit_code = Some(exit_code);
-
-            if let Some(parent) = self.parent.read().upgrade() {
-                if exit_signal != 0 {
-                    parent
-                        .tcb
-                        .write()
-                        .signal
-                        .add_signal(SignalFlags::from_num(exit_signal as _));
-                } else {
-                    parent.tcb.write().signal.add_signal(SignalFlags::SIGCHLD);
-                }
-            }
+            self.exit_notify(exit_signal as usize);
         }
 
         // If this is not the main thread, Just exit immediately, don't store any resources.
@@ -290,49 +507,516 @@ impl UserTask {
         }
     }
 
+    /// The name of the task, it is shown in procfs and the logs.
+    pub fn comm(&self) -> String {
+        self.tcb.read().comm.clone()
+    }
+
+    /// The name is truncated to TASK_COMM_LEN - 1 bytes like linux.
+    pub fn set_comm(&self, name: &str) {
+        let mut len = min(name.len(), TASK_COMM_LEN - 1);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.tcb.write().comm = String::from(&name[..len]);
+    }
+
+    /// Set the credentials of the new program. The set-user-ID programs
+    /// are not dumpable and don't inherit the parent death signal.
+    pub fn set_exec_cred(&self, cred: Credentials) {
+        let mut pcb = self.pcb.lock();
+        let changed = cred.euid != pcb.cred.euid || cred.egid != pcb.cred.egid;
+        pcb.dumpable = !changed;
+        if changed {
+            pcb.pdeath_signal = 0;
+        }
+        pcb.cred = cred;
+    }
+
+    /// Drop the old image before loading the new program. The memory shared
+    /// with the vfork parent is left to it, a new address space is used.
+    /// The caught signals are reset to the default, the handlers are gone.
+    pub fn flush_exec(&self) {
+        let mut pcb = self.pcb.lock();
+        if Arc::strong_count(&pcb.mm) > 1 {
+            pcb.mm = Arc::new(Mutex::new(MemSpace::new()));
+        } else {
+            let mut mm = pcb.mm.lock();
+            mm.memset.clear();
+            mm.shms.clear();
+            mm.page_table.restore();
+        }
+        if Arc::strong_count(&pcb.files) > 1 {
+            let files = pcb.files.lock().clone();
+            pcb.files = Arc::new(Mutex::new(files));
+        }
+        let mut handlers = *pcb.sighand.lock();
+        handlers
+            .iter_mut()
+            .filter(|x| x.handler > 1)
+            .for_each(|x| *x = SigAction::new());
+        pcb.sighand = Arc::new(Mutex::new(handlers));
+        pcb.posix_timers.clear();
+        pcb.vfork_done = true;
+        pcb.did_exec = true;
+        drop(pcb);
+        let mut tcb = self.tcb.write();
+        tcb.robust_list = 0;
+        tcb.sigaltstack = SignalStack::disabled();
+        drop(tcb);
+        self.page_table().change();
+    }
+
+    /// The key of the futex word at the user address for the shared futex.
+    pub fn futex_key(&self, uaddr: usize) -> FutexKey {
+        let paddr = self.page_table().translate(VirtAddr::from(uaddr));
+        let mm = self.mm();
+        let mm_ref = mm.lock();
+        let area = mm_ref.memset.iter().find(|x| x.contains(uaddr));
+        if let Some(area) = area.filter(|x| x.mtype == MemType::ShareFile) {
+            // the inode is opened again by each mapping, it is keyed by the number.
+            let mut stat = Stat::default();
+            if let Some(Ok(_)) = area.file.as_ref().map(|x| x.stat(&mut stat)) {
+                return FutexKey::File(stat.dev, stat.ino, uaddr - area.start + area.offset);
+            }
+        }
+        let shared = area.is_some_and(|x| x.mtype == MemType::Shared)
+            || mm_ref
+                .shms
+                .iter()
+                .any(|x| (x.start..x.start + x.size).contains(&uaddr));
+        match paddr {
+            Some((paddr, _)) if shared => FutexKey::Frame(paddr.raw()),
+            _ => FutexKey::Private(Arc::as_ptr(&mm) as usize, uaddr),
+        }
+    }
+
+    /// Wake the waiters of the word, both the private and the shared ones.
+    /// It is used by the kernel, such as clearing the child tid.
+    pub fn futex_wake_any(&self, uaddr: usize, count: usize) {
+        let waked = futex_wake(self.mm().lock().futex_table.clone(), uaddr, count);
+        if waked < count {
+            let key = self.futex_key(uaddr);
+            futex_wake_bitset(
+                &mut SHARED_FUTEX_TABLE.lock(),
+                key,
+                count - waked,
+                FUTEX_BITSET_MATCH_ANY,
+            );
+        }
+    }
+
+    /// The pointer of the user address in the memory of the task, it is
+    /// None if the page isn't mapped.
+    fn user_ptr<T>(&self, uaddr: usize) -> Option<*mut T> {
+        self.page_table()
+            .translate(VirtAddr::from(uaddr))
+            .map(|(paddr, _)| paddr.get_mut_ptr::<T>())
+    }
+
+    /// Release the robust futexes held by the exiting thread like linux,
+    /// the word gets FUTEX_OWNER_DIED and one of the waiters is woken.
+    fn exit_robust_list(&self) {
+        let head_addr = self.tcb.read().robust_list;
+        if head_addr == 0 {
+            return;
+        }
+        let Some(head) = self.user_ptr::<RobustListHead>(head_addr) else {
+            return;
+        };
+        let head = unsafe { head.read() };
+        let futex_addr = |entry: usize| (entry & !1).wrapping_add_signed(head.futex_offset);
+        let mut entry = head.list;
+        for _ in 0..ROBUST_LIST_LIMIT {
+            // the list is circular, it ends at the head.
+            if entry & !1 == head_addr {
+                break;
+            }
+            let Some(next) = self.user_ptr::<usize>(entry & !1) else {
+                break;
+            };
+            // the pending one is handled at last.
+            if entry != head.list_op_pending {
+                self.futex_owner_died(futex_addr(entry));
+            }
+            entry = unsafe { next.read() };
+        }
+        if head.list_op_pending != 0 {
+            self.futex_owner_died(futex_addr(head.list_op_pending));
+        }
+    }
+
+    fn futex_owner_died(&self, uaddr: usize) {
+        let Some(ptr) = self.user_ptr::<u32>(uaddr) else {
+            return;
+        };
+        let word = unsafe { AtomicU32::from_ptr(ptr) };
+        let value = word.load(Ordering::SeqCst);
+        if value & FUTEX_TID_MASK != self.task_id as u32 {
+            return;
+        }
+        word.store((value & FUTEX_WAITERS) | FUTEX_OWNER_DIED, Ordering::SeqCst);
+        if value & FUTEX_WAITERS != 0 {
+            self.futex_wake_any(uaddr, 1);
+        }
+    }
+
+    /// Recycle the memory and the files of the exited process,
+    /// they are kept if the other processes share them.
+    fn release_parts(&self) {
+        let pcb = self.pcb.lock();
+        if Arc::strong_count(&pcb.mm) == 1 {
+            pcb.mm.lock().memset.clear();
+        }
+        if Arc::strong_count(&pcb.files) == 1 {
+            pcb.files.lock().clear();
+        }
+    }
+
+    /// Report the exit to the parent. The process is reaped at once if the
+    /// parent ignores SIGCHLD or sets SA_NOCLDWAIT, and the orphan which
+    /// has no parent reaps itself.
+    fn exit_notify(&self, exit_signal: usize) {
+        const SIG_IGN: usize = 1;
+        const SA_NOCLDWAIT: usize = 2;
+        let Some(parent) = self.parent.read().upgrade() else {
+            release_task(self.process_id);
+            return;
+        };
+        // no signal is sent for exit_signal 0, the waiters poll the zombie.
+        if exit_signal == 0 {
+            return;
+        }
+        let signal = SignalFlags::from_num(exit_signal);
+        let sigaction = parent.sighand().lock()[SignalFlags::SIGCHLD.num()];
+        if signal == SignalFlags::SIGCHLD
+            && (sigaction.handler == SIG_IGN || sigaction.flags & SA_NOCLDWAIT != 0)
+        {
+            let mut pcb = parent.pcb.lock();
+            let index = pcb
+                .children
+                .iter()
+                .position(|x| x.task_id == self.process_id);
+            let child = index.map(|x| pcb.children.remove(x));
+            drop(pcb);
+            child.inspect(|x| x.release());
+            if sigaction.handler == SIG_IGN {
+                return;
+            }
+        }
+        let mut info = self.child_info(&WaitEvent::Exited(self.wait_status()));
+        info.signo = exit_signal as _;
+        parent.send_process_siginfo(info);
+    }
+
+    /// The children are adopted by the nearest living subreaper in the
+    /// ancestors, or by the init process. The zombies are reported to the
+    /// new parent again, they are reaped if there is no one to adopt them.
+    fn reparent_children(&self) {
+        let children = core::mem::take(&mut self.pcb.lock().children);
+        if children.is_empty() {
+            return;
+        }
+        let mut ancestor = self.parent.read().upgrade();
+        while let Some(task) = ancestor.take() {
+            if task.pcb.lock().child_subreaper && task.exit_code().is_none() {
+                ancestor = Some(task);
+                break;
+            }
+            ancestor = task.parent.read().upgrade();
+        }
+        let reaper = ancestor.or_else(|| {
+            init_proc().filter(|x| !Arc::ptr_eq(&x.pcb, &self.pcb) && x.exit_code().is_none())
+        });
+        for child in children {
+            let pdeath_signal = child.pcb.lock().pdeath_signal;
+            if pdeath_signal != 0 && child.exit_code().is_none() {
+                child.send_signal(SignalFlags::from_num(pdeath_signal));
+            }
+            *child.parent.write() = reaper.as_ref().map_or(Weak::new(), Arc::downgrade);
+            child.tcb.write().exit_signal = SignalFlags::SIGCHLD.num() as _;
+            if let Some(reaper) = &reaper {
+                reaper.pcb.lock().children.push(child.clone());
+            }
+            if child.exit_code().is_some() {
+                child.exit_notify(SignalFlags::SIGCHLD.num());
+            }
+        }
+    }
+
     #[inline]
     pub fn exit_with_signal(&self, signal: usize) {
+        self.pcb.lock().term_signal.get_or_insert(signal);
         self.exit(128 + signal);
     }
 
+    /// The status reported by wait4, the exit code is at the bits 8..16,
+    /// the signal which killed the process is at the low 7 bits and
+    /// 0x80 is set if the core is dumped.
+    pub fn wait_status(&self) -> i32 {
+        let pcb = self.pcb.lock();
+        match pcb.term_signal {
+            Some(signal) if pcb.core_dumped => signal as i32 | 0x80,
+            Some(signal) => signal as i32,
+            None => ((pcb.exit_code.unwrap_or(0) & 0xff) << 8) as i32,
+        }
+    }
+
+    /// Send the signal to the process.
+    /// SIGCONT continues the process even if it is blocked or ignored,
+    /// and it discards the pending stop signals, and vice versa.
+    pub fn send_signal(&self, signal: SignalFlags) {
+        self.send_siginfo(SigInfo::new(signal.num() as _, SI_KERNEL));
+    }
+
+    /// Send the signal with the siginfo, the standard signals are not
+    /// queued, the siginfo of the pending one is kept.
+    pub fn send_siginfo(&self, info: SigInfo) {
+        let signal = SignalFlags::from_num(info.signo as _);
+        let mut tcb = self.tcb.write();
+        if signal == SignalFlags::SIGCONT {
+            tcb.discard_signal(
+                SignalFlags::SIGSTOP
+                    | SignalFlags::SIGTSTP
+                    | SignalFlags::SIGTTIN
+                    | SignalFlags::SIGTTOU,
+            );
+            drop(tcb);
+            self.job_continue();
+            tcb = self.tcb.write();
+        } else if signal.is_stop() {
+            tcb.discard_signal(SignalFlags::SIGCONT);
+        }
+        if signal.is_real_time() || !tcb.signal.has_sig(signal.clone()) {
+            tcb.sigqueue.push(info);
+        }
+        tcb.signal.add_signal(signal);
+    }
+
+    /// Send the signal from the user, the real time signal fails with EAGAIN
+    /// if the queue of the thread reaches RLIMIT_SIGPENDING.
+    pub fn try_send_siginfo(&self, info: SigInfo) -> Result<(), Errno> {
+        let limit = self.pcb.lock().rlimits[RLIMIT_SIGPENDING].curr;
+        let signal = SignalFlags::from_num(info.signo as _);
+        if signal.is_real_time() && self.tcb.read().sigqueue.len() >= limit {
+            return Err(Errno::EAGAIN);
+        }
+        self.send_siginfo(info);
+        Ok(())
+    }
+
+    /// Send the process-directed signal to the thread which doesn't block
+    /// it, the main thread gets it if all the threads block it.
+    pub fn send_process_siginfo(self: &Arc<Self>, info: SigInfo) {
+        self.process_signal_target(info.signo as _)
+            .send_siginfo(info);
+    }
+
+    /// The process-directed signal from the user, see `try_send_siginfo`.
+    pub fn try_send_process_siginfo(self: &Arc<Self>, info: SigInfo) -> Result<(), Errno> {
+        self.process_signal_target(info.signo as _)
+            .try_send_siginfo(info)
+    }
+
+    fn process_signal_target(self: &Arc<Self>, signum: usize) -> Arc<Self> {
+        let signal = SignalFlags::from_num(signum);
+        let threads = self.pcb.lock().threads.clone();
+        threads
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|x| {
+                let tcb = x.tcb.read();
+                tcb.thread_exit_code.is_none() && tcb.sigmask.mask & signal.bits() as usize == 0
+            })
+            .unwrap_or(self.clone())
+    }
+
+    /// The threads which the signals waited by the thread are taken from,
+    /// the thread itself and the main thread which gets the blocked
+    /// process-directed signals.
+    fn signal_sources(self: &Arc<Self>) -> Vec<Arc<Self>> {
+        let mut sources = vec![self.clone()];
+        let main = self.pcb.lock().threads.first().and_then(Weak::upgrade);
+        sources.extend(main.filter(|x| !Arc::ptr_eq(x, self)));
+        sources
+    }
+
+    /// Take the pending signal in the mask, it is used by sigtimedwait
+    /// and signalfd.
+    pub fn take_signal(self: &Arc<Self>, mask: usize) -> Option<SigInfo> {
+        self.signal_sources().iter().find_map(|task| {
+            let mut tcb = task.tcb.write();
+            let pending = SignalList {
+                signal: tcb.signal.signal & mask,
+            };
+            let signal = pending.try_get_signal()?;
+            Some(tcb.take_siginfo(signal))
+        })
+    }
+
+    /// Whether there is a pending signal in the mask.
+    pub fn has_pending(self: &Arc<Self>, mask: usize) -> bool {
+        self.signal_sources()
+            .iter()
+            .any(|x| x.tcb.read().signal.signal & mask != 0)
+    }
+
+    /// The pending signal which interrupts the blocking syscall, the blocked
+    /// signals and the signals ignored by the disposition are skipped.
+    pub fn interrupt_signal(&self) -> Option<SignalFlags> {
+        let tcb = self.tcb.read();
+        let pending = tcb.signal.mask(tcb.sigmask).signal;
+        drop(tcb);
+        let sighand = self.sighand();
+        let sighand = sighand.lock();
+        (0..64)
+            .filter(|i| pending & (1 << i) != 0)
+            .map(|i| SignalFlags::from_bits_truncate(1 << i))
+            .find(|signal| match sighand[signal.num()].handler {
+                0 => !matches!(signal.default_action(), SigDefault::Ign | SigDefault::Cont),
+                1 => false,
+                _ => true,
+            })
+    }
+
     #[inline]
-    pub fn cow_fork(self: Arc<Self>) -> Arc<Self> {
+    pub fn is_stopped(&self) -> bool {
+        self.pcb.lock().stop_signal.is_some()
+    }
+
+    /// Stop all the threads of the process, they wait until SIGCONT.
+    pub fn job_stop(&self, signal: usize) {
+        let mut pcb = self.pcb.lock();
+        if pcb.stop_signal.is_some() {
+            return;
+        }
+        pcb.stop_signal = Some(signal);
+        pcb.job_report = Some(JobReport::Stopped(signal));
+        drop(pcb);
+        self.notify_job_change(WaitEvent::Stopped(signal));
+    }
+
+    pub fn job_continue(&self) {
+        let mut pcb = self.pcb.lock();
+        if pcb.stop_signal.take().is_none() {
+            return;
+        }
+        pcb.job_report = Some(JobReport::Continued);
+        drop(pcb);
+        self.notify_job_change(WaitEvent::Continued);
+    }
+
+    /// Send SIGCHLD to the parent unless it sets SA_NOCLDSTOP.
+    fn notify_job_change(&self, event: WaitEvent) {
+        if let Some(parent) = self.parent.read().upgrade() {
+            let sigaction = parent.sighand().lock()[SignalFlags::SIGCHLD.num()];
+            if sigaction.flags & SA_NOCLDSTOP == 0 {
+                parent.send_process_siginfo(self.child_info(&event));
+            }
+        }
+    }
+
+    /// Account the CPU time to the thread and the process, the times are ticks.
+    pub fn account_time(&self, utime: u64, stime: u64) {
+        let mut tcb = self.tcb.write();
+        tcb.tms.utime += utime;
+        tcb.tms.stime += stime;
+        drop(tcb);
+        let mut pcb = self.pcb.lock();
+        pcb.tms.utime += utime;
+        pcb.tms.stime += stime;
+    }
+
+    /// The current time of the clock, CLOCK_THREAD_CPUTIME_ID is measured
+    /// by the CPU time of the thread.
+    pub fn clock_nsec(&self, clock_id: usize) -> Option<usize> {
+        let tms = match clock_id {
+            CLOCK_THREAD_CPUTIME_ID => self.tcb.read().tms,
+            _ => self.pcb.lock().tms,
+        };
+        clock_nsec(clock_id, &tms)
+    }
+
+    /// The siginfo of SIGCHLD which reports the state change to the parent,
+    /// the times are clock ticks.
+    pub fn child_info(&self, event: &WaitEvent) -> SigInfo {
+        let (code, status) = event.code_status();
+        let pcb = self.pcb.lock();
+        let ticks = |x: u64| Time::new(x as _).to_msec() as u64 / 10;
+        let (utime, stime) = (ticks(pcb.tms.utime), ticks(pcb.tms.stime));
+        SigInfo::child(code, self.task_id, pcb.cred.uid, status, utime, stime)
+    }
+
+    /// Fork the process, the parts are shared or copied by the clone flags.
+    #[inline]
+    pub fn cow_fork(self: Arc<Self>, flags: &CloneFlags) -> Arc<Self> {
         // Give the frame_tracker in the memset a type.
         // it will contains the frame used for page mapping、
         // mmap or text section.
         // and then we can implement COW(copy on write).
         let parent_task: Arc<UserTask> = self.clone();
-        let work_dir = parent_task.clone().pcb.lock().curr_dir.path_buf();
+        let work_dir = parent_task.fs().lock().curr_dir.path_buf();
         let new_task = Self::new(Arc::downgrade(&parent_task), work_dir);
         let mut new_tcb_writer = new_task.tcb.write();
-        // clone fd_table and clone heap
-        let mut new_pcb = new_task.pcb.lock();
-        let mut pcb = self.pcb.lock();
-        new_pcb.fd_table.0 = pcb.fd_table.0.clone();
-        new_pcb.heap = pcb.heap;
         new_tcb_writer.cx = self.tcb.read().cx.clone();
         new_tcb_writer.cx[TrapFrameArgs::RET] = 0;
-        new_pcb.curr_dir = pcb.curr_dir.clone();
+        new_tcb_writer.comm = self.tcb.read().comm.clone();
+        new_tcb_writer.sigaltstack = self.tcb.read().sigaltstack;
+        drop(new_tcb_writer);
+
+        let mut new_pcb = new_task.pcb.lock();
+        let mut pcb = self.pcb.lock();
+        new_pcb.files = match flags.contains(CloneFlags::CLONE_FILES) {
+            true => pcb.files.clone(),
+            false => Arc::new(Mutex::new(pcb.files.lock().clone())),
+        };
+        new_pcb.fs = match flags.contains(CloneFlags::CLONE_FS) {
+            true => pcb.fs.clone(),
+            false => Arc::new(Mutex::new(pcb.fs.lock().clone())),
+        };
+        new_pcb.sighand = match flags.contains(CloneFlags::CLONE_SIGHAND) {
+            true => pcb.sighand.clone(),
+            false => Arc::new(Mutex::new(*pcb.sighand.lock())),
+        };
         pcb.children.push(new_task.clone());
-        new_pcb.shms = pcb.shms.clone();
+        new_pcb.rlimits = pcb.rlimits;
+        new_pcb.auxv = pcb.auxv.clone();
+        new_pcb.pgid = pcb.pgid;
+        new_pcb.sid = pcb.sid;
+        new_pcb.cred = pcb.cred.clone();
+        new_pcb.no_new_privs = pcb.no_new_privs;
+        new_pcb.dumpable = pcb.dumpable;
+        if flags.contains(CloneFlags::CLONE_VM) {
+            new_pcb.mm = pcb.mm.clone();
+            drop(new_pcb);
+            return new_task;
+        }
+        let mm = pcb.mm.clone();
+        let new_mm = new_pcb.mm.clone();
         drop(new_pcb);
+        drop(pcb);
 
         // cow fork
-        pcb.memset.iter().for_each(|x| {
+        let mm = mm.lock();
+        let mut new_mm = new_mm.lock();
+        new_mm.heap = mm.heap;
+        new_mm.brk_start = mm.brk_start;
+        new_mm.shms = mm.shms.clone();
+        mm.memset.iter().for_each(|x| {
             let map_area = x.clone();
             map_area.mtrackers.iter().for_each(|x| {
-                new_task.map(x.tracker.0, x.vaddr, MappingFlags::URX);
-                self.map(x.tracker.0, x.vaddr, MappingFlags::URX);
+                new_mm.map(x.tracker.0, x.vaddr, MappingFlags::URX);
+                mm.map(x.tracker.0, x.vaddr, MappingFlags::URX);
             });
-            new_task.pcb.lock().memset.push(map_area);
+            new_mm.memset.push(map_area);
         });
-        drop(new_tcb_writer);
         // copy shm and map them
-        pcb.shms.iter().for_each(|x| {
+        mm.shms.iter().for_each(|x| {
             x.mem.trackers.iter().enumerate().for_each(|(i, tracker)| {
-                new_task.map(tracker.0, va!(x.start + i * PAGE_SIZE), MappingFlags::URWX);
+                new_mm.map(tracker.0, va!(x.start + i * PAGE_SIZE), MappingFlags::URWX);
             });
         });
+        drop(new_mm);
         new_task
     }
 
@@ -352,16 +1036,21 @@ impl UserTask {
             clear_child_tid: 0,
             set_child_tid: 0,
             signal: SignalList::new(),
-            signal_queue: [0; REAL_TIME_SIGNAL_NUM],
+            sigqueue: Vec::new(),
             exit_signal: 0,
             thread_exit_code: Option::None,
+            ptrace: None,
+            comm: parent_tcb.comm.clone(),
+            robust_list: 0,
+            sigaltstack: SignalStack::disabled(),
+            core_parked: false,
+            tms: TMS::default(),
         });
 
         tcb.write().cx[TrapFrameArgs::RET] = 0;
         drop(parent_tcb);
 
         let new_task = Arc::new(Self {
-            page_table: self.page_table.clone(),
             task_id,
             process_id: self.task_id,
             parent: RwLock::new(self.parent.read().clone()),
@@ -402,92 +1091,127 @@ impl UserTask {
     }
 
     pub fn get_last_free_addr(&self) -> VirtAddr {
-        let map_last = self
-            .pcb
-            .lock()
+        let mm = self.mm();
+        let mm = mm.lock();
+        let map_last = mm
             .memset
             .iter()
             .filter(|x| x.mtype != MemType::Stack)
             .fold(0, |acc, x| max(acc, x.start + x.len));
-        let shm_last = self
-            .pcb
-            .lock()
-            .shms
-            .iter()
-            .fold(0, |acc, v| max(v.start + v.size, acc));
+        let shm_last = mm.shms.iter().fold(0, |acc, v| max(v.start + v.size, acc));
         VirtAddr::new(max(map_last, shm_last))
     }
 
     pub fn get_fd(&self, index: usize) -> Option<Arc<File>> {
-        let pcb = self.pcb.lock();
-        match index >= pcb.rlimits[7] {
+        let limit = self.rlimit(RLIMIT_NOFILE);
+        match index >= limit {
             true => None,
-            false => pcb.fd_table.0[index].clone(),
+            false => self.files().lock().0.get(index).cloned().flatten(),
         }
     }
 
     pub fn set_fd(&self, index: usize, value: Arc<File>) {
-        let mut pcb = self.pcb.lock();
-        match index >= pcb.rlimits[7] {
-            true => {}
-            false => pcb.fd_table.0[index] = Some(value),
+        let limit = self.rlimit(RLIMIT_NOFILE);
+        if index >= limit {
+            return;
+        }
+        let files = self.files();
+        let mut files = files.lock();
+        // the table grows when RLIMIT_NOFILE is raised.
+        if index >= files.0.len() {
+            files.0.resize(index + 1, None);
         }
+        files.0[index] = Some(value);
     }
 
     pub fn clear_fd(&self, index: usize) {
-        let mut pcb = self.pcb.lock();
-        match index >= pcb.fd_table.len() {
+        let files = self.files();
+        let mut files = files.lock();
+        match index >= files.len() {
             true => {}
-            false => pcb.fd_table.0[index] = None,
+            false => files.0[index] = None,
         }
     }
 
     pub fn alloc_fd(&self) -> Option<usize> {
-        let mut pcb = self.pcb.lock();
-        let index = pcb
-            .fd_table
+        let limit = self.rlimit(RLIMIT_NOFILE);
+        let files = self.files();
+        let mut files = files.lock();
+        let index = files
             .0
             .iter()
             .enumerate()
-            .find(|(i, x)| x.is_none() && *i < pcb.rlimits[7])
+            .find(|(i, x)| x.is_none() && *i < limit)
             .map(|(i, _)| i);
-        if index.is_none() && pcb.fd_table.0.len() < pcb.rlimits[7] {
-            pcb.fd_table.0.push(None);
-            Some(pcb.fd_table.0.len() - 1)
+        if index.is_none() && files.0.len() < limit {
+            files.0.push(None);
+            Some(files.0.len() - 1)
         } else {
             index
         }
     }
 
+    /// Open the file, the directories in the path must be searchable.
+    /// Creating a file needs the write permission of the parent.
     pub fn fd_open(&self, fd: isize, filename: &str, flags: OpenFlags) -> VfsResult<File> {
+        self.fd_create(fd, filename, flags, 0o666)
+            .map(|(file, _)| file)
+    }
+
+    /// Open the file like [UserTask::fd_open], the file created by O_CREAT
+    /// gets the mode without the umask. It is true if the file is created.
+    pub fn fd_create(
+        &self,
+        fd: isize,
+        filename: &str,
+        flags: OpenFlags,
+        mode: u32,
+    ) -> VfsResult<(File, bool)> {
         let path = self.fd_resolve(fd, filename)?;
-        File::open(path, flags)
+        let cred = self.pcb.lock().cred.clone();
+        cred.check_search(&path)?;
+        if flags.contains(OpenFlags::O_CREAT)
+            && File::open(path.clone(), flags.clone() - OpenFlags::O_CREAT).is_err()
+        {
+            let parent = File::open(path.dir(), OpenFlags::O_DIRECTORY)?;
+            cred.check_file(&parent, Access::WRITE | Access::EXEC)?;
+            let file = File::open(path, flags)?;
+            // the filesystems without owners (fat32, devfs) are ignored.
+            let umask = self.fs().lock().umask;
+            let _ = file.chown(cred.fsuid, cred.fsgid);
+            let _ = file.chmod(mode & !umask & 0o7777);
+            return Ok((file, true));
+        }
+        File::open(path, flags).map(|file| (file, false))
     }
 
+    /// Resolve the path in the root directory, ".." can't go out of it.
     #[inline]
     pub fn fd_resolve(&self, fd: isize, filename: &str) -> VfsResult<PathBuf> {
-        if filename.starts_with("/") {
-            Ok(filename.into())
+        let fs = self.fs().lock().clone();
+        let parent = if filename.starts_with("/") {
+            PathBuf::new()
         } else {
-            let parent = match fd {
-                AT_CWD => self.pcb.lock().curr_dir.clone(),
+            match fd {
+                AT_CWD => fs.curr_dir.path_buf(),
                 _ => self
-                    .pcb
+                    .files()
                     .lock()
-                    .fd_table
                     .get(fd as usize)
                     .cloned()
                     .flatten()
-                    .ok_or(Errno::EBADF)?,
-            };
-            Ok(parent.path_buf().join(filename))
-        }
+                    .ok_or(Errno::EBADF)?
+                    .path_buf(),
+            }
+        };
+        let path = parent.trim_start(&fs.root).join(filename);
+        Ok(fs.root.join(&path.path()[1..]))
     }
 }
 
 impl AsyncTask for UserTask {
     fn before_run(&self) {
-        self.page_table.change();
+        self.page_table().change();
     }
 
     fn get_task_id(&self) -> TaskId {
@@ -500,44 +1224,41 @@ impl AsyncTask for UserTask {
 
     #[inline]
     fn exit(&self, exit_code: usize) {
+        // the other threads die with the process, their robust futexes are
+        // released too. The exited threads have released theirs.
+        self.exit_robust_list();
+        let threads = self.pcb.lock().threads.clone();
+        threads
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|x| x.task_id != self.task_id && x.tcb.read().thread_exit_code.is_none())
+            .for_each(|x| x.exit_robust_list());
         let tcb_writer = self.tcb.write();
         let uaddr = tcb_writer.clear_child_tid;
         if uaddr != 0 {
             debug!("write addr: {:#x}", uaddr);
             let addr = self
-                .page_table
+                .page_table()
                 .translate(VirtAddr::from(uaddr))
                 .expect("can't find a valid addr")
                 .0;
             unsafe {
                 addr.get_mut_ptr::<u32>().write(0);
             }
-            futex_wake(self.pcb.lock().futex_table.clone(), uaddr, 1);
+            self.futex_wake_any(uaddr, 1);
         }
         self.pcb.lock().exit_code = Some(exit_code);
         let exit_signal = tcb_writer.exit_signal;
         drop(tcb_writer);
+        self.ptrace_exit();
+        self.release_tracees();
 
         // recycle memory resouces if the pcb just used by this thread
         if Arc::strong_count(&self.pcb) == 1 {
-            self.pcb.lock().memset.clear();
-            self.pcb.lock().fd_table.clear();
-            self.pcb.lock().children.clear();
-        }
-
-        if let Some(parent) = self.parent.read().upgrade() {
-            if exit_signal != 0 {
-                parent
-                    .tcb
-                    .write()
-                    .signal
-                    .add_signal(SignalFlags::from_num(exit_signal as usize));
-            } else {
-                parent.tcb.write().signal.add_signal(SignalFlags::SIGCHLD);
-            }
-        } else {
-            self.pcb.lock().children.clear();
+            self.release_parts();
         }
+        self.reparent_children();
+        self.exit_notify(exit_signal as usize);
     }
 
     #[inline]
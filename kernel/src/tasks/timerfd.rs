@@ -0,0 +1,74 @@
+use crate::{
+    syscall::types::time::{ProcessTimer, CLOCK_REALTIME},
+    utils::time::{current_nsec, realtime_nsec},
+};
+use core::mem::{size_of, take};
+use sync::Mutex;
+use syscalls::Errno;
+use vfscore::{INodeInterface, PollEvent, VfsResult};
+
+/// The timerfd, reading it gets the count of the expirations since the
+/// last read, it blocks if the timer hasn't expired.
+pub struct TimerFd {
+    pub clock: usize,
+    pub inner: Mutex<TimerFdInner>,
+}
+
+/// The timer and its expirations are changed together under one lock.
+#[derive(Default)]
+pub struct TimerFdInner {
+    pub timer: ProcessTimer,
+    /// The expirations which aren't read.
+    pub ticks: u64,
+}
+
+impl TimerFd {
+    pub fn new(clock: usize) -> Self {
+        Self {
+            clock,
+            inner: Mutex::new(Default::default()),
+        }
+    }
+
+    /// The current time of the clock, CLOCK_MONOTONIC and CLOCK_BOOTTIME
+    /// are the same.
+    pub fn now(&self) -> usize {
+        match self.clock {
+            CLOCK_REALTIME => realtime_nsec(),
+            _ => current_nsec(),
+        }
+    }
+
+    /// Add the new expirations to the ticks.
+    fn update(&self) -> u64 {
+        let now = self.now();
+        let mut inner = self.inner.lock();
+        inner.ticks += inner.timer.expire(now) as u64;
+        inner.ticks
+    }
+}
+
+impl INodeInterface for TimerFd {
+    fn readat(&self, _offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        if buffer.len() < size_of::<u64>() {
+            return Err(Errno::EINVAL);
+        }
+        let now = self.now();
+        let mut inner = self.inner.lock();
+        let expired = inner.timer.expire(now) as u64;
+        let ticks = match take(&mut inner.ticks) + expired {
+            0 => return Err(Errno::EWOULDBLOCK),
+            ticks => ticks,
+        };
+        buffer[..size_of::<u64>()].copy_from_slice(&ticks.to_ne_bytes());
+        Ok(size_of::<u64>())
+    }
+
+    fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
+        let mut res = PollEvent::NONE;
+        if events.contains(PollEvent::POLLIN) && self.update() > 0 {
+            res |= PollEvent::POLLIN;
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,723 @@
+//! vDSO, a tiny shared object mapped into every user task.
+//!
+//! It lets `clock_gettime` and `gettimeofday` return without trapping
+//! into the kernel. The vDSO is two pages at [USER_VDSO_ADDR]:
+//! - the data page, the clock source and the realtime offset, updated
+//!   by the kernel when the realtime is set.
+//! - the elf page, built at boot, `AT_SYSINFO_EHDR` points to it.
+//!   It also holds the sigreturn trampoline of the signal handlers.
+//!
+//! The functions find the data page by the address of their own page, so
+//! the code must stay in the elf page. They read the counter (rdtime,
+//! cntvct, rdtsc, rdtime.d) and convert it like the kernel, the
+//! unsupported clocks fall back to the syscall.
+
+use super::{
+    elf::{write_at, ElfHeader, ProgramHeader, ELF_MACHINE, PT_LOAD},
+    MapTrack, MemArea, MemType, UserTask,
+};
+use crate::{
+    consts::USER_VDSO_ADDR,
+    utils::time::{clock_source, realtime_offset},
+};
+use alloc::sync::Arc;
+use core::{
+    mem::size_of,
+    sync::atomic::{fence, AtomicU32, Ordering},
+};
+use devices::{frame_alloc, FrameTracker, PAGE_SIZE};
+use polyhal::MappingFlags;
+use sync::{LazyInit, Mutex};
+
+cfg_if! {
+    if #[cfg(target_arch = "riscv64")] {
+        const CLOCK_GETTIME: &str = "__vdso_clock_gettime";
+        const GETTIMEOFDAY: &str = "__vdso_gettimeofday";
+        const RT_SIGRETURN: &str = "__vdso_rt_sigreturn";
+        core::arch::global_asm!(
+            r#"
+            .section .rodata.vdso, "a"
+            .option push
+            .option arch, +m
+            .p2align 4
+            .globl vdso_text_start
+        vdso_text_start:
+            .globl vdso_clock_gettime
+        vdso_clock_gettime:
+            auipc   t0, 0
+            srli    t0, t0, 12
+            slli    t0, t0, 12
+            li      t1, 4096
+            sub     t0, t0, t1
+            beqz    a0, .Lcgt_realtime
+            li      t1, 5
+            beq     a0, t1, .Lcgt_realtime
+            li      t1, 1
+            beq     a0, t1, .Lcgt_monotonic
+            li      t1, 4
+            beq     a0, t1, .Lcgt_monotonic
+            li      t1, 6
+            beq     a0, t1, .Lcgt_monotonic
+            li      t1, 7
+            beq     a0, t1, .Lcgt_monotonic
+            li      a7, 113
+            ecall
+            ret
+        .Lcgt_realtime:
+            li      t6, 1
+            j       .Lvdso_time
+        .Lcgt_monotonic:
+            li      t6, 0
+        .Lvdso_time:
+            lw      t1, 0(t0)
+            andi    t2, t1, 1
+            bnez    t2, .Lvdso_time
+            fence   r, r
+            rdtime  t2
+            ld      t3, 8(t0)
+            ld      t4, 16(t0)
+            ld      t5, 24(t0)
+            lwu     a2, 4(t0)
+            ld      a3, 32(t0)
+            fence   r, r
+            lw      a4, 0(t0)
+            bne     t1, a4, .Lvdso_time
+            sub     t2, t2, t3
+            mulhu   a4, t2, t5
+            mul     t2, t2, t5
+            srl     t2, t2, a2
+            neg     a2, a2
+            sll     a4, a4, a2
+            or      t2, t2, a4
+            add     t2, t2, t4
+            andi    a4, t6, 1
+            beqz    a4, .Lvdso_mono
+            add     t2, t2, a3
+        .Lvdso_mono:
+            li      a4, 1000000000
+            divu    a2, t2, a4
+            remu    a3, t2, a4
+            andi    a4, t6, 2
+            beqz    a4, .Lvdso_nsec
+            li      a4, 1000
+            divu    a3, a3, a4
+        .Lvdso_nsec:
+            sd      a2, 0(a1)
+            sd      a3, 8(a1)
+            li      a0, 0
+            ret
+
+            .globl vdso_gettimeofday
+        vdso_gettimeofday:
+            auipc   t0, 0
+            srli    t0, t0, 12
+            slli    t0, t0, 12
+            li      t1, 4096
+            sub     t0, t0, t1
+            beqz    a1, .Lgtod_tv
+            sw      zero, 0(a1)
+            sw      zero, 4(a1)
+        .Lgtod_tv:
+            beqz    a0, .Lgtod_ret
+            mv      a1, a0
+            li      t6, 3
+            j       .Lvdso_time
+        .Lgtod_ret:
+            li      a0, 0
+            ret
+            .globl vdso_sigreturn
+        vdso_sigreturn:
+            li      a7, 139
+            ecall
+            .globl vdso_text_end
+        vdso_text_end:
+            .option pop
+            "#
+        );
+    } else if #[cfg(target_arch = "aarch64")] {
+        const CLOCK_GETTIME: &str = "__kernel_clock_gettime";
+        const GETTIMEOFDAY: &str = "__kernel_gettimeofday";
+        const RT_SIGRETURN: &str = "__kernel_rt_sigreturn";
+        core::arch::global_asm!(
+            r#"
+            .section .rodata.vdso, "a"
+            .p2align 4
+            .globl vdso_text_start
+        vdso_text_start:
+            .globl vdso_clock_gettime
+        vdso_clock_gettime:
+            adr     x9, .
+            and     x9, x9, #0xfffffffffffff000
+            sub     x9, x9, #0x1000
+            cmp     x0, #0
+            b.eq    .Lcgt_realtime
+            cmp     x0, #5
+            b.eq    .Lcgt_realtime
+            cmp     x0, #1
+            b.eq    .Lcgt_monotonic
+            cmp     x0, #4
+            b.eq    .Lcgt_monotonic
+            cmp     x0, #6
+            b.eq    .Lcgt_monotonic
+            cmp     x0, #7
+            b.eq    .Lcgt_monotonic
+            mov     x8, #113
+            svc     #0
+            ret
+        .Lcgt_realtime:
+            mov     x15, #1
+            b       .Lvdso_time
+        .Lcgt_monotonic:
+            mov     x15, #0
+        .Lvdso_time:
+            ldar    w10, [x9]
+            tbnz    w10, #0, .Lvdso_time
+            isb
+            mrs     x11, cntvct_el0
+            ldp     x12, x13, [x9, #8]
+            ldp     x14, x16, [x9, #24]
+            ldr     w17, [x9, #4]
+            dmb     ishld
+            ldr     w2, [x9]
+            cmp     w10, w2
+            b.ne    .Lvdso_time
+            sub     x11, x11, x12
+            umulh   x2, x11, x14
+            mul     x11, x11, x14
+            lsr     x11, x11, x17
+            neg     x3, x17
+            lsl     x2, x2, x3
+            orr     x11, x11, x2
+            add     x11, x11, x13
+            tbz     x15, #0, .Lvdso_mono
+            add     x11, x11, x16
+        .Lvdso_mono:
+            mov     x2, #0xca00
+            movk    x2, #0x3b9a, lsl #16
+            udiv    x3, x11, x2
+            msub    x4, x3, x2, x11
+            tbz     x15, #1, .Lvdso_nsec
+            mov     x2, #1000
+            udiv    x4, x4, x2
+        .Lvdso_nsec:
+            stp     x3, x4, [x1]
+            mov     x0, #0
+            ret
+
+            .globl vdso_gettimeofday
+        vdso_gettimeofday:
+            adr     x9, .
+            and     x9, x9, #0xfffffffffffff000
+            sub     x9, x9, #0x1000
+            cbz     x1, .Lgtod_tv
+            stp     wzr, wzr, [x1]
+        .Lgtod_tv:
+            cbz     x0, .Lgtod_ret
+            mov     x1, x0
+            mov     x15, #3
+            b       .Lvdso_time
+        .Lgtod_ret:
+            mov     x0, #0
+            ret
+            .globl vdso_sigreturn
+        vdso_sigreturn:
+            mov     x8, #139
+            svc     #0
+            .globl vdso_text_end
+        vdso_text_end:
+            "#
+        );
+    } else if #[cfg(target_arch = "x86_64")] {
+        const CLOCK_GETTIME: &str = "__vdso_clock_gettime";
+        const GETTIMEOFDAY: &str = "__vdso_gettimeofday";
+        const RT_SIGRETURN: &str = "__vdso_rt_sigreturn";
+        core::arch::global_asm!(
+            r#"
+            .section .rodata.vdso, "a"
+            .p2align 4
+            .globl vdso_text_start
+        vdso_text_start:
+            .globl vdso_clock_gettime
+        vdso_clock_gettime:
+            lea     r11, [rip]
+            and     r11, -4096
+            sub     r11, 4096
+            cmp     rdi, 0
+            je      .Lcgt_realtime
+            cmp     rdi, 5
+            je      .Lcgt_realtime
+            cmp     rdi, 1
+            je      .Lcgt_monotonic
+            cmp     rdi, 4
+            je      .Lcgt_monotonic
+            cmp     rdi, 6
+            je      .Lcgt_monotonic
+            cmp     rdi, 7
+            je      .Lcgt_monotonic
+            mov     eax, 228
+            syscall
+            ret
+        .Lcgt_realtime:
+            mov     edi, 1
+            jmp     .Lvdso_time
+        .Lcgt_monotonic:
+            xor     edi, edi
+        .Lvdso_time:
+            mov     r8d, dword ptr [r11]
+            test    r8d, 1
+            jnz     .Lvdso_time
+            lfence
+            rdtsc
+            shl     rdx, 32
+            or      rax, rdx
+            sub     rax, qword ptr [r11 + 8]
+            mul     qword ptr [r11 + 24]
+            mov     ecx, dword ptr [r11 + 4]
+            shrd    rax, rdx, cl
+            add     rax, qword ptr [r11 + 16]
+            test    edi, 1
+            jz      .Lvdso_mono
+            add     rax, qword ptr [r11 + 32]
+        .Lvdso_mono:
+            mov     r9d, dword ptr [r11]
+            cmp     r8d, r9d
+            jne     .Lvdso_time
+            xor     edx, edx
+            mov     ecx, 1000000000
+            div     rcx
+            test    edi, 2
+            jz      .Lvdso_nsec
+            mov     r8, rax
+            mov     rax, rdx
+            xor     edx, edx
+            mov     ecx, 1000
+            div     rcx
+            mov     rdx, rax
+            mov     rax, r8
+        .Lvdso_nsec:
+            mov     qword ptr [rsi], rax
+            mov     qword ptr [rsi + 8], rdx
+            xor     eax, eax
+            ret
+
+            .globl vdso_gettimeofday
+        vdso_gettimeofday:
+            lea     r11, [rip]
+            and     r11, -4096
+            sub     r11, 4096
+            test    rsi, rsi
+            jz      .Lgtod_tv
+            mov     qword ptr [rsi], 0
+        .Lgtod_tv:
+            test    rdi, rdi
+            jz      .Lgtod_ret
+            mov     rsi, rdi
+            mov     edi, 3
+            jmp     .Lvdso_time
+        .Lgtod_ret:
+            xor     eax, eax
+            ret
+            .globl vdso_sigreturn
+        vdso_sigreturn:
+            mov     eax, 15
+            syscall
+            .globl vdso_text_end
+        vdso_text_end:
+            "#
+        );
+    } else if #[cfg(target_arch = "loongarch64")] {
+        const CLOCK_GETTIME: &str = "__vdso_clock_gettime";
+        const GETTIMEOFDAY: &str = "__vdso_gettimeofday";
+        const RT_SIGRETURN: &str = "__vdso_rt_sigreturn";
+        core::arch::global_asm!(
+            r#"
+            .section .rodata.vdso, "a"
+            .p2align 4
+            .globl vdso_text_start
+        vdso_text_start:
+            .globl vdso_clock_gettime
+        vdso_clock_gettime:
+            pcaddi  $t0, 0
+            srli.d  $t0, $t0, 12
+            slli.d  $t0, $t0, 12
+            lu12i.w $t1, 1
+            sub.d   $t0, $t0, $t1
+            beqz    $a0, .Lcgt_realtime
+            ori     $t1, $zero, 5
+            beq     $a0, $t1, .Lcgt_realtime
+            ori     $t1, $zero, 1
+            beq     $a0, $t1, .Lcgt_monotonic
+            ori     $t1, $zero, 4
+            beq     $a0, $t1, .Lcgt_monotonic
+            ori     $t1, $zero, 6
+            beq     $a0, $t1, .Lcgt_monotonic
+            ori     $t1, $zero, 7
+            beq     $a0, $t1, .Lcgt_monotonic
+            ori     $a7, $zero, 113
+            syscall 0
+            jirl    $zero, $ra, 0
+        .Lcgt_realtime:
+            ori     $t8, $zero, 1
+            b       .Lvdso_time
+        .Lcgt_monotonic:
+            move    $t8, $zero
+        .Lvdso_time:
+            ld.w    $t1, $t0, 0
+            andi    $t2, $t1, 1
+            bnez    $t2, .Lvdso_time
+            dbar    0
+            rdtime.d $t2, $zero
+            ld.d    $t3, $t0, 8
+            ld.d    $t4, $t0, 16
+            ld.d    $t5, $t0, 24
+            ld.wu   $t6, $t0, 4
+            ld.d    $t7, $t0, 32
+            dbar    0
+            ld.w    $a2, $t0, 0
+            bne     $t1, $a2, .Lvdso_time
+            sub.d   $t2, $t2, $t3
+            mulh.du $a2, $t2, $t5
+            mul.d   $t2, $t2, $t5
+            srl.d   $t2, $t2, $t6
+            sub.d   $a3, $zero, $t6
+            sll.d   $a2, $a2, $a3
+            or      $t2, $t2, $a2
+            add.d   $t2, $t2, $t4
+            andi    $a2, $t8, 1
+            beqz    $a2, .Lvdso_mono
+            add.d   $t2, $t2, $t7
+        .Lvdso_mono:
+            lu12i.w $a2, 0x3b9ac
+            ori     $a2, $a2, 0xa00
+            div.du  $a3, $t2, $a2
+            mod.du  $a4, $t2, $a2
+            andi    $a2, $t8, 2
+            beqz    $a2, .Lvdso_nsec
+            ori     $a2, $zero, 1000
+            div.du  $a4, $a4, $a2
+        .Lvdso_nsec:
+            st.d    $a3, $a1, 0
+            st.d    $a4, $a1, 8
+            move    $a0, $zero
+            jirl    $zero, $ra, 0
+
+            .globl vdso_gettimeofday
+        vdso_gettimeofday:
+            pcaddi  $t0, 0
+            srli.d  $t0, $t0, 12
+            slli.d  $t0, $t0, 12
+            lu12i.w $t1, 1
+            sub.d   $t0, $t0, $t1
+            beqz    $a1, .Lgtod_tv
+            st.w    $zero, $a1, 0
+            st.w    $zero, $a1, 4
+        .Lgtod_tv:
+            beqz    $a0, .Lgtod_ret
+            move    $a1, $a0
+            ori     $t8, $zero, 3
+            b       .Lvdso_time
+        .Lgtod_ret:
+            move    $a0, $zero
+            jirl    $zero, $ra, 0
+            .globl vdso_sigreturn
+        vdso_sigreturn:
+            ori     $a7, $zero, 139
+            syscall 0
+            .globl vdso_text_end
+        vdso_text_end:
+            "#
+        );
+    }
+}
+
+extern "C" {
+    fn vdso_text_start();
+    fn vdso_text_end();
+    fn vdso_clock_gettime();
+    fn vdso_gettimeofday();
+    fn vdso_sigreturn();
+}
+
+/// The data shared with the user, at the first page of the vDSO.
+/// The offsets of the fields are used by the code in the elf page.
+///
+/// The user reads the counter and converts it like the kernel, see
+/// `ClockSource`, the fields are only changed when the realtime is set.
+#[repr(C)]
+struct VdsoData {
+    /// The sequence counter, it is odd while the kernel is updating.
+    seq: AtomicU32,
+    shift: u32,
+    base_cycles: u64,
+    base_nsec: u64,
+    mult: u64,
+    /// CLOCK_REALTIME minus CLOCK_MONOTONIC.
+    realtime_offset: u64,
+}
+
+struct Vdso {
+    data: Arc<FrameTracker>,
+    elf: Arc<FrameTracker>,
+    /// The offset of the sigreturn trampoline in the elf page.
+    sigreturn: usize,
+}
+
+static VDSO: LazyInit<Vdso> = LazyInit::new();
+
+/// Only one core updates the data page at the same time.
+static VDSO_UPDATE: Mutex<()> = Mutex::new(());
+
+#[repr(C)]
+struct DynEntry {
+    tag: u64,
+    val: u64,
+}
+
+#[repr(C)]
+struct Symbol {
+    name: u32,
+    info: u8,
+    other: u8,
+    shndx: u16,
+    value: u64,
+    size: u64,
+}
+
+const PT_DYNAMIC: u32 = 2;
+const DT_NULL: u64 = 0;
+const DT_HASH: u64 = 4;
+const DT_STRTAB: u64 = 5;
+const DT_SYMTAB: u64 = 6;
+const DT_STRSZ: u64 = 10;
+const DT_SYMENT: u64 = 11;
+const DT_SONAME: u64 = 14;
+/// STB_GLOBAL << 4 | STT_FUNC
+const SYM_GLOBAL_FUNC: u8 = 0x12;
+const SONAME: &str = "linux-vdso.so.1";
+
+/// Build the shared object in the elf page, return the offset of the
+/// sigreturn trampoline.
+///
+/// There are no sections, the dynamic segment only has the symbols
+/// and the hash table, so the dynamic linker can look up the functions.
+fn build_elf(page: &mut [u8]) -> usize {
+    let text_start = vdso_text_start as usize;
+    let text = unsafe {
+        core::slice::from_raw_parts(text_start as *const u8, vdso_text_end as usize - text_start)
+    };
+    let symbols = [
+        (CLOCK_GETTIME, vdso_clock_gettime as usize - text_start),
+        (GETTIMEOFDAY, vdso_gettimeofday as usize - text_start),
+        (RT_SIGRETURN, vdso_sigreturn as usize - text_start),
+    ];
+    // The symbol 0 is the undefined symbol.
+    let nsyms = symbols.len() + 1;
+
+    let dyn_off = size_of::<ElfHeader>() + 2 * size_of::<ProgramHeader>();
+    let dyn_len = 7 * size_of::<DynEntry>();
+    let hash_off = dyn_off + dyn_len;
+    let sym_off = (hash_off + (2 + 1 + nsyms) * size_of::<u32>()).next_multiple_of(8);
+    let str_off = sym_off + nsyms * size_of::<Symbol>();
+
+    // string table, the first byte is the empty string.
+    let mut str_len = 1;
+    let mut push_str = |page: &mut [u8], s: &str| {
+        let index = str_len;
+        page[str_off + index..str_off + index + s.len()].copy_from_slice(s.as_bytes());
+        str_len += s.len() + 1;
+        index as u32
+    };
+    let soname = push_str(page, SONAME);
+    let names = symbols.map(|(name, _)| push_str(page, name));
+
+    let text_off = (str_off + str_len).next_multiple_of(16);
+    assert!(text_off + text.len() <= PAGE_SIZE, "vdso is too large");
+    page[text_off..text_off + text.len()].copy_from_slice(text);
+
+    let mut ident = [0u8; 16];
+    // magic, 64 bit, little endian, version 1.
+    ident[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    write_at(
+        page,
+        0,
+        ElfHeader {
+            ident,
+            etype: 3, // ET_DYN
+            machine: ELF_MACHINE,
+            version: 1,
+            entry: 0,
+            phoff: size_of::<ElfHeader>() as _,
+            shoff: 0,
+            flags: 0,
+            ehsize: size_of::<ElfHeader>() as _,
+            phentsize: size_of::<ProgramHeader>() as _,
+            phnum: 2,
+            shentsize: 64,
+            shnum: 0,
+            shstrndx: 0,
+        },
+    );
+    write_at(
+        page,
+        size_of::<ElfHeader>(),
+        ProgramHeader {
+            ptype: PT_LOAD,
+            flags: 0b101, // R | X
+            offset: 0,
+            vaddr: 0,
+            paddr: 0,
+            filesz: PAGE_SIZE as _,
+            memsz: PAGE_SIZE as _,
+            align: PAGE_SIZE as _,
+        },
+    );
+    write_at(
+        page,
+        size_of::<ElfHeader>() + size_of::<ProgramHeader>(),
+        ProgramHeader {
+            ptype: PT_DYNAMIC,
+            flags: 0b100, // R
+            offset: dyn_off as _,
+            vaddr: dyn_off as _,
+            paddr: dyn_off as _,
+            filesz: dyn_len as _,
+            memsz: dyn_len as _,
+            align: 8,
+        },
+    );
+
+    let dyns = [
+        (DT_HASH, hash_off),
+        (DT_STRTAB, str_off),
+        (DT_SYMTAB, sym_off),
+        (DT_STRSZ, str_len),
+        (DT_SYMENT, size_of::<Symbol>()),
+        (DT_SONAME, soname as usize),
+        (DT_NULL, 0),
+    ];
+    for (i, (tag, val)) in dyns.into_iter().enumerate() {
+        let entry = DynEntry { tag, val: val as _ };
+        write_at(page, dyn_off + i * size_of::<DynEntry>(), entry);
+    }
+
+    // sysv hash table with one bucket, all the symbols are in the chain.
+    write_at(page, hash_off, 1u32);
+    write_at(page, hash_off + 4, nsyms as u32);
+    write_at(page, hash_off + 8, (nsyms - 1) as u32);
+    for i in 0..nsyms {
+        write_at(page, hash_off + 12 + i * 4, i.saturating_sub(1) as u32);
+    }
+
+    for (i, (_, offset)) in symbols.iter().enumerate() {
+        let symbol = Symbol {
+            name: names[i],
+            info: SYM_GLOBAL_FUNC,
+            other: 0,
+            shndx: 1,
+            value: (text_off + offset) as _,
+            size: 0,
+        };
+        write_at(page, sym_off + (i + 1) * size_of::<Symbol>(), symbol);
+    }
+    text_off + symbols[2].1
+}
+
+/// Alloc the pages of the vDSO and build the elf.
+pub fn init() {
+    let data = Arc::new(frame_alloc().expect("can't alloc vdso data page"));
+    let elf = Arc::new(frame_alloc().expect("can't alloc vdso elf page"));
+    data.0.slice_mut_with_len(PAGE_SIZE).fill(0);
+    let page = elf.0.slice_mut_with_len(PAGE_SIZE);
+    page.fill(0);
+    let sigreturn = build_elf(page);
+    VDSO.init_by(Vdso {
+        data,
+        elf,
+        sigreturn,
+    });
+    enable_user_counter();
+    update();
+}
+
+/// Publish the clock source and the realtime offset in the data page.
+pub fn update() {
+    let Some(vdso) = VDSO.try_get() else {
+        return;
+    };
+    let _guard = VDSO_UPDATE.lock();
+    let data = unsafe { vdso.data.0.get_mut_ptr::<VdsoData>().as_mut().unwrap() };
+    let source = clock_source();
+
+    let seq = data.seq.load(Ordering::Relaxed);
+    data.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+    fence(Ordering::Release);
+    data.shift = source.shift;
+    data.base_cycles = source.base_cycles;
+    data.base_nsec = source.base_nsec;
+    data.mult = source.mult;
+    data.realtime_offset = realtime_offset() as u64;
+    data.seq.store(seq.wrapping_add(2), Ordering::Release);
+}
+
+/// Let the user read the counter of the clock source.
+fn enable_user_counter() {
+    cfg_if! {
+        if #[cfg(target_arch = "riscv64")] {
+            // scounteren.TM
+            unsafe { core::arch::asm!("csrs scounteren, {}", in(reg) 2usize) };
+        } else if #[cfg(target_arch = "aarch64")] {
+            // CNTKCTL_EL1.EL0VCTEN
+            unsafe {
+                core::arch::asm!(
+                    "mrs {tmp}, cntkctl_el1",
+                    "orr {tmp}, {tmp}, #2",
+                    "msr cntkctl_el1, {tmp}",
+                    tmp = out(reg) _,
+                )
+            };
+        }
+    }
+}
+
+/// The user address of the trampoline calling rt_sigreturn, it is the
+/// return address of the signal handler without SA_RESTORER.
+pub fn sigreturn_addr() -> usize {
+    let vdso = VDSO.try_get().expect("vdso is not initialized");
+    USER_VDSO_ADDR + PAGE_SIZE + vdso.sigreturn
+}
+
+/// Map the vDSO into the user task, return the address of the elf.
+pub fn map_vdso(task: &Arc<UserTask>) -> usize {
+    let vdso = VDSO.try_get().expect("vdso is not initialized");
+    let mtrackers = vec![
+        MapTrack {
+            vaddr: va!(USER_VDSO_ADDR),
+            tracker: vdso.data.clone(),
+            rwx: 0b100,
+        },
+        MapTrack {
+            vaddr: va!(USER_VDSO_ADDR + PAGE_SIZE),
+            tracker: vdso.elf.clone(),
+            rwx: 0b101,
+        },
+    ];
+    task.map(
+        vdso.data.0,
+        va!(USER_VDSO_ADDR),
+        MappingFlags::U | MappingFlags::R,
+    );
+    task.map(
+        vdso.elf.0,
+        va!(USER_VDSO_ADDR + PAGE_SIZE),
+        MappingFlags::URX,
+    );
+    task.mm().lock().memset.push(MemArea {
+        mtype: MemType::Vdso,
+        mtrackers,
+        file: None,
+        offset: 0,
+        start: USER_VDSO_ADDR,
+        len: 2 * PAGE_SIZE,
+    });
+    USER_VDSO_ADDR + PAGE_SIZE
+}
@@ -1,14 +1,23 @@
-use alloc::boxed::Box;
+use alloc::{boxed::Box, sync::Weak, vec::Vec};
 use async_recursion::async_recursion;
+use core::cmp::min;
 use executor::{boot_page_table, yield_now, AsyncTask};
 use futures_lite::future;
 use log::debug;
+use polyhal::Time;
 use polyhal_trap::trapframe::TrapFrame;
 use signal::SignalFlags;
 
 use crate::{
-    tasks::{current_user_task, UserTaskControlFlow},
-    utils::time::current_timeval,
+    syscall::types::{
+        signal::{SigInfo, SIGEV_NONE, SIGEV_THREAD_ID, SI_KERNEL, SI_USER},
+        sys::{RLIMIT_CPU, RLIM_INFINITY},
+    },
+    tasks::{
+        current_user_task, ptrace::PtraceStop, tid2user_task, UserTaskControlFlow, WaitContinue,
+        WaitExit,
+    },
+    utils::time::{clock_nsec, cputime_nsec, current_nsec},
 };
 
 use super::UserTaskContainer;
@@ -21,22 +30,83 @@ impl UserTaskContainer {
     }
 
     pub fn check_timer(&self) {
-        let mut pcb = self.task.pcb.lock();
-        let timer = &mut pcb.timer[0];
-        if timer.next > timer.last {
-            let now = current_timeval();
-            if now >= timer.next {
-                self.task
-                    .tcb
-                    .write()
-                    .signal
-                    .add_signal(SignalFlags::SIGALRM);
-                timer.last = timer.next;
+        let mut guard = self.task.pcb.lock();
+        let pcb = &mut *guard;
+        let tms = pcb.tms;
+        let mut expired = Vec::new();
+        // ITIMER_REAL, ITIMER_VIRTUAL and ITIMER_PROF.
+        let clocks = [
+            current_nsec(),
+            cputime_nsec(tms.utime),
+            cputime_nsec(tms.utime + tms.stime),
+        ];
+        let signals = [
+            SignalFlags::SIGALRM,
+            SignalFlags::SIGVTALRM,
+            SignalFlags::SIGPROF,
+        ];
+        for (i, timer) in pcb.timer.iter_mut().enumerate() {
+            if timer.expire(clocks[i]) > 0 {
+                expired.push((None, SigInfo::new(signals[i].num() as _, SI_KERNEL)));
+            }
+        }
+        for (&timerid, posix) in pcb.posix_timers.iter_mut() {
+            let count = clock_nsec(posix.clock, &tms).map_or(0, |now| posix.timer.expire(now));
+            if count == 0 || posix.event.notify == SIGEV_NONE {
+                continue;
+            }
+            let target = match posix.event.notify {
+                SIGEV_THREAD_ID => tid2user_task(posix.event.tid as _),
+                _ => None,
+            };
+            // the expirations are the overrun until the signal is delivered.
+            let bit = 1 << (posix.event.signo - 1);
+            let pending = match &target {
+                Some(task) => task.tcb.read().signal.signal & bit != 0,
+                None => pcb
+                    .threads
+                    .iter()
+                    .filter_map(Weak::upgrade)
+                    .any(|x| x.tcb.read().signal.signal & bit != 0),
+            };
+            if pending {
+                posix.overrun += count;
+                continue;
+            }
+            posix.overrun = count - 1;
+            let signo = posix.event.signo as _;
+            let info = SigInfo::timer(signo, timerid, posix.overrun, posix.event.value);
+            expired.push((target, info));
+        }
+        // RLIMIT_CPU, SIGXCPU is sent every second after the soft limit.
+        let secs = Time::new((tms.utime + tms.stime) as _).to_msec() / 1000;
+        let rlimit = &mut pcb.rlimits[RLIMIT_CPU];
+        if rlimit.curr != RLIM_INFINITY {
+            let signal = match secs >= rlimit.max {
+                true => Some(SignalFlags::SIGKILL),
+                false if secs >= rlimit.curr => {
+                    rlimit.curr = min(secs + 1, rlimit.max);
+                    Some(SignalFlags::SIGXCPU)
+                }
+                false => None,
+            };
+            if let Some(signal) = signal {
+                expired.push((None, SigInfo::new(signal.num() as _, SI_KERNEL)));
+            }
+        }
+        drop(guard);
+        for (target, info) in expired {
+            match target {
+                Some(task) => task.send_siginfo(info),
+                None => self.task.send_process_siginfo(info),
             }
         }
     }
 
-    pub async fn check_signal(&self) {
+    /// The signals of the traced task and the signals with handlers are
+    /// delivered after the running syscall returns, the signal-delivery-stop
+    /// can't be dropped with it and the handler changes the user context.
+    pub async fn check_signal(&self, in_syscall: bool) {
         loop {
             let sig_mask = self.task.tcb.read().sigmask;
             let signal = self
@@ -49,42 +119,74 @@ impl UserTaskContainer {
                 .try_get_signal();
             if let Some(signal) = signal {
                 debug!("mask: {:?}", sig_mask);
-                self.handle_signal(signal.clone()).await;
-                let mut tcb = self.task.tcb.write();
-                tcb.signal.remove_signal(signal.clone());
-                // check if it is a real time signal
-                if let Some(index) = signal.real_time_index()
-                    && tcb.signal_queue[index] > 0
-                {
-                    tcb.signal.add_signal(signal.clone());
-                    tcb.signal_queue[index] -= 1;
+                let handler = self.task.sighand().lock()[signal.num()].handler;
+                if in_syscall && handler > 1 && signal != SignalFlags::SIGKILL {
+                    break;
                 }
-            } else {
+                let deliver = match self.task.is_traced() && signal != SignalFlags::SIGKILL {
+                    true if in_syscall => break,
+                    true => {
+                        let stop = PtraceStop::Signal(signal.num());
+                        self.task.ptrace_stop(stop).await
+                    }
+                    false => signal.num(),
+                };
+                let info = self.task.tcb.write().take_siginfo(signal.clone());
+                match deliver {
+                    0 => {}
+                    _ if deliver == signal.num() => self.handle_signal(info).await,
+                    // the signal is changed by the tracer.
+                    _ => {
+                        let info = SigInfo::kill(deliver, SI_USER, 0, 0);
+                        self.handle_signal(info).await
+                    }
+                }
+                continue;
+            }
+            // stopped by the job control signal, wait for SIGCONT or SIGKILL.
+            if !self.task.is_stopped() || self.check_thread_exit().is_some() {
                 break;
             }
+            WaitContinue(self.task.clone()).await;
         }
     }
 
+    /// Park the thread if another thread is dumping the core.
+    fn park_core_dump(&self) -> bool {
+        let dumping = self.task.pcb.lock().core_dumping;
+        if dumping {
+            self.task.tcb.write().core_parked = true;
+        }
+        dumping
+    }
+
     pub async fn entry_point(&mut self, cx_ref: &mut TrapFrame) {
         let mut times: i32 = 0;
 
         loop {
+            // don't return to the user until the process exits.
+            if self.park_core_dump() {
+                WaitExit(self.task.clone()).await;
+            }
             self.check_timer();
-            self.check_signal().await;
+            self.check_signal(false).await;
 
             // check for task exit status.
             if let Some(exit_code) = self.check_thread_exit() {
                 debug!(
-                    "program exit with code: {}  task_id: {}  with  inner",
+                    "program exit with code: {}  task_id: {}  comm: {}  with  inner",
                     exit_code,
-                    self.task.get_task_id()
+                    self.task.get_task_id(),
+                    self.task.comm()
                 );
                 break;
             }
 
             let res = future::or(self.handle_syscall(cx_ref), async {
                 loop {
-                    self.check_signal().await;
+                    // the user context is saved while the syscall is blocked.
+                    self.park_core_dump();
+                    self.check_signal(true).await;
 
                     if let Some(_exit_code) = self.check_thread_exit() {
                         return UserTaskControlFlow::Break;
@@ -100,9 +202,10 @@ impl UserTaskContainer {
 
             if let Some(exit_code) = self.check_thread_exit() {
                 debug!(
-                    "program exit with code: {}  task_id: {}  with  inner",
+                    "program exit with code: {}  task_id: {}  comm: {}  with  inner",
                     exit_code,
-                    self.task.get_task_id()
+                    self.task.get_task_id(),
+                    self.task.comm()
                 );
                 break;
             }
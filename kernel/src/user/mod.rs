@@ -1,3 +1,7 @@
+use crate::consts::USER_STACK_TOP;
+use crate::syscall::types::signal::{SigInfo, ILL_ILLOPC, SA_RESTART, SEGV_ACCERR, SEGV_MAPERR};
+use crate::syscall::types::sys::RLIMIT_STACK;
+use crate::tasks::ptrace::PtraceStop;
 use crate::tasks::UserTaskControlFlow;
 use crate::tasks::{MapTrack, MemType, UserTask};
 use crate::utils::hexdump;
@@ -10,7 +14,7 @@ use polyhal::{MappingFlags, Time, VirtAddr};
 use polyhal_trap::trap::{run_user_task, EscapeReason};
 use polyhal_trap::trapframe::{TrapFrame, TrapFrameArgs};
 use runtime::frame::frame_alloc;
-use syscalls::Sysno;
+use syscalls::Errno;
 
 pub mod entry;
 pub mod signal;
@@ -26,20 +30,27 @@ pub struct UserTaskContainer {
 /// copy page or remap page.
 pub fn user_cow_int(task: Arc<UserTask>, cx_ref: &mut TrapFrame, vaddr: VirtAddr) {
     warn!(
-        "store/instruction page fault @ {:#x} vaddr: {} paddr: {:?} task_id: {}",
+        "store/instruction page fault @ {:#x} vaddr: {} paddr: {:?} task_id: {} comm: {}",
         cx_ref[TrapFrameArgs::SEPC],
         vaddr,
-        task.page_table.translate(vaddr),
-        task.get_task_id()
+        task.page_table().translate(vaddr),
+        task.get_task_id(),
+        task.comm()
     );
-    let mut pcb = task.pcb.lock();
-    let area = pcb.memset.iter_mut().find(|x| x.contains(vaddr.raw()));
+    let stack_limit = task.rlimit(RLIMIT_STACK);
+    let mm = task.mm();
+    let mut mm = mm.lock();
+    let area = mm.memset.iter_mut().find(|x| x.contains(vaddr.raw()));
     if let Some(area) = area {
         let finded = area.mtrackers.iter_mut().find(|x| x.vaddr == vaddr.floor());
         let ppn = match finded {
             Some(map_track) => {
-                if area.mtype == MemType::Shared {
-                    task.tcb.write().signal.add_signal(SignalFlags::SIGSEGV);
+                if matches!(area.mtype, MemType::Shared | MemType::Vdso) {
+                    task.send_siginfo(SigInfo::fault(
+                        SignalFlags::SIGSEGV.num(),
+                        SEGV_ACCERR,
+                        vaddr.raw(),
+                    ));
                     return;
                 }
                 // tips: this finded will consume a strong count.
@@ -57,6 +68,17 @@ pub fn user_cow_int(task: Arc<UserTask>, cx_ref: &mut TrapFrame, vaddr: VirtAddr
                 map_track.tracker.0
             }
             None => {
+                // the stack can't grow beyond RLIMIT_STACK.
+                if area.mtype == MemType::Stack
+                    && USER_STACK_TOP - vaddr.floor().raw() > stack_limit
+                {
+                    task.send_siginfo(SigInfo::fault(
+                        SignalFlags::SIGSEGV.num(),
+                        SEGV_MAPERR,
+                        vaddr.raw(),
+                    ));
+                    return;
+                }
                 let tracker = Arc::new(frame_alloc().expect("can't alloc frame in cow_fork_int"));
                 let mtracker = MapTrack {
                     vaddr: vaddr.floor(),
@@ -74,10 +96,13 @@ pub fn user_cow_int(task: Arc<UserTask>, cx_ref: &mut TrapFrame, vaddr: VirtAddr
             }
         };
 
-        drop(pcb);
-        task.map(ppn, vaddr.floor(), MappingFlags::URWX);
+        mm.map(ppn, vaddr.floor(), MappingFlags::URWX);
     } else {
-        task.tcb.write().signal.add_signal(SignalFlags::SIGSEGV);
+        task.send_siginfo(SigInfo::fault(
+            SignalFlags::SIGSEGV.num(),
+            SEGV_MAPERR,
+            vaddr.raw(),
+        ));
     }
 }
 
@@ -87,28 +112,39 @@ impl UserTaskContainer {
         let ustart = Time::now().raw();
         if matches!(run_user_task(cx_ref), EscapeReason::SysCall) {
             self.task
-                .inner_map(|inner| inner.tms.utime += (Time::now().raw() - ustart) as u64);
+                .account_time((Time::now().raw() - ustart) as u64, 0);
 
             let sstart = Time::now().raw();
-            if cx_ref[TrapFrameArgs::SYSCALL] == Sysno::rt_sigreturn.id() as _ {
-                return UserTaskControlFlow::Break;
-            }
             cx_ref.syscall_ok();
-            let result = self
-                .syscall(cx_ref[TrapFrameArgs::SYSCALL], cx_ref.args())
-                .await
-                .map_or_else(|e| -e.into_raw() as isize, |x| x as isize)
-                as usize;
+            // the tracer may change the syscall number and the arguments
+            // at the syscall-enter-stop.
+            if self.task.ptrace_syscall() {
+                let sysno = cx_ref[TrapFrameArgs::SYSCALL];
+                self.task.ptrace_stop(PtraceStop::Syscall(sysno)).await;
+            }
+            let sysno = cx_ref[TrapFrameArgs::SYSCALL];
+            let result = match self.syscall(sysno, cx_ref.args()).await {
+                Err(errno) if is_restart(errno) && self.restart_syscall(errno) => None,
+                Err(errno) if is_restart(errno) => Some(-Errno::EINTR.into_raw() as isize),
+                res => Some(res.map_or_else(|e| -e.into_raw() as isize, |x| x as isize)),
+            };
 
             debug!(
-                "[task {}] syscall result: {}",
+                "[task {}] syscall result: {:?}",
                 self.task.get_task_id(),
-                result as isize
+                result
             );
 
-            cx_ref[TrapFrameArgs::RET] = result;
+            match result {
+                Some(result) => cx_ref[TrapFrameArgs::RET] = result as usize,
+                // execute the syscall instruction again, the arguments are unchanged.
+                None => cx_ref[TrapFrameArgs::SEPC] -= SYSCALL_INST_LEN,
+            }
+            if self.task.ptrace_syscall() {
+                self.task.ptrace_stop(PtraceStop::Syscall(sysno)).await;
+            }
             self.task
-                .inner_map(|inner| inner.tms.stime += (Time::now().raw() - sstart) as u64);
+                .account_time(0, (Time::now().raw() - sstart) as u64);
         }
 
         // let trap_type = trap_pre_handle(cx_ref);
@@ -126,11 +162,39 @@ impl UserTaskContainer {
         // }
         UserTaskControlFlow::Continue
     }
+
+    /// Whether the syscall interrupted by the signal is restarted. It is
+    /// restarted if no handler runs, ERESTARTSYS is also restarted by
+    /// the handler with SA_RESTART.
+    fn restart_syscall(&self, errno: Errno) -> bool {
+        let Some(signal) = self.task.interrupt_signal() else {
+            return true;
+        };
+        let sigaction = self.task.sighand().lock()[signal.num()];
+        match sigaction.handler {
+            0 => signal != SignalFlags::SIGKILL,
+            _ => errno == Errno::ERESTARTSYS && sigaction.flags & SA_RESTART != 0,
+        }
+    }
+}
+
+/// The length of the syscall instruction, it is executed again when the
+/// syscall is restarted.
+#[cfg(target_arch = "x86_64")]
+const SYSCALL_INST_LEN: usize = 2;
+#[cfg(not(target_arch = "x86_64"))]
+const SYSCALL_INST_LEN: usize = 4;
+
+/// ERESTARTSYS is restarted by SA_RESTART, ERESTARTNOHAND is restarted
+/// only if no handler runs, both are EINTR if they aren't restarted.
+fn is_restart(errno: Errno) -> bool {
+    matches!(errno, Errno::ERESTARTSYS | Errno::ERESTARTNOHAND)
 }
 
 pub fn task_ilegal(task: &Arc<UserTask>, vaddr: VirtAddr, cx_ref: &mut TrapFrame) {
-    let mut pcb = task.pcb.lock();
-    let area = pcb.memset.iter_mut().find(|x| x.contains(vaddr.raw()));
+    let mm = task.mm();
+    let mut mm = mm.lock();
+    let area = mm.memset.iter_mut().find(|x| x.contains(vaddr.raw()));
     if let Some(area) = area {
         let finded = area.mtrackers.iter_mut().find(|x| x.vaddr == vaddr);
         match finded {
@@ -138,7 +202,11 @@ pub fn task_ilegal(task: &Arc<UserTask>, vaddr: VirtAddr, cx_ref: &mut TrapFrame
                 cx_ref[TrapFrameArgs::SEPC] += 2;
             }
             None => {
-                task.tcb.write().signal.add_signal(SignalFlags::SIGILL);
+                task.send_siginfo(SigInfo::fault(
+                    SignalFlags::SIGILL.num(),
+                    ILL_ILLOPC,
+                    vaddr.raw(),
+                ));
                 unsafe {
                     hexdump(
                         core::slice::from_raw_parts_mut(vaddr.raw() as _, 0x1000),
@@ -148,7 +216,11 @@ pub fn task_ilegal(task: &Arc<UserTask>, vaddr: VirtAddr, cx_ref: &mut TrapFrame
             }
         };
     } else {
-        task.tcb.write().signal.add_signal(SignalFlags::SIGILL);
+        task.send_siginfo(SigInfo::fault(
+            SignalFlags::SIGILL.num(),
+            ILL_ILLOPC,
+            vaddr.raw(),
+        ));
         unsafe {
             hexdump(
                 core::slice::from_raw_parts_mut(vaddr.raw() as _, 0x1000),
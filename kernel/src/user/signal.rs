@@ -3,16 +3,23 @@ use core::mem::size_of;
 use executor::AsyncTask;
 use log::debug;
 use polyhal_trap::trapframe::TrapFrameArgs;
-use signal::SignalFlags;
+use signal::{SigDefault, SignalFlags};
 
-use crate::syscall::types::signal::SignalUserContext;
-use crate::tasks::{current_user_task, UserTaskControlFlow};
+use crate::syscall::types::signal::{
+    SigInfo, SignalFrame, SignalStack, SignalStackFlags, SignalUserContext, SA_NODEFER, SA_ONSTACK,
+    SA_SIGINFO,
+};
+use crate::tasks::{coredump::dump_core, vdso};
 use crate::utils::useref::UserRef;
 
 use super::UserTaskContainer;
 
 impl UserTaskContainer {
-    pub async fn handle_signal(&self, signal: SignalFlags) {
+    /// Deliver the signal, the handler is run by pushing a [SignalFrame]
+    /// to the user stack and changing the context, it returns by
+    /// rt_sigreturn.
+    pub async fn handle_signal(&self, info: SigInfo) {
+        let signal = SignalFlags::from_num(info.signo as _);
         debug!(
             "handle signal: {:?} task_id: {}",
             signal,
@@ -23,20 +30,31 @@ impl UserTaskContainer {
         // the SIGKILL can't be catched and be ignored.
         if signal == SignalFlags::SIGKILL {
             self.task.exit_with_signal(signal.num());
+            return;
+        }
+        // the SIGSTOP can't be catched and be ignored too.
+        if signal == SignalFlags::SIGSTOP {
+            self.task.job_stop(signal.num());
+            return;
         }
 
         // get the signal action for the signal.
-        let sigaction = self.task.pcb.lock().sigaction[signal.num()].clone();
+        let sigaction = self.task.sighand().lock()[signal.num()];
 
         // if there doesn't have signal handler.
-        // Then use default handler. Exit or do nothing.
+        // Then use the default action of the signal.
         // SIG_ERR = -1, SIG_DEF(default) = 0, SIG_IGN = 1(ignore)
         if sigaction.handler == 0 {
-            match signal {
-                SignalFlags::SIGCANCEL | SignalFlags::SIGSEGV | SignalFlags::SIGILL => {
-                    current_user_task().exit_with_signal(signal.num());
+            match signal.default_action() {
+                SigDefault::Term => self.task.exit_with_signal(signal.num()),
+                SigDefault::Core => {
+                    if dump_core(&self.task, signal.num()).await {
+                        self.task.exit_with_signal(signal.num());
+                    }
                 }
-                _ => {}
+                SigDefault::Stop => self.task.job_stop(signal.num()),
+                // the process is continued when SIGCONT is sent.
+                SigDefault::Cont | SigDefault::Ign => {}
             }
             return;
         }
@@ -46,72 +64,65 @@ impl UserTaskContainer {
         }
 
         info!(
-            "handle signal: {:?} task: {}",
+            "handle signal: {:?} task: {} comm: {}",
             signal,
-            self.task.get_task_id()
+            self.task.get_task_id(),
+            self.task.comm()
         );
 
-        // let cx_ref = unsafe { task.get_cx_ptr().as_mut().unwrap() };
-        let cx_ref = self.task.force_cx_ref();
-        // store task_mask and context.
-        let task_mask = self.task.tcb.read().sigmask;
-        let store_cx = cx_ref.clone();
-        self.task.tcb.write().sigmask = sigaction.mask;
-
-        // alloc space for SignalUserContext at stack and align with 16 bytes.
-        let sp = (cx_ref[TrapFrameArgs::SP] - 128 - size_of::<SignalUserContext>()) / 16 * 16;
-        let cx: &mut SignalUserContext = UserRef::<SignalUserContext>::from(sp).get_mut();
-        // change task context to do the signal.
         let mut tcb = self.task.tcb.write();
-        cx.store_ctx(&cx_ref);
+        // switch to the alternate signal stack if SA_ONSTACK is set and
+        // the thread isn't running on it, SS_AUTODISARM disables it until
+        // the handler returns.
+        let altstack = tcb.sigaltstack;
+        let on_altstack = altstack.contains(tcb.cx[TrapFrameArgs::SP]);
+        let switch = sigaction.flags & SA_ONSTACK != 0
+            && !altstack.flags.contains(SignalStackFlags::DISABLE)
+            && !on_altstack;
+        if switch && altstack.flags.contains(SignalStackFlags::AUTODISARM) {
+            tcb.sigaltstack = SignalStack::disabled();
+        }
+        let stack_top = match switch {
+            true => altstack.sp + altstack.size,
+            false => tcb.cx[TrapFrameArgs::SP] - 128,
+        };
+
+        // alloc space for SignalFrame at stack and align with 16 bytes.
+        // rt_sigreturn restores the context and the mask from it.
+        let sp = (stack_top - size_of::<SignalFrame>()) / 16 * 16;
+        let frame = UserRef::<SignalFrame>::from(sp).get_mut();
+        frame.info = info;
+        let cx = &mut frame.ucontext;
+        cx.flags = 0;
+        cx.link = 0;
+        cx.store_ctx(&tcb.cx);
         cx.set_pc(tcb.cx[TrapFrameArgs::SEPC]);
-        cx.sig_mask = sigaction.mask;
+        cx.sig_mask = tcb.sigmask;
+        cx.stack = altstack;
+        if on_altstack {
+            cx.stack.flags |= SignalStackFlags::ONSTACK;
+        }
+
+        // block sa_mask and the signal itself while the handler runs.
+        tcb.sigmask.mask |= sigaction.mask.mask;
+        if sigaction.flags & SA_NODEFER == 0 {
+            tcb.sigmask.mask |= signal.bits() as usize;
+        }
+        // change task context to do the signal.
         tcb.cx[TrapFrameArgs::SP] = sp;
         tcb.cx[TrapFrameArgs::SEPC] = sigaction.handler;
+        // the handler returns to the sigreturn trampoline in the vDSO
+        // if there is no restorer. x86_64 pushes it to the stack.
         tcb.cx[TrapFrameArgs::RA] = if sigaction.restorer == 0 {
-            // SIG_RETURN_ADDR
-            // TODO: add sigreturn addr.
-            0
+            vdso::sigreturn_addr()
         } else {
             sigaction.restorer
         };
         tcb.cx[TrapFrameArgs::ARG0] = signal.num();
-        tcb.cx[TrapFrameArgs::ARG1] = 0;
-        tcb.cx[TrapFrameArgs::ARG2] = cx as *mut SignalUserContext as usize;
-        drop(tcb);
-
-        loop {
-            if let Some(exit_code) = self.task.exit_code() {
-                debug!(
-                    "program exit with code: {}  task_id: {}",
-                    exit_code,
-                    self.task.get_task_id()
-                );
-                break;
-            }
-
-            let cx_ref = self.task.force_cx_ref();
-
-            debug!(
-                "[task {}]task sepc: {:#x}",
-                self.task.get_task_id(),
-                cx_ref[TrapFrameArgs::SEPC]
-            );
-
-            if let UserTaskControlFlow::Break = self.handle_syscall(cx_ref).await {
-                break;
-            }
-        }
-        info!(
-            "handle signal: {:?} task: {} ended",
-            signal,
-            self.task.get_task_id()
-        );
-        // restore sigmask to the mask before doing the signal.
-        self.task.tcb.write().sigmask = task_mask;
-        *cx_ref = store_cx;
-        // copy pc from new_pc
-        cx_ref[TrapFrameArgs::SEPC] = cx.pc();
-        cx.restore_ctx(cx_ref);
+        tcb.cx[TrapFrameArgs::ARG1] = match sigaction.flags & SA_SIGINFO != 0 {
+            true => &frame.info as *const SigInfo as usize,
+            false => 0,
+        };
+        tcb.cx[TrapFrameArgs::ARG2] = &frame.ucontext as *const SignalUserContext as usize;
     }
 }
@@ -1,28 +1,168 @@
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use devices::get_rtc_device;
 use fs::TimeSpec;
+use log::{info, warn};
 use polyhal::Time;
+use syscalls::Errno;
 
-use crate::syscall::types::time::TimeVal;
+use crate::syscall::types::time::{
+    TimeVal, CLOCK_BOOTTIME, CLOCK_MONOTONIC, CLOCK_MONOTONIC_COARSE, CLOCK_MONOTONIC_RAW,
+    CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME, CLOCK_REALTIME_COARSE, CLOCK_THREAD_CPUTIME_ID, TMS,
+};
+use crate::tasks::vdso;
 
+/// CLOCK_REALTIME at the boot, the realtime is the offset plus the time since the boot.
+static REALTIME_OFFSET: AtomicUsize = AtomicUsize::new(0);
+
+/// The shift of the conversion from the counter to the nanoseconds.
+const CLOCK_SHIFT: u32 = 32;
+
+/// The counter and the nanoseconds when the clock source is initialized.
+static CLOCK_BASE_CYCLES: AtomicU64 = AtomicU64::new(0);
+static CLOCK_BASE_NSEC: AtomicU64 = AtomicU64::new(0);
+/// 0 before the clock source is initialized.
+static CLOCK_MULT: AtomicU64 = AtomicU64::new(0);
+
+/// The conversion from the counter to the time since the boot, it is
+/// also published in the vDSO, so the user gets the same time:
+/// ns = base_nsec + (cycles - base_cycles) * mult >> shift.
+#[derive(Debug, Clone, Copy)]
+pub struct ClockSource {
+    pub base_cycles: u64,
+    pub base_nsec: u64,
+    pub mult: u64,
+    pub shift: u32,
+}
+
+impl ClockSource {
+    /// The nanoseconds since the boot at the counter.
+    #[inline]
+    pub fn nsec(&self, cycles: u64) -> usize {
+        let delta = cycles.wrapping_sub(self.base_cycles) as u128 * self.mult as u128;
+        (self.base_nsec + (delta >> self.shift) as u64) as usize
+    }
+}
+
+/// The counter of the clock source, the vDSO reads the same counter.
+/// aarch64 uses the virtual counter, the physical one can't be read by the user.
+#[inline]
+pub fn clock_cycles() -> u64 {
+    cfg_if! {
+        if #[cfg(target_arch = "aarch64")] {
+            let cycles: u64;
+            unsafe { core::arch::asm!("isb", "mrs {}, cntvct_el0", out(reg) cycles) };
+            cycles
+        } else {
+            Time::now().raw() as u64
+        }
+    }
+}
+
+pub fn clock_source() -> ClockSource {
+    let mult = match CLOCK_MULT.load(Ordering::Relaxed) {
+        0 => ((1_000_000_000u128 << CLOCK_SHIFT) / Time::get_freq() as u128) as u64,
+        mult => mult,
+    };
+    ClockSource {
+        base_cycles: CLOCK_BASE_CYCLES.load(Ordering::Relaxed),
+        base_nsec: CLOCK_BASE_NSEC.load(Ordering::Relaxed),
+        mult,
+        shift: CLOCK_SHIFT,
+    }
+}
+
+/// Fix the base and the mult of the clock source, the time continues
+/// from the time before it.
+pub fn init_clock() {
+    let source = clock_source();
+    let cycles = clock_cycles();
+    CLOCK_BASE_NSEC.store(source.nsec(cycles) as u64, Ordering::Relaxed);
+    CLOCK_BASE_CYCLES.store(cycles, Ordering::Relaxed);
+    CLOCK_MULT.store(source.mult, Ordering::Relaxed);
+}
+
+/// The nanoseconds since the boot, the timeouts are measured by it.
 #[inline]
 pub fn current_nsec() -> usize {
-    // devices::RTC_DEVICES.lock()[0].read() as usize
-    // arch::time_to_usec(arch::get_time()) * 1000
-    Time::now().to_nsec()
+    clock_source().nsec(clock_cycles())
 }
 
-pub fn current_timeval() -> TimeVal {
-    let ns = current_nsec();
-    TimeVal {
-        sec: ns / 1_000_000_000,
-        usec: (ns % 1_000_000_000) / 1000,
+/// The nanoseconds since 1970-01-01, CLOCK_REALTIME.
+#[inline]
+pub fn realtime_nsec() -> usize {
+    REALTIME_OFFSET.load(Ordering::Relaxed) + current_nsec()
+}
+
+/// Set CLOCK_REALTIME, the time since the boot isn't changed.
+///
+/// The realtime before CLOCK_MONOTONIC can't be set, it is EINVAL like linux.
+pub fn set_realtime(ns: usize) -> Result<(), Errno> {
+    let offset = ns.checked_sub(current_nsec()).ok_or(Errno::EINVAL)?;
+    REALTIME_OFFSET.store(offset, Ordering::Relaxed);
+    vdso::update();
+    Ok(())
+}
+
+/// The offset of CLOCK_REALTIME to the time since the boot.
+pub fn realtime_offset() -> usize {
+    REALTIME_OFFSET.load(Ordering::Relaxed)
+}
+
+/// The time since the boot of the absolute CLOCK_REALTIME, it is used as the deadline.
+pub fn realtime_to_boot(ns: usize) -> usize {
+    ns.saturating_sub(REALTIME_OFFSET.load(Ordering::Relaxed))
+}
+
+/// Seed CLOCK_REALTIME from the first registered RTC, it starts at 1970 without it.
+pub fn init_realtime() {
+    let Some(rtc) = get_rtc_device() else {
+        warn!("can't find the rtc device, the realtime starts at 1970");
+        return;
+    };
+    if let Err(err) = set_realtime(rtc.read_timestamp() as usize * 1_000_000_000) {
+        warn!("can't set the realtime from the rtc: {:?}", err);
+        return;
     }
+    info!("realtime: {}s since 1970", realtime_nsec() / 1_000_000_000);
+}
+
+pub fn current_timeval() -> TimeVal {
+    TimeVal::from_nsec(realtime_nsec())
 }
 
 pub fn current_timespec() -> TimeSpec {
-    let ns = current_nsec();
+    let ns = realtime_nsec();
 
     TimeSpec {
         sec: ns / 1_000_000_000,
-        nsec: (ns % 1_000_000_000) / 1000,
+        nsec: ns % 1_000_000_000,
+    }
+}
+
+/// The resolution of the clocks, it is the period of the counter which
+/// the clocks read, not the period of the timer interrupts.
+pub fn clock_resolution() -> usize {
+    (1_000_000_000 / Time::get_freq()).max(1)
+}
+
+/// The nanoseconds of the CPU time, the times of TMS are the ticks.
+#[inline]
+pub fn cputime_nsec(ticks: u64) -> usize {
+    Time::new(ticks as _).to_nsec()
+}
+
+/// The current time of the clock, the CPU-time clocks are measured by
+/// the tms, it is the one of the thread for CLOCK_THREAD_CPUTIME_ID.
+/// None if the clock isn't supported.
+pub fn clock_nsec(clock_id: usize, tms: &TMS) -> Option<usize> {
+    match clock_id {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => Some(realtime_nsec()),
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => {
+            Some(current_nsec())
+        }
+        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => {
+            Some(cputime_nsec(tms.utime + tms.stime))
+        }
+        _ => None,
     }
 }